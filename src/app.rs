@@ -4,23 +4,39 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use adw::prelude::*;
+use formatx::formatx;
 use glib::{Object, dgettext, dpgettext2, subclass::types::ObjectSubclassIsExt};
 use gnome_app_utils::io::ensure_directory_with_parents;
-use gnome_app_utils::portal::{wallpaper, window::PortalWindowHandle};
+use gnome_app_utils::portal::window::PortalWindowHandle;
 use gtk::{
     UriLauncher,
     gio::{self, ActionEntry, ApplicationFlags},
 };
 
-use model::{ErrorNotification, ErrorNotificationActions};
-use scheduler::ScheduledWallpaperUpdate;
+use model::{ErrorNotification, ErrorNotificationActions, History};
+use scheduler::{FailedUpdate, ScheduledWallpaperUpdate, WallpaperUpdateOutcome};
 
 use crate::{
+    caption::{Caption, CaptionSettings, captioned_wallpaper},
     config::G_LOG_DOMAIN,
-    images::{Source, SourceError},
+    dark::{DarkVariant, DarkVariantSettings, dimmed_variant},
+    dimensions::{DimensionsFilter, dimensions_of_file},
+    fit::{FitMode, Size, fit_wallpaper},
+    history::HistoryEntry,
+    images::{DownloadableImage, Source, SourceError, favorites},
+    monitors::{MonitorGeometry, MonitorLayout, MultiMonitorMode, compose_wallpaper},
+    rotation::Rotation,
+    slideshow::{Slideshow, SlideshowSettings, slideshow_files},
+    target::{WallpaperTarget, WallpaperTargets},
+    wallpaper,
 };
 
+mod cleanup;
 mod model;
 mod scheduler;
 mod widgets;
@@ -35,6 +51,125 @@ glib::wrapper! {
 
 const ERROR_NOTIFICATION_ID: &str = "automatic-wallpaper-error";
 
+const FALLBACK_NOTIFICATION_ID: &str = "automatic-wallpaper-fallback";
+
+/// Settings which may fix failed wallpaper updates.
+///
+/// Changing any of these settings resumes suspended updates and forgets about
+/// previous failures.
+const SETTINGS_FIXING_UPDATES: [&str; 4] = [
+    "selected-source",
+    "apod-api-key",
+    "feed-sources",
+    "local-folder",
+];
+
+/// The geometries of all monitors in physical pixels.
+///
+/// GTK has no notion of a primary monitor, so take the first monitor as
+/// primary monitor.  Positions are only approximate if monitors have
+/// different scale factors.
+fn monitor_geometries() -> Vec<MonitorGeometry> {
+    let Some(display) = gtk::gdk::Display::default() else {
+        return Vec::new();
+    };
+    display
+        .monitors()
+        .iter::<gtk::gdk::Monitor>()
+        .filter_map(Result::ok)
+        .map(|monitor| {
+            let geometry = monitor.geometry();
+            let scale = monitor.scale_factor();
+            MonitorGeometry {
+                x: geometry.x() * scale,
+                y: geometry.y() * scale,
+                size: Size {
+                    width: geometry.width() * scale,
+                    height: geometry.height() * scale,
+                },
+            }
+        })
+        .collect()
+}
+
+/// The layout of wallpapers across all monitors as configured in `settings`.
+fn monitor_layout(settings: &gio::Settings) -> MonitorLayout {
+    MonitorLayout::new(
+        MultiMonitorMode::from_settings(settings),
+        monitor_geometries(),
+    )
+}
+
+/// Fit the wallpaper `file` to `size` according to `mode`.
+///
+/// Return `file` itself if the user wants the original image, or if we fail
+/// to fit the image.
+async fn fitted_wallpaper(file: &Path, mode: FitMode, size: Size) -> PathBuf {
+    let directory = crate::config::fitted_wallpapers_directory();
+    match fit_wallpaper(file, &directory, mode, size).await {
+        Ok(fitted) => fitted,
+        Err(error) => {
+            glib::warn!("Failed to fit {} to monitor: {error}", file.display());
+            file.to_owned()
+        }
+    }
+}
+
+/// Get the wallpaper for dark style as configured in `settings`.
+///
+/// `light` is the prepared wallpaper for light style, and `other` a different
+/// prepared image for dark style, if any.  Return `None` to use `light` in
+/// dark style as well.
+async fn dark_wallpaper(
+    settings: &gio::Settings,
+    light: &Path,
+    other: Option<&Path>,
+) -> Option<PathBuf> {
+    let dark = DarkVariantSettings::from_settings(settings);
+    match dark.variant {
+        DarkVariant::Same => None,
+        DarkVariant::OtherImage => other.map(ToOwned::to_owned),
+        DarkVariant::Dimmed => {
+            let directory = crate::config::fitted_wallpapers_directory();
+            match dimmed_variant(light, &directory, dark.dimming).await {
+                Ok(dimmed) => Some(dimmed),
+                Err(error) => {
+                    glib::warn!("Failed to dim {}: {error}", light.display());
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Separate wallpapers for light and dark style.
+#[derive(Debug, Clone)]
+struct WallpaperVariants {
+    light: PathBuf,
+    dark: PathBuf,
+    target: WallpaperTarget,
+    spanned: bool,
+}
+
+/// Whether `image` downloaded to `file` has dimensions which `filter` accepts.
+///
+/// Use the dimensions the source told us if any, or read dimensions from
+/// `file` otherwise.  Accept `image` if we can't tell its dimensions.
+async fn has_suitable_dimensions(
+    image: &DownloadableImage,
+    file: &Path,
+    filter: &DimensionsFilter,
+) -> bool {
+    if filter.accepts_all() {
+        return true;
+    }
+    let dimensions = match image.dimensions {
+        Some(dimensions) => Some(dimensions),
+        None => dimensions_of_file(file).await,
+    };
+    dimensions.is_none_or(|dimensions| filter.accepts(dimensions))
+}
+
 impl Application {
     /// Setup actions of the application.
     ///
@@ -72,21 +207,23 @@ impl Application {
                 })
                 .build(),
             ActionEntry::builder("open-source-url")
+                .parameter_type(Some(glib::VariantTy::STRING))
                 .activate(|app: &Application, _, parameter| {
-                    if let Some(source) = parameter.and_then(glib::Variant::get::<Source>) {
+                    if let Some(source) = parameter
+                        .and_then(glib::Variant::str)
+                        .and_then(|id| app.sources().lookup(id))
+                        && let Some(url) = source.url().map(ToOwned::to_owned)
+                    {
                         glib::spawn_future_local(glib::clone!(
                             #[weak]
                             app,
                             async move {
-                                if let Err(error) = UriLauncher::new(source.url())
+                                if let Err(error) = UriLauncher::new(&url)
                                     .launch_future(app.active_window().as_ref())
                                     .await
                                 {
                                     // TODO: Perhaps show this as another error notification
-                                    glib::warn!(
-                                        "Failed to launch URI of \
-                                        {source:?}: {error}"
-                                    );
+                                    glib::warn!("Failed to launch URI of {}: {error}", source.id());
                                 }
                             }
                         ));
@@ -173,6 +310,8 @@ impl Application {
     fn show_preferences(&self) -> PreferencesDialog {
         let prefs = PreferencesDialog::default();
         prefs.bind(&self.imp().settings());
+        prefs.bind_sources(&self.imp().settings(), &self.sources());
+        prefs.show_disk_usage(&self.sources());
         prefs.present(self.active_window().as_ref());
         // Restart the session monitor after the prefs dialog was closed, because
         // the user might have followed the instructions to override logind access.
@@ -244,28 +383,52 @@ it again manually.",
         dialog.present(self.active_window().as_ref());
     }
 
+    /// Get the source to update the wallpaper from today.
+    ///
+    /// Rotate through sources according to settings, and fall back to the
+    /// `selected` source.
+    fn rotated_source(&self, selected: Source) -> Source {
+        let rotation = Rotation::from_settings(&self.imp().settings());
+        let id = rotation.source_for_date(crate::date::today_local(), |n| {
+            // `random_int_range` never returns anything outside of the given
+            // range, so we can safely unwrap the conversions.
+            u32::try_from(glib::random_int_range(
+                0,
+                i32::try_from(n).unwrap_or(i32::MAX),
+            ))
+            .unwrap()
+        });
+        match id.and_then(|id| self.sources().lookup(id)) {
+            Some(source) => {
+                glib::info!("Rotating to source {}", source.id());
+                source
+            }
+            None => selected,
+        }
+    }
+
     async fn handle_scheduled_wallpaper_update(&self, scheduled_update: ScheduledWallpaperUpdate) {
-        let source = scheduled_update.source;
+        let source = self.rotated_source(scheduled_update.source);
         match gio::CancellableFuture::new(
-            self.fetch_and_set_wallpaper(source),
+            self.fetch_and_set_wallpaper(&source),
             scheduled_update.cancellable,
         )
         .await
         {
             Ok(result) => {
                 match &result {
-                    Ok(()) => {
+                    Ok(_) => {
                         // If we successfully updated the wallpaper,
                         // automatically hide any previous error notification.
                         self.withdraw_notification(ERROR_NOTIFICATION_ID);
                     }
-                    Err(error) => {
+                    Err(failure) => {
                         glib::warn!(
-                            "Failed to fetch and set \
-                             wallpaper from {source:?}: \
-                             {error}"
+                            "Failed to fetch and set wallpaper from {}: {}",
+                            failure.source.id(),
+                            failure.error
                         );
-                        self.show_error_from_automatic_wallpaper(source, error);
+                        self.show_error_from_automatic_wallpaper(&failure.source, &failure.error);
                     }
                 }
                 if scheduled_update.response.send(result).is_err() {
@@ -280,7 +443,7 @@ it again manually.",
         }
     }
 
-    fn show_error_from_automatic_wallpaper(&self, source: Source, error: &SourceError) {
+    fn show_error_from_automatic_wallpaper(&self, source: &Source, error: &SourceError) {
         let error = ErrorNotification::from_error(source, error);
         if error.needs_attention() {
             let notification = gio::Notification::new(&error.title());
@@ -300,7 +463,7 @@ it again manually.",
                     }
                     ErrorNotificationActions::OPEN_SOURCE_URL => {
                         let label = dpgettext2(None, "notification.button.label", "Open URL");
-                        (label, "app.open-source-url", Some(source.to_variant()))
+                        (label, "app.open-source-url", Some(source.id().to_variant()))
                     }
                     _ => unreachable!(),
                 };
@@ -311,42 +474,810 @@ it again manually.",
         }
     }
 
-    async fn fetch_and_set_wallpaper(&self, source: Source) -> Result<(), SourceError> {
+    /// Get images from `source` which the user did not block.
+    ///
+    /// If the user blocked all images of `source` fall back to favorites.
+    /// Return the source the images came from, and a possibly empty list of
+    /// images.
+    async fn get_unblocked_images(
+        &self,
+        source: &Source,
+    ) -> Result<(Source, Vec<DownloadableImage>), SourceError> {
         let session = self.http_session();
-        glib::info!("Setting wallpaper from {source:?}");
-        let images = source.get_images(&session, None).await?;
+        let blocked = self.imp().settings().get::<Vec<String>>("blocked-images");
+        let mut images = source.get_images(&session, None).await?;
+        images.retain(|image| !blocked.contains(&image.image_url));
+        if !images.is_empty() || source.id() == favorites::ID {
+            return Ok((source.clone(), images));
+        }
+        glib::info!(
+            "All images of {} are blocked, falling back to favorites",
+            source.id()
+        );
+        let favorites = self.sources().lookup_or_default(favorites::ID);
+        match favorites.get_images(&session, None).await {
+            Ok(images) => Ok((favorites, images)),
+            Err(SourceError::NoImage) => Ok((favorites, Vec::new())),
+            Err(error) => Err(error),
+        }
+    }
 
-        let image = if images.len() == 1 {
-            // This won't panic because  we just checked that we have one element
-            #[allow(clippy::indexing_slicing)]
-            &images[0]
-        } else {
-            // This won't panic because `get_images` never returns an empty list,
-            // never returns more images than i32::max, and we take care to
-            // generate a random index within bounds.
-            let index = glib::random_int_range(0, i32::try_from(images.len()).unwrap());
-            #[allow(clippy::indexing_slicing)]
-            &images[usize::try_from(index).unwrap()]
+    fn show_fallback_notification(&self, source: &Source, fallback: &Source) {
+        let notification = gio::Notification::new(&dpgettext2(
+            None,
+            "notification.title",
+            "Wallpaper from fallback source",
+        ));
+        notification.set_body(Some(
+            &formatx!(
+                dpgettext2(
+                    None,
+                    "notification.body",
+                    "{source_name} had no image today, so the wallpaper comes from {fallback_name} instead.",
+                ),
+                source_name = source.i18n_name(),
+                fallback_name = fallback.i18n_name()
+            )
+            .unwrap(),
+        ));
+        notification.set_priority(gio::NotificationPriority::Low);
+        self.send_notification(Some(FALLBACK_NOTIFICATION_ID), &notification);
+    }
+
+    /// Fetch an image from `source` and set it as wallpaper.
+    ///
+    /// If `source` has no image, try all fallback sources from settings in
+    /// turn, and tell the user which source we eventually used.  If no source
+    /// has an image, return the error of `source`, otherwise return the error
+    /// of the source which failed.
+    async fn fetch_and_set_wallpaper(
+        &self,
+        source: &Source,
+    ) -> Result<WallpaperUpdateOutcome, FailedUpdate> {
+        let fallbacks = self.imp().settings().get::<Vec<String>>("fallback-sources");
+        let mut source_error = None;
+        for candidate in self.sources().fallback_chain(source, fallbacks.as_slice()) {
+            match self.fetch_and_set_wallpaper_from(&candidate).await {
+                Ok(outcome) => {
+                    if source_error.is_some() {
+                        glib::info!("Used fallback source {}", candidate.id());
+                        self.show_fallback_notification(source, &candidate);
+                    } else {
+                        self.withdraw_notification(FALLBACK_NOTIFICATION_ID);
+                    }
+                    self.update_lock_screen().await;
+                    return Ok(outcome);
+                }
+                Err(error) if error.should_fall_back() => {
+                    glib::info!(
+                        "No image from {}, trying next source: {error}",
+                        candidate.id()
+                    );
+                    source_error.get_or_insert(error);
+                }
+                Err(error) => {
+                    return Err(FailedUpdate {
+                        source: candidate,
+                        error,
+                    });
+                }
+            }
+        }
+        // The chain always contains `source`, so we always have an error here
+        Err(FailedUpdate {
+            source: source.clone(),
+            error: source_error.unwrap_or(SourceError::NoImage),
+        })
+    }
+
+    async fn fetch_and_set_wallpaper_from(
+        &self,
+        source: &Source,
+    ) -> Result<WallpaperUpdateOutcome, SourceError> {
+        let session = self.http_session();
+        glib::info!("Setting wallpaper from {}", source.id());
+        let (source, images) = self.get_unblocked_images(source).await?;
+        if images.is_empty() {
+            glib::info!("No image left which the user did not block, keeping current wallpaper");
+            return Ok(WallpaperUpdateOutcome::Unchanged);
+        }
+
+        // Skip images which the source tells us are too small or have the
+        // wrong aspect ratio right away, to avoid downloading them.
+        let filter = DimensionsFilter::from_settings(&self.imp().settings());
+        let images = images
+            .into_iter()
+            .filter(|image| {
+                image
+                    .dimensions
+                    .is_none_or(|dimensions| filter.accepts(dimensions))
+            })
+            .collect::<Vec<_>>();
+        if images.is_empty() {
+            glib::info!("No image from {} has suitable dimensions", source.id());
+            return Err(SourceError::NoImage);
+        }
+
+        if SlideshowSettings::from_settings(&self.imp().settings()).enabled {
+            return self.start_slideshow(&source, &images, &filter).await;
+        }
+
+        // Start at a random image, and try the other images in turn if the
+        // image turns out to have unsuitable dimensions.
+        //
+        // This won't panic because we checked that the list is not empty, and
+        // `get_images` never returns more images than i32::max, and we take
+        // care to generate a random index within bounds.
+        let start = glib::random_int_range(0, i32::try_from(images.len()).unwrap());
+        let candidates = images
+            .iter()
+            .cycle()
+            .skip(usize::try_from(start).unwrap())
+            .take(images.len());
+
+        let history = self.history();
+        let current_url = history
+            .current_wallpaper()
+            .and_then(|file| history.image_url_for_file(&file));
+        let target_directory = source.images_directory();
+        ensure_directory_with_parents(&target_directory).await?;
+        let mut download_error = None;
+        for image in candidates {
+            if current_url.as_ref() == Some(&image.image_url) {
+                glib::info!(
+                    "Image {} is the current wallpaper already, keeping current wallpaper",
+                    image.image_url
+                );
+                return Ok(WallpaperUpdateOutcome::Unchanged);
+            }
+
+            // Try the next image if one download fails
+            let target = match image
+                .download_to_directory(&target_directory, &session)
+                .await
+            {
+                Ok(target) => target,
+                Err(error) => {
+                    glib::warn!(
+                        "Failed to download {}, trying next image: {error}",
+                        image.image_url
+                    );
+                    download_error = Some(error);
+                    continue;
+                }
+            };
+            if !has_suitable_dimensions(image, &target, &filter).await {
+                glib::info!(
+                    "Image {} has unsuitable dimensions, trying next image",
+                    image.image_url
+                );
+                continue;
+            }
+
+            let dark = self
+                .download_dark_image(&images, image, &target_directory, &filter)
+                .await;
+            let others = self
+                .download_monitor_images(
+                    &images,
+                    image,
+                    dark.as_deref(),
+                    &target_directory,
+                    &filter,
+                )
+                .await;
+            // Record the image before setting it, to caption the wallpaper
+            // from its metadata
+            let entry = HistoryEntry::new(crate::date::today_local(), image, Some(target.clone()));
+            history.record(entry.clone());
+            self.set_wallpaper(&target, dark.as_deref(), &others)
+                .await?;
+            history.record(entry.with_set_as_wallpaper());
+            cleanup::clean_up_images(&history).await;
+            return Ok(WallpaperUpdateOutcome::Updated(image.image_url.clone()));
+        }
+        if let Some(error) = download_error {
+            return Err(error.into());
+        }
+        glib::info!("No image from {} has suitable dimensions", source.id());
+        Err(SourceError::NoImage)
+    }
+
+    /// Download an image other than `light` from `images` for dark style.
+    ///
+    /// Return `None` if the user does not want a different image in dark
+    /// style, or if no other image is suitable.
+    async fn download_dark_image(
+        &self,
+        images: &[DownloadableImage],
+        light: &DownloadableImage,
+        directory: &Path,
+        filter: &DimensionsFilter,
+    ) -> Option<PathBuf> {
+        let settings = DarkVariantSettings::from_settings(&self.imp().settings());
+        if settings.variant != DarkVariant::OtherImage {
+            return None;
+        }
+        let session = self.http_session();
+        for image in images
+            .iter()
+            .filter(|image| image.image_url != light.image_url)
+        {
+            let file = match image.download_to_directory(directory, &session).await {
+                Ok(file) => file,
+                Err(error) => {
+                    glib::warn!("Failed to download {}: {error}", image.image_url);
+                    continue;
+                }
+            };
+            if has_suitable_dimensions(image, &file, filter).await {
+                self.history().record(HistoryEntry::new(
+                    crate::date::today_local(),
+                    image,
+                    Some(file.clone()),
+                ));
+                return Some(file);
+            }
+        }
+        glib::info!("No other image for dark style, using the same image");
+        None
+    }
+
+    /// Download images other than `first` from `images` for further monitors.
+    ///
+    /// Return no images unless the user wants a different image on every
+    /// monitor.  Skip the file `exclude`, e.g. the image for dark style.  Return
+    /// fewer images than monitors if `images` has not enough suitable images.
+    async fn download_monitor_images(
+        &self,
+        images: &[DownloadableImage],
+        first: &DownloadableImage,
+        exclude: Option<&Path>,
+        directory: &Path,
+        filter: &DimensionsFilter,
+    ) -> Vec<PathBuf> {
+        let layout = monitor_layout(&self.imp().settings());
+        if !layout.has_image_per_monitor() {
+            return Vec::new();
+        }
+        let count = layout.monitors().len() - 1;
+        let session = self.http_session();
+        let mut files = Vec::with_capacity(count);
+        for image in images
+            .iter()
+            .filter(|image| image.image_url != first.image_url)
+        {
+            if files.len() == count {
+                break;
+            }
+            let file = match image.download_to_directory(directory, &session).await {
+                Ok(file) => file,
+                Err(error) => {
+                    glib::warn!("Failed to download {}: {error}", image.image_url);
+                    continue;
+                }
+            };
+            if Some(file.as_path()) == exclude
+                || !has_suitable_dimensions(image, &file, filter).await
+            {
+                continue;
+            }
+            self.history().record(HistoryEntry::new(
+                crate::date::today_local(),
+                image,
+                Some(file.clone()),
+            ));
+            files.push(file);
+        }
+        if files.len() < count {
+            glib::info!("Not enough images for all monitors, repeating images");
+        }
+        files
+    }
+
+    /// Set a separate image on the lock screen, if the user wants one.
+    async fn update_lock_screen(&self) {
+        let targets = WallpaperTargets::from_settings(&self.imp().settings());
+        let Some(id) = targets.separate_lock_screen_source() else {
+            return;
+        };
+        let Some(source) = self.sources().lookup(id) else {
+            glib::warn!("Unknown lock screen source {id}, keeping lock screen");
+            return;
         };
+        if let Err(error) = self.fetch_and_set_lock_screen(&source).await {
+            glib::warn!("Failed to set lock screen from {}: {error}", source.id());
+        }
+    }
 
+    /// Fetch an image from `source` and set it on the lock screen.
+    ///
+    /// Prefer images other than the current wallpaper, to show a different
+    /// image of the day if the lock screen uses the same source as the desktop
+    /// background.
+    async fn fetch_and_set_lock_screen(&self, source: &Source) -> Result<(), SourceError> {
+        let session = self.http_session();
+        glib::info!("Setting lock screen from {}", source.id());
+        let (source, images) = self.get_unblocked_images(source).await?;
+        let history = self.history();
+        let current_url = history
+            .current_wallpaper()
+            .and_then(|file| history.image_url_for_file(&file));
+        let filter = DimensionsFilter::from_settings(&self.imp().settings());
+        let (others, current): (Vec<_>, Vec<_>) = images
+            .iter()
+            .filter(|image| {
+                image
+                    .dimensions
+                    .is_none_or(|dimensions| filter.accepts(dimensions))
+            })
+            .partition(|image| current_url.as_ref() != Some(&image.image_url));
         let target_directory = source.images_directory();
         ensure_directory_with_parents(&target_directory).await?;
-        let target = image
-            .download_to_directory(&target_directory, &session)
-            .await?;
+        let mut download_error = None;
+        for image in others.into_iter().chain(current) {
+            // Try the next image if one download fails
+            let target = match image
+                .download_to_directory(&target_directory, &session)
+                .await
+            {
+                Ok(target) => target,
+                Err(error) => {
+                    glib::warn!(
+                        "Failed to download {}, trying next image: {error}",
+                        image.image_url
+                    );
+                    download_error = Some(error);
+                    continue;
+                }
+            };
+            if !has_suitable_dimensions(image, &target, &filter).await {
+                glib::info!(
+                    "Image {} has unsuitable dimensions, trying next image",
+                    image.image_url
+                );
+                continue;
+            }
+            history.record(HistoryEntry::new(
+                crate::date::today_local(),
+                image,
+                Some(target.clone()),
+            ));
+            self.set_wallpaper_on(&target, None, &[], WallpaperTarget::LockScreen)
+                .await?;
+            return Ok(());
+        }
+        if let Some(error) = download_error {
+            return Err(error.into());
+        }
+        Err(SourceError::NoImage)
+    }
 
-        glib::info!("Setting wallpaper to {}", target.display());
-        let parent_window = PortalWindowHandle::new_for_app(self).await;
-        wallpaper::set_wallpaper_file(
-            &self.dbus_connection().unwrap(),
-            &parent_window,
-            &gio::File::for_path(&target),
-            wallpaper::Preview::NoPreview,
-            wallpaper::SetOn::Both,
+    /// Set `file` as wallpaper where automatic updates set wallpapers.
+    ///
+    /// Use `dark` for dark style, if any, and `others` for further monitors.
+    async fn set_wallpaper(
+        &self,
+        file: &Path,
+        dark: Option<&Path>,
+        others: &[PathBuf],
+    ) -> Result<(), SourceError> {
+        let target = WallpaperTargets::from_settings(&self.imp().settings()).automatic_target();
+        self.set_wallpaper_on(file, dark, others, target).await
+    }
+
+    async fn set_wallpaper_on(
+        &self,
+        file: &Path,
+        dark: Option<&Path>,
+        others: &[PathBuf],
+        target: WallpaperTarget,
+    ) -> Result<(), SourceError> {
+        self.set_wallpaper_files(
+            file,
+            dark,
+            others,
+            target,
+            PortalWindowHandle::new_for_app(self),
         )
         .await?;
         Ok(())
     }
+
+    /// Render a caption onto `fitted`, if the user wants one.
+    ///
+    /// Take title, source and copyright for the caption from the history entry
+    /// of `file`, which is the original image of `fitted`.  Return `fitted`
+    /// itself if the user wants no caption, if `file` is not in history, or if
+    /// we failed to render the caption.
+    async fn add_caption(&self, settings: &gio::Settings, file: &Path, fitted: PathBuf) -> PathBuf {
+        let caption_settings = CaptionSettings::from_settings(settings);
+        if !caption_settings.enabled {
+            return fitted;
+        }
+        let Some(metadata) = self.history().metadata_for_file(file) else {
+            glib::debug!("No metadata for {}, not adding caption", file.display());
+            return fitted;
+        };
+        let source_name = self.sources().lookup(&metadata.source).map_or_else(
+            || metadata.source.clone(),
+            |source| source.i18n_name().into(),
+        );
+        let caption = Caption::new(&metadata, &source_name);
+        let directory = crate::config::fitted_wallpapers_directory();
+        match captioned_wallpaper(&fitted, &directory, caption, caption_settings).await {
+            Ok(captioned) => captioned,
+            Err(error) => {
+                glib::warn!("Failed to add caption to {}: {error}", fitted.display());
+                fitted
+            }
+        }
+    }
+
+    /// Prepare `file` as wallpaper for the monitors of `layout`.
+    ///
+    /// Fit `file` to the primary monitor, or to all monitors if the wallpaper
+    /// spans all monitors, and add a caption if the user wants one.  Compose
+    /// `file` and `others` into one wallpaper if the user wants a different
+    /// image on every monitor.
+    async fn prepared_wallpaper(
+        &self,
+        settings: &gio::Settings,
+        layout: &MonitorLayout,
+        file: &Path,
+        others: &[PathBuf],
+    ) -> PathBuf {
+        if layout.has_image_per_monitor() {
+            return self
+                .composed_wallpaper(settings, layout, file, others)
+                .await;
+        }
+        let mode = FitMode::from_settings(settings);
+        let size = if layout.is_spanned() {
+            layout.spanning_size()
+        } else {
+            layout.primary_size()
+        };
+        let fitted = if mode == FitMode::Original {
+            file.to_owned()
+        } else if let Some(size) = size {
+            fitted_wallpaper(file, mode, size).await
+        } else {
+            glib::warn!("No monitor to fit wallpaper to");
+            file.to_owned()
+        };
+        self.add_caption(settings, file, fitted).await
+    }
+
+    /// Compose `file` and `others` into one wallpaper across all monitors of `layout`.
+    ///
+    /// Crop or letterbox every image to its monitor, and add a caption to
+    /// every image if the user wants one.  Repeat images if there are fewer
+    /// images than monitors.  Return `file` prepared for the primary monitor
+    /// if we failed to compose the wallpaper.
+    async fn composed_wallpaper(
+        &self,
+        settings: &gio::Settings,
+        layout: &MonitorLayout,
+        file: &Path,
+        others: &[PathBuf],
+    ) -> PathBuf {
+        // Every image must cover its monitor exactly, so we can't keep the
+        // original image, or only scale it.
+        let mode = match FitMode::from_settings(settings) {
+            FitMode::Letterbox => FitMode::Letterbox,
+            _ => FitMode::Crop,
+        };
+        let files = std::iter::once(file)
+            .chain(others.iter().map(PathBuf::as_path))
+            .cycle();
+        let mut tiles = Vec::with_capacity(layout.monitors().len());
+        for (monitor, file) in layout.monitors().iter().zip(files) {
+            let fitted = fitted_wallpaper(file, mode, monitor.size).await;
+            tiles.push(self.add_caption(settings, file, fitted).await);
+        }
+        let directory = crate::config::fitted_wallpapers_directory();
+        match compose_wallpaper(&tiles, layout, &directory).await {
+            Ok(composed) => composed,
+            Err(error) => {
+                glib::warn!("Failed to compose wallpaper for all monitors: {error}");
+                tiles.into_iter().next().unwrap_or_else(|| file.to_owned())
+            }
+        }
+    }
+
+    /// Set `file` as wallpaper on `target`, with `dark` for dark style.
+    ///
+    /// Show `others` on further monitors, if the user wants a different image
+    /// on every monitor; repeat images if there are more monitors than images.
+    ///
+    /// Fit the wallpaper to the monitor, add a caption if the user wants one,
+    /// and dim it for dark style if the user wants a dimmed variant.  If the
+    /// wallpaper backend can't set separate wallpapers for light and dark
+    /// style, set the variant for the current style, and switch variants when
+    /// the style changes.
+    ///
+    /// Use `parent_window` for the wallpaper portal.
+    pub async fn set_wallpaper_files<F>(
+        &self,
+        file: &Path,
+        dark: Option<&Path>,
+        others: &[PathBuf],
+        target: WallpaperTarget,
+        parent_window: F,
+    ) -> Result<(), glib::Error>
+    where
+        F: Future<Output = PortalWindowHandle>,
+    {
+        let settings = self.imp().settings();
+        let mut layout = monitor_layout(&settings);
+        let setter = wallpaper::setter_from_settings(
+            &settings,
+            &self.dbus_connection().unwrap(),
+            parent_window,
+            layout.is_spanned(),
+        )
+        .await;
+        if layout.is_spanned() && !setter.supports_spanning() {
+            glib::warn!(
+                "Backend {} can't span wallpapers across monitors, showing the same image on every monitor",
+                setter.id()
+            );
+            layout.mode = MultiMonitorMode::Same;
+        }
+        let light = self
+            .prepared_wallpaper(&settings, &layout, file, others)
+            .await;
+        // Show the other image for dark style on every monitor
+        let other = match dark {
+            Some(dark) => Some(self.prepared_wallpaper(&settings, &layout, dark, &[]).await),
+            None => None,
+        };
+        let dark_variant = dark_wallpaper(&settings, &light, other.as_deref()).await;
+        // The downloaded images and the wallpapers prepared from them which
+        // we're about to set, which clean up must keep
+        let applied = std::iter::once(file)
+            .chain(dark)
+            .chain(others.iter().map(PathBuf::as_path))
+            .chain([light.as_path()])
+            .chain(dark_variant.as_deref())
+            .collect::<Vec<_>>();
+        let Some(dark) = dark_variant.as_deref() else {
+            if target.includes_background() {
+                self.imp().wallpaper_variants.take();
+            }
+            wallpaper::set_wallpaper(setter.as_ref(), &light, target).await?;
+            cleanup::remember_applied_files(&settings, target, &applied);
+            cleanup::clean_up_fitted_wallpapers().await;
+            return Ok(());
+        };
+        let dark_style = adw::StyleManager::default().is_dark();
+        wallpaper::set_wallpaper_variants(setter.as_ref(), &light, dark, target, dark_style)
+            .await?;
+        cleanup::remember_applied_files(&settings, target, &applied);
+        cleanup::clean_up_fitted_wallpapers().await;
+        // Only follow style changes on the desktop background; the lock
+        // screen is never visible together with the desktop anyway.
+        if target.includes_background() {
+            let variants = (!setter.supports_dark_variant()).then_some(WallpaperVariants {
+                light,
+                dark: dark.to_owned(),
+                target,
+                spanned: layout.is_spanned(),
+            });
+            self.imp().wallpaper_variants.replace(variants);
+        }
+        Ok(())
+    }
+
+    /// Switch the wallpaper to the variant for the current style.
+    ///
+    /// Do nothing if the current wallpaper has no separate variants, or if
+    /// the wallpaper backend switches variants by itself.
+    async fn switch_wallpaper_variant(&self) {
+        let Some(variants) = self.imp().wallpaper_variants.borrow().clone() else {
+            return;
+        };
+        let file = if adw::StyleManager::default().is_dark() {
+            &variants.dark
+        } else {
+            &variants.light
+        };
+        let setter = wallpaper::setter_from_settings(
+            &self.imp().settings(),
+            &self.dbus_connection().unwrap(),
+            PortalWindowHandle::new_for_app(self),
+            variants.spanned,
+        )
+        .await;
+        if let Err(error) = wallpaper::set_wallpaper(setter.as_ref(), file, variants.target).await {
+            glib::warn!("Failed to switch wallpaper to {}: {error}", file.display());
+        }
+    }
+
+    /// Download all `images` of `source`, and cycle through all recent images.
+    ///
+    /// Set the first image as wallpaper right away, and then switch to the
+    /// next image at the interval from settings.  Report an unchanged
+    /// wallpaper if we had downloaded all `images` today already.
+    async fn start_slideshow(
+        &self,
+        source: &Source,
+        images: &[DownloadableImage],
+        filter: &DimensionsFilter,
+    ) -> Result<WallpaperUpdateOutcome, SourceError> {
+        let settings = SlideshowSettings::from_settings(&self.imp().settings());
+        let blocked = self.imp().settings().get::<Vec<String>>("blocked-images");
+        let session = self.http_session();
+        let history = self.history();
+        let target_directory = source.images_directory();
+        ensure_directory_with_parents(&target_directory).await?;
+
+        let today = crate::date::today_local();
+        let known = history.entries();
+        let mut new_image_url = None;
+        let mut download_error = None;
+        for image in images {
+            match image
+                .download_to_directory(&target_directory, &session)
+                .await
+            {
+                Ok(file) => {
+                    if !has_suitable_dimensions(image, &file, filter).await {
+                        glib::info!(
+                            "Image {} has unsuitable dimensions, skipping",
+                            image.image_url
+                        );
+                        continue;
+                    }
+                    let is_new = !known.iter().any(|entry| {
+                        entry.date == today
+                            && entry.image_url == image.image_url
+                            && entry.file.is_some()
+                    });
+                    if is_new && new_image_url.is_none() {
+                        new_image_url = Some(image.image_url.clone());
+                    }
+                    history.record(HistoryEntry::new(today, image, Some(file)));
+                }
+                Err(error) => {
+                    glib::warn!("Failed to download {}: {error}", image.image_url);
+                    download_error = Some(error);
+                }
+            }
+        }
+
+        let slideshow = Slideshow::new(slideshow_files(
+            &history.entries(),
+            source.id(),
+            &blocked,
+            settings.since(today),
+        ));
+        let Some(first) = slideshow.current().map(ToOwned::to_owned) else {
+            return Err(download_error.map_or(SourceError::NoImage, SourceError::from));
+        };
+        self.set_wallpaper(&first, None, &[]).await?;
+        history.mark_set_as_wallpaper(&first);
+        self.imp().slideshow.replace(slideshow);
+        self.schedule_slideshow(settings.interval);
+        cleanup::clean_up_images(&history).await;
+        Ok(new_image_url.map_or(
+            WallpaperUpdateOutcome::Unchanged,
+            WallpaperUpdateOutcome::Updated,
+        ))
+    }
+
+    /// Resume the slideshow through the images of the current wallpaper.
+    ///
+    /// Rebuild the slideshow from history after a restart, if the user
+    /// enabled slideshows, and continue after the current wallpaper.
+    fn resume_slideshow(&self) {
+        let settings = self.imp().settings();
+        let slideshow_settings = SlideshowSettings::from_settings(&settings);
+        if !slideshow_settings.enabled {
+            return;
+        }
+        let history = self.history();
+        let Some(current) = history.current_wallpaper() else {
+            return;
+        };
+        let Some(source) = history
+            .metadata_for_file(&current)
+            .map(|metadata| metadata.source)
+        else {
+            return;
+        };
+        let blocked = settings.get::<Vec<String>>("blocked-images");
+        let files = slideshow_files(
+            &history.entries(),
+            &source,
+            &blocked,
+            slideshow_settings.since(crate::date::today_local()),
+        );
+        if files.is_empty() {
+            glib::info!("No recent images from {source} to resume slideshow");
+            return;
+        }
+        glib::info!(
+            "Resuming slideshow through {} images from {source}",
+            files.len()
+        );
+        self.imp()
+            .slideshow
+            .replace(Slideshow::starting_at(files, &current));
+        self.schedule_slideshow(slideshow_settings.interval);
+    }
+
+    /// Switch to the next image of the slideshow every `interval`.
+    fn schedule_slideshow(&self, interval: Duration) {
+        self.stop_slideshow();
+        glib::info!("Switching to next slideshow image every {interval:?}");
+        let seconds = u32::try_from(interval.as_secs())
+            .unwrap_or(u32::MAX)
+            .max(60);
+        let source_id = glib::timeout_add_seconds_local(
+            seconds,
+            glib::clone!(
+                #[weak(rename_to = app)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    app.advance_slideshow();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+        self.imp().slideshow_timeout.replace(Some(source_id));
+    }
+
+    fn stop_slideshow(&self) {
+        if let Some(source_id) = self.imp().slideshow_timeout.take() {
+            glib::info!("Stopping slideshow");
+            source_id.remove();
+        }
+    }
+
+    /// Set the next image of the slideshow as wallpaper.
+    ///
+    /// Pause while automatic updates are inhibited.
+    fn advance_slideshow(&self) {
+        if self.imp().automatic_updates_inhibited() {
+            glib::debug!("Automatic updates inhibited, pausing slideshow");
+            return;
+        }
+        let Some(file) = self
+            .imp()
+            .slideshow
+            .borrow_mut()
+            .advance()
+            .map(ToOwned::to_owned)
+        else {
+            return;
+        };
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = app)]
+            self,
+            async move {
+                match app.set_wallpaper(&file, None, &[]).await {
+                    Ok(()) => app.history().mark_set_as_wallpaper(&file),
+                    Err(error) => {
+                        glib::warn!("Failed to set {} as wallpaper: {error}", file.display());
+                    }
+                }
+            }
+        ));
+    }
+
+    /// Apply changed slideshow `settings`.
+    fn update_slideshow(&self, settings: &gio::Settings) {
+        let settings = SlideshowSettings::from_settings(settings);
+        if !settings.enabled {
+            self.stop_slideshow();
+        } else if self.imp().slideshow_timeout.borrow().is_some() {
+            self.schedule_slideshow(settings.interval);
+        }
+    }
 }
 
 impl Default for Application {
@@ -374,10 +1305,15 @@ mod imp {
     use jiff::civil::Date;
     use soup::prelude::*;
 
+    use super::WallpaperVariants;
+    use super::model::History;
     use super::scheduler::AutomaticWallpaperUpdateScheduler;
     use crate::{
         app::{scheduler::AutomaticWallpaperUpdateInhibitor, widgets::ApplicationWindow},
         config::G_LOG_DOMAIN,
+        images::{SourceRegistry, feed},
+        schedule::UpdateSchedule,
+        slideshow::Slideshow,
     };
 
     #[derive(Default, Properties)]
@@ -387,6 +1323,12 @@ mod imp {
         http_session: soup::Session,
         #[property(get)]
         settings: RefCell<Option<gio::Settings>>,
+        /// All available sources.
+        #[property(get)]
+        sources: SourceRegistry,
+        /// The history of fetched images and wallpapers.
+        #[property(get)]
+        history: History,
         /// The overridden date, if any.
         ///
         /// Set if the user specified --date on the command line.
@@ -399,9 +1341,21 @@ mod imp {
         updated_monitor: AppUpdatedMonitor,
         /// Hold on to ourselves while automatic wallpaper updates are scheduled
         pub scheduled_updates_hold: RefCell<Option<ApplicationHoldGuard>>,
+        /// The current slideshow of wallpapers.
+        pub slideshow: RefCell<Slideshow>,
+        /// The timeout to switch to the next image of the slideshow.
+        pub slideshow_timeout: RefCell<Option<glib::SourceId>>,
+        /// Variants of the current wallpaper to switch between as the style
+        /// changes.
+        pub wallpaper_variants: RefCell<Option<WallpaperVariants>>,
     }
 
     impl Application {
+        /// Whether automatic wallpaper updates are currently inhibited.
+        pub fn automatic_updates_inhibited(&self) -> bool {
+            !self.scheduler.inhibitors().is_empty()
+        }
+
         pub fn settings(&self) -> gio::Settings {
             self.settings.borrow().as_ref().unwrap().clone()
         }
@@ -574,10 +1528,56 @@ mod imp {
                 }
             });
 
+            // Apply changes to the slideshow
+            settings.connect_changed(
+                None,
+                glib::clone!(
+                    #[weak(rename_to = app)]
+                    self.obj(),
+                    move |settings, key| {
+                        if key.starts_with("slideshow") {
+                            app.update_slideshow(settings);
+                        }
+                    }
+                ),
+            );
+
+            // Update wallpapers according to the schedule in settings
+            settings.connect_changed(
+                None,
+                glib::clone!(
+                    #[weak(rename_to = scheduler)]
+                    self.scheduler,
+                    move |settings, key| {
+                        if key.starts_with("update-schedule") {
+                            scheduler.set_schedule(UpdateSchedule::from_settings(settings));
+                        }
+                        // The user may have fixed whatever made updates fail,
+                        // e.g. by changing the API key, so try again.
+                        if SETTINGS_FIXING_UPDATES.contains(&key) {
+                            scheduler.resume_after_settings_changed();
+                        }
+                    }
+                ),
+            );
+            self.scheduler
+                .set_schedule(UpdateSchedule::from_settings(settings));
+            self.scheduler.persist_last_update(settings);
+
             // Finally, update the source for scheduled wallpaper updates.
             // This implicit starts scheduled updates.
             settings
                 .bind("selected-source", &self.scheduler, "source")
+                .get_only()
+                .mapping(glib::clone!(
+                    #[weak(rename_to = sources)]
+                    self.sources,
+                    #[upgrade_or_default]
+                    move |value, _| {
+                        let source = sources.lookup_or_default(value.str()?);
+                        Some(source.to_value())
+                    }
+                ))
                 .build();
         }
 
@@ -676,6 +1676,32 @@ mod imp {
             let settings = crate::config::get_settings();
             self.settings.replace(Some(settings.clone()));
 
+            glib::info!("Loading feed sources");
+            self.sources.set_feeds(feed::feeds_from_settings(&settings));
+            settings.connect_changed(
+                Some("feed-sources"),
+                glib::clone!(
+                    #[weak(rename_to = sources)]
+                    self.sources,
+                    move |settings, _| {
+                        sources.set_feeds(feed::feeds_from_settings(settings));
+                    }
+                ),
+            );
+
+            glib::info!("Loading history");
+            glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = app)]
+                self.obj(),
+                async move {
+                    let history = app.history();
+                    let file = gio::File::for_path(crate::config::history_file());
+                    history.load_from(file).await;
+                    app.resume_slideshow();
+                    super::cleanup::clean_up_images(&history).await;
+                }
+            ));
+
             glib::info!(
                 "Initializing soup session with user agent {}",
                 crate::config::USER_AGENT
@@ -708,6 +1734,17 @@ mod imp {
             glib::info!("Monitoring session lock");
             self.restart_session_monitor();
 
+            glib::info!("Monitoring dark style");
+            adw::StyleManager::default().connect_dark_notify(glib::clone!(
+                #[weak(rename_to = app)]
+                self.obj(),
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        app.switch_wallpaper_variant().await;
+                    });
+                }
+            ));
+
             glib::info!("Configuring automatic updates");
             self.setup_scheduled_wallpaper_updates(&settings);
 
@@ -754,7 +1791,18 @@ mod imp {
                 });
                 ExitCode::SUCCESS
             } else if let Ok(Some(date)) = options.lookup::<String>("date") {
+                let source = self
+                    .sources
+                    .lookup_or_default(&self.settings().string("selected-source"));
                 match jiff::civil::Date::from_str(&date) {
+                    Ok(_) if !source.supports_date() => {
+                        command_line.printerr_literal(&format!(
+                            "Source {} does not provide images for other dates than today",
+                            source.id()
+                        ));
+                        command_line.set_exit_status(ExitCode::FAILURE.into());
+                        ExitCode::FAILURE
+                    }
                     Ok(date) => {
                         glib::warn!("Overriding date to {date}");
                         self.date.replace(Some(date));
@@ -783,6 +1831,8 @@ mod imp {
                 glib::debug!("Creating new window");
                 let window = ApplicationWindow::new(
                     &*self.obj(),
+                    &self.sources,
+                    &self.history,
                     &self.obj().http_session(),
                     &self.obj().dbus_connection().unwrap(),
                     self.date.get(),
@@ -792,7 +1842,12 @@ mod imp {
                 }
 
                 let settings = self.settings();
-                for setting in ["selected-source", "set-wallpaper-automatically"] {
+                for setting in [
+                    "selected-source",
+                    "set-wallpaper-automatically",
+                    "favorite-images",
+                    "blocked-images",
+                ] {
                     settings.bind(setting, &window, setting).build();
                 }
                 settings
@@ -0,0 +1,173 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Clean up downloaded images according to the retention policy, and old
+//! fitted wallpapers.
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use gtk::gio::{self, prelude::SettingsExtManual};
+
+use crate::app::model::History;
+use crate::config::G_LOG_DOMAIN;
+use crate::fit::delete_old_fitted_wallpapers;
+use crate::history::favorite_entries;
+use crate::retention::{
+    RetentionPolicy, list_image_files, list_stale_partial_downloads, plan_cleanup,
+};
+use crate::target::WallpaperTarget;
+
+thread_local! {
+    /// Whether we're currently cleaning up images.
+    static CLEANING_UP: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks a clean up in progress while alive.
+///
+/// Resets the mark when dropped, even if the clean up future was cancelled.
+struct CleanUpGuard;
+
+impl CleanUpGuard {
+    fn acquire() -> Option<Self> {
+        if CLEANING_UP.replace(true) {
+            None
+        } else {
+            Some(Self)
+        }
+    }
+}
+
+impl Drop for CleanUpGuard {
+    fn drop(&mut self) {
+        CLEANING_UP.set(false);
+    }
+}
+
+/// Remember `files` as the images currently set on `target`.
+///
+/// `files` includes downloaded images as well as wallpapers prepared from
+/// them.  Replace the files previously set on `target`, and keep the files on
+/// the other target.
+pub fn remember_applied_files(settings: &gio::Settings, target: WallpaperTarget, files: &[&Path]) {
+    let files = files
+        .iter()
+        .map(|file| file.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let (mut background, mut lock_screen) =
+        settings.get::<(Vec<String>, Vec<String>)>("applied-images");
+    if target.includes_background() {
+        background.clone_from(&files);
+    }
+    if target.includes_lock_screen() {
+        lock_screen = files;
+    }
+    if let Err(error) = settings.set("applied-images", (background, lock_screen)) {
+        glib::warn!("Failed to remember applied images: {error}");
+    }
+}
+
+/// All files currently set on the desktop background or the lock screen.
+fn applied_files(settings: &gio::Settings) -> Vec<PathBuf> {
+    let (background, lock_screen) = settings.get::<(Vec<String>, Vec<String>)>("applied-images");
+    background
+        .into_iter()
+        .chain(lock_screen)
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Files we must never delete.
+///
+/// Protect all favorites, the current wallpaper, and all images currently set
+/// on the desktop background or the lock screen, including images for dark
+/// style and for other monitors.
+fn protected_files(history: &History) -> Vec<PathBuf> {
+    let settings = crate::config::get_settings();
+    let favorites = settings.get::<Vec<String>>("favorite-images");
+    let entries = history.entries();
+    let mut protected = favorite_entries(&entries, favorites.as_slice())
+        .into_iter()
+        .filter_map(|entry| entry.file.clone())
+        .collect::<Vec<_>>();
+    if let Some(wallpaper) = history.current_wallpaper() {
+        protected.push(wallpaper);
+    }
+    protected.extend(applied_files(&settings));
+    protected
+}
+
+/// Delete all `paths`, and return the paths we deleted.
+fn delete_files(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .filter(|path| match std::fs::remove_file(path) {
+            Ok(()) => true,
+            Err(error) => {
+                glib::warn!("Failed to delete {}: {error}", path.display());
+                false
+            }
+        })
+        .collect()
+}
+
+/// Delete downloaded images according to the retention policy in settings.
+///
+/// Never delete favorites or the current wallpaper, as recorded in `history`,
+/// nor any other image currently set on the desktop background or the lock
+/// screen, see [`remember_applied_files`].  Remove deleted files from
+/// `history`.  Also delete partial downloads which did not change for a
+/// while.  Do nothing if a clean up is already in progress.
+pub async fn clean_up_images(history: &History) {
+    let policy = RetentionPolicy::from_settings(&crate::config::get_settings());
+    let Some(_guard) = CleanUpGuard::acquire() else {
+        return;
+    };
+    let protected = protected_files(history);
+    let directory = crate::config::images_directory();
+    let result = gio::spawn_blocking(move || {
+        let now = SystemTime::now();
+        let stale = delete_files(list_stale_partial_downloads(&directory, now)?);
+        if !stale.is_empty() {
+            glib::info!("Deleted {} stale partial downloads", stale.len());
+        }
+        if policy.keeps_everything() {
+            return Ok(Vec::new());
+        }
+        let files = list_image_files(&directory)?;
+        let deleted = delete_files(plan_cleanup(&files, &policy, &protected, now));
+        std::io::Result::Ok(deleted)
+    })
+    .await;
+    match result {
+        Ok(Ok(deleted)) if deleted.is_empty() => glib::debug!("No images to clean up"),
+        Ok(Ok(deleted)) => {
+            glib::info!(
+                "Deleted {} images according to retention policy",
+                deleted.len()
+            );
+            history.forget_files(&deleted);
+        }
+        Ok(Err(error)) => glib::warn!("Failed to clean up images: {error}"),
+        Err(_) => glib::warn!("Cleaning up images panicked"),
+    }
+}
+
+/// Delete old wallpapers fitted to the monitor.
+///
+/// This includes wallpapers with captions, dimmed wallpapers and wallpapers
+/// composed for multiple monitors.  Never delete wallpapers currently set on
+/// the desktop background or the lock screen, see [`remember_applied_files`].
+pub async fn clean_up_fitted_wallpapers() {
+    let protected = applied_files(&crate::config::get_settings());
+    let directory = crate::config::fitted_wallpapers_directory();
+    let result =
+        gio::spawn_blocking(move || delete_old_fitted_wallpapers(&directory, &protected)).await;
+    if result.is_err() {
+        glib::warn!("Cleaning up fitted wallpapers panicked");
+    }
+}
@@ -5,7 +5,9 @@
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
 mod error_notification;
+mod history;
 mod image;
 
 pub use error_notification::{ErrorNotification, ErrorNotificationActions};
+pub use history::History;
 pub use image::{Image, ImageState};
@@ -35,7 +35,7 @@ mod errors {
 
     use super::{ErrorNotification, ErrorNotificationActions};
 
-    pub fn invalid_api_key(source: Source) -> ErrorNotification {
+    pub fn invalid_api_key(source: &Source) -> ErrorNotification {
         let title = dpgettext2(
             None,
             "error-notification.title",
@@ -59,7 +59,7 @@ application preferences. The current key appears to be invalid.",
             .build()
     }
 
-    pub fn rate_limited(source: Source) -> ErrorNotification {
+    pub fn rate_limited(source: &Source) -> ErrorNotification {
         let title = dpgettext2(
             None,
             "error-notification.title",
@@ -81,7 +81,7 @@ allow you to fetch an image. Try again later, or try a different source.",
             .build()
     }
 
-    pub fn no_image(source: Source) -> ErrorNotification {
+    pub fn no_image(source: &Source) -> ErrorNotification {
         let title = dpgettext2(None, "error-notification.title", "No image today");
         let description = formatx!(
             dpgettext2(
@@ -99,7 +99,7 @@ Try again later, or try a different source.",
             .build()
     }
 
-    pub fn not_an_image(source: Source) -> ErrorNotification {
+    pub fn not_an_image(source: &Source) -> ErrorNotification {
         let title = dpgettext2(None, "error-notification.title", "No image today");
         let description = formatx!(
             dpgettext2(
@@ -112,16 +112,59 @@ different source.",
             source_name = source.i18n_name()
         )
         .unwrap();
+        // Sources without homepage have no URL to open
+        let actions = if source.url().is_some() {
+            ErrorNotificationActions::OPEN_SOURCE_URL
+        } else {
+            ErrorNotificationActions::NONE
+        };
         ErrorNotification::builder()
             .title(title)
             .description(description)
-            .actions(ErrorNotificationActions::OPEN_SOURCE_URL)
+            .actions(actions)
             // Needs attention because the user can navigate to the source site and e.g. watch the video
             .needs_attention()
             .build()
     }
 
-    pub fn http_status(source: Source, status: soup::Status) -> ErrorNotification {
+    pub fn date_not_supported(source: &Source) -> ErrorNotification {
+        let title = dpgettext2(None, "error-notification.title", "Date not supported");
+        let description = formatx!(
+            dpgettext2(
+                None,
+                "error-notification.description",
+                "{source_name} only provides images for today. Choose a \
+different source to show images for other dates.",
+            ),
+            source_name = source.i18n_name()
+        )
+        .unwrap();
+        ErrorNotification::builder()
+            .title(title)
+            .description(description)
+            .build()
+    }
+
+    pub fn not_configured(source: &Source) -> ErrorNotification {
+        let title = dpgettext2(None, "error-notification.title", "Source not configured");
+        let description = formatx!(
+            dpgettext2(
+                None,
+                "error-notification.description",
+                "Please configure {source_name} in the application preferences.",
+            ),
+            source_name = source.i18n_name()
+        )
+        .unwrap();
+        ErrorNotification::builder()
+            .title(title)
+            .description(description)
+            .actions(ErrorNotificationActions::OPEN_PREFERENCES)
+            .needs_attention()
+            .build()
+    }
+
+    pub fn http_status(source: &Source, status: soup::Status) -> ErrorNotification {
         let title = dpgettext2(None, "error-notification.title", "Fetching images failed");
         let description = formatx!(
             dpgettext2(
@@ -142,7 +185,7 @@ source. If the issue persists please report the problem.",
             .build()
     }
 
-    pub fn invalid_data(source: Source) -> ErrorNotification {
+    pub fn invalid_data(source: &Source) -> ErrorNotification {
         let title = dpgettext2(None, "error-notification.title", "Fetching images failed");
         let description = formatx!(
             dpgettext2(
@@ -162,7 +205,7 @@ issue persists please report the problem.",
             .build()
     }
 
-    pub fn io_error(source: Source, error: &glib::Error) -> ErrorNotification {
+    pub fn io_error(source: &Source, error: &glib::Error) -> ErrorNotification {
         let connectivity = gio::NetworkMonitor::default().connectivity();
         let (title, description) = if connectivity == gio::NetworkConnectivity::Full {
             let title = dpgettext2(
@@ -212,13 +255,15 @@ impl ErrorNotification {
         ErrorNotificationBuilder::default()
     }
 
-    pub fn from_error(source: Source, error: &SourceError) -> Self {
+    pub fn from_error(source: &Source, error: &SourceError) -> Self {
         match error {
             SourceError::InvalidApiKey => errors::invalid_api_key(source),
-            SourceError::RateLimited => errors::rate_limited(source),
+            SourceError::RateLimited(_) => errors::rate_limited(source),
             SourceError::NoImage => errors::no_image(source),
             SourceError::NotAnImage => errors::not_an_image(source),
-            SourceError::HttpStatus(status, _) => errors::http_status(source, *status),
+            SourceError::DateNotSupported => errors::date_not_supported(source),
+            SourceError::NotConfigured => errors::not_configured(source),
+            SourceError::HttpStatus(status, _, _) => errors::http_status(source, *status),
             SourceError::InvalidJson(_)
             | SourceError::ScrapingFailed(_)
             | SourceError::InvalidRss(_) => errors::invalid_data(source),
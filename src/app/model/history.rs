@@ -0,0 +1,201 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+use std::path::{Path, PathBuf};
+
+use glib::subclass::types::ObjectSubclassIsExt;
+use gnome_app_utils::io::ensure_directory_with_parents;
+use gtk::gio::{self, FileCreateFlags, IOErrorEnum, prelude::*};
+
+use crate::config::G_LOG_DOMAIN;
+use crate::history::HistoryEntry;
+use crate::images::ImageMetadata;
+
+glib::wrapper! {
+    /// The persistent history of images.
+    ///
+    /// Wraps [`crate::history::History`], and saves the history after every
+    /// change.
+    pub struct History(ObjectSubclass<imp::History>);
+}
+
+impl History {
+    /// Load history from `file`, and save all subsequent changes to `file`.
+    ///
+    /// Merge entries recorded before loading into the loaded history.  If
+    /// `file` does not contain a valid history, move it out of the way, and
+    /// start with an empty history.
+    pub async fn load_from(&self, file: gio::File) {
+        match file.load_contents_future().await {
+            Ok((data, _)) => match crate::history::History::from_json(&data) {
+                Ok(loaded) => {
+                    glib::info!(
+                        "Loaded {} history entries from {}",
+                        loaded.entries().len(),
+                        file.uri()
+                    );
+                    let mut history = self.imp().history.borrow_mut();
+                    let recorded = std::mem::replace(&mut *history, loaded);
+                    history.merge(recorded);
+                }
+                Err(error) => {
+                    glib::warn!("Invalid history in {}: {error}", file.uri());
+                    if let Some(path) = file.path() {
+                        let backup = path.with_extension("json.invalid");
+                        if let Err(error) = std::fs::rename(&path, &backup) {
+                            glib::warn!(
+                                "Failed to move invalid history to {}: {error}",
+                                backup.display()
+                            );
+                        }
+                    }
+                }
+            },
+            Err(error) if error.matches(IOErrorEnum::NotFound) => {
+                glib::info!("No history at {} yet", file.uri());
+            }
+            Err(error) => {
+                glib::warn!("Failed to load history from {}: {error}", file.uri());
+            }
+        }
+        self.imp().file.replace(Some(file));
+        if self.imp().dirty.get() {
+            self.save();
+        }
+    }
+
+    /// Get all entries in this history, from oldest to newest.
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.imp().history.borrow().entries().to_vec()
+    }
+
+    /// The file of the current wallpaper, if any.
+    pub fn current_wallpaper(&self) -> Option<PathBuf> {
+        self.imp()
+            .history
+            .borrow()
+            .current_wallpaper()
+            .map(ToOwned::to_owned)
+    }
+
+    /// Get the URL we originally fetched `file` from.
+    pub fn image_url_for_file(&self, file: &Path) -> Option<String> {
+        self.imp()
+            .history
+            .borrow()
+            .image_url_for_file(file)
+            .map(ToOwned::to_owned)
+    }
+
+    /// Get metadata of the image downloaded to `file`.
+    pub fn metadata_for_file(&self, file: &Path) -> Option<ImageMetadata> {
+        self.imp().history.borrow().metadata_for_file(file).cloned()
+    }
+
+    /// Record `entry` in this history.
+    pub fn record(&self, entry: HistoryEntry) {
+        self.imp().history.borrow_mut().record(entry);
+        self.changed();
+    }
+
+    /// Mark the most recent entry for `file` as set as wallpaper.
+    pub fn mark_set_as_wallpaper(&self, file: &Path) {
+        if self.imp().history.borrow_mut().mark_set_as_wallpaper(file) {
+            self.changed();
+        } else {
+            glib::debug!("No history entry for {}", file.display());
+        }
+    }
+
+    /// Forget about all `files`, e.g. because we deleted them.
+    pub fn forget_files(&self, files: &[PathBuf]) {
+        if self.imp().history.borrow_mut().forget_files(files) {
+            self.changed();
+        }
+    }
+
+    fn changed(&self) {
+        self.imp().dirty.set(true);
+        self.save();
+    }
+
+    /// Save the history to the file, if we loaded the history already.
+    ///
+    /// If we're already saving the history, save again after the current save
+    /// finished, to make sure that the file ends up with the latest state.
+    fn save(&self) {
+        let Some(file) = self.imp().file.borrow().clone() else {
+            // We'll save after loading the history
+            return;
+        };
+        if self.imp().saving.replace(true) {
+            return;
+        }
+        glib::spawn_future_local(glib::clone!(
+            #[strong(rename_to = history)]
+            self,
+            async move {
+                while history.imp().dirty.replace(false) {
+                    let data = history.imp().history.borrow().to_json();
+                    if let Err(error) = save_to_file(&file, data).await {
+                        glib::warn!("Failed to save history to {}: {error}", file.uri());
+                    }
+                }
+                history.imp().saving.set(false);
+            }
+        ));
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        glib::Object::builder().build()
+    }
+}
+
+async fn save_to_file(file: &gio::File, data: Vec<u8>) -> Result<(), glib::Error> {
+    if let Some(directory) = file.parent().and_then(|parent| parent.path()) {
+        ensure_directory_with_parents(&directory).await?;
+    }
+    file.replace_contents_future(
+        data,
+        None,
+        false,
+        FileCreateFlags::PRIVATE | FileCreateFlags::REPLACE_DESTINATION,
+    )
+    .await
+    .map_err(|(_, error)| error)?;
+    Ok(())
+}
+
+mod imp {
+    use std::cell::{Cell, RefCell};
+
+    use glib::subclass::prelude::*;
+    use gtk::gio;
+
+    #[derive(Default)]
+    pub struct History {
+        pub history: RefCell<crate::history::History>,
+        /// The file to save history to.
+        ///
+        /// Only set after loading history from this file.
+        pub file: RefCell<Option<gio::File>>,
+        /// Whether the history has unsaved changes.
+        pub dirty: Cell<bool>,
+        /// Whether we're currently saving the history.
+        pub saving: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for History {
+        const NAME: &'static str = "PotDHistory";
+
+        type Type = super::History;
+    }
+
+    impl ObjectImpl for History {}
+}
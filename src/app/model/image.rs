@@ -8,8 +8,10 @@
 #![allow(clippy::as_conversions)]
 
 use glib::Object;
+use gtk::gio;
 
-use crate::images::{DownloadableImage, ImageMetadata};
+use crate::history::HistoryEntry;
+use crate::images::{ImageMetadata, Source};
 
 #[derive(Default, Copy, Clone, Debug, glib::Enum)]
 #[enum_type(name = "PotDImageDownloadState")]
@@ -24,22 +26,47 @@ glib::wrapper! {
     pub struct Image(ObjectSubclass<imp::Image>);
 }
 
-impl From<&ImageMetadata> for Image {
-    fn from(metadata: &ImageMetadata) -> Self {
+impl Image {
+    /// Create a new image object for `metadata` from `source`.
+    pub fn new(metadata: &ImageMetadata, source: &Source) -> Self {
         Object::builder()
             .property("title", &metadata.title)
             .property("description", &metadata.description)
             .property("copyright", &metadata.copyright)
             .property("url", &metadata.url)
-            .property("source-name", metadata.source.i18n_name())
-            .property("source-url", metadata.source.url())
+            .property("source-name", source.i18n_name())
+            .property("source-url", source.url().unwrap_or_default())
             .build()
     }
-}
 
-impl From<&DownloadableImage> for Image {
-    fn from(image: &DownloadableImage) -> Self {
-        Image::from(&image.metadata)
+    /// Create a new image object for a history `entry` from `source`.
+    ///
+    /// `source` is `None` if the source of the entry no longer exists, e.g.
+    /// if the user removed the feed of the image.  In this case use the ID of
+    /// the source as name.
+    ///
+    /// If the downloaded file of the entry still exists, mark the image as
+    /// downloaded.
+    pub fn for_history_entry(entry: &HistoryEntry, source: Option<&Source>) -> Self {
+        let metadata = &entry.metadata;
+        let image: Self = Object::builder()
+            .property("title", &metadata.title)
+            .property("description", &metadata.description)
+            .property("copyright", &metadata.copyright)
+            .property("url", &metadata.url)
+            .property(
+                "source-name",
+                source.map_or_else(|| metadata.source.as_str().into(), Source::i18n_name),
+            )
+            .property(
+                "source-url",
+                source.and_then(Source::url).unwrap_or_default(),
+            )
+            .build();
+        if let Some(file) = entry.file.as_deref().filter(|file| file.is_file()) {
+            image.set_downloaded_file(Some(&gio::File::for_path(file)));
+        }
+        image
     }
 }
 
@@ -71,6 +98,12 @@ mod imp {
         /// The state of the download
         #[property(get, builder(ImageState::default()))]
         state: Cell<ImageState>,
+        /// The fraction of the image downloaded so far.
+        ///
+        /// Zero if the download did not start yet, or if we don't know the
+        /// size of the image.
+        #[property(get, set, minimum = 0.0, maximum = 1.0)]
+        download_progress: Cell<f64>,
         /// The downloaded file, if the download was successful.
         #[property(get, set = Self::set_downloaded_file, nullable)]
         downloaded_file: RefCell<Option<gio::File>>,
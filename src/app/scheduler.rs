@@ -12,7 +12,9 @@ use glib::subclass::types::ObjectSubclassIsExt;
 use gnome_app_utils::futures::channel::oneshot;
 use gtk::gio;
 
+use crate::config::G_LOG_DOMAIN;
 use crate::images::{Source, SourceError};
+use crate::schedule::{LastUpdate, UpdateSchedule};
 
 #[glib::flags(name = "PotDAutomaticWallpaperUpdateInhibitor")]
 pub enum AutomaticWallpaperUpdateInhibitor {
@@ -40,6 +42,27 @@ impl Display for AutomaticWallpaperUpdateInhibitor {
     }
 }
 
+/// The outcome of a successful wallpaper update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WallpaperUpdateOutcome {
+    /// We set the image at the given URL as new wallpaper.
+    Updated(String),
+    /// We found no new image, and kept the current wallpaper.
+    Unchanged,
+}
+
+/// A failed wallpaper update.
+#[derive(Debug)]
+pub struct FailedUpdate {
+    /// The source which failed.
+    ///
+    /// This may be a different source than the scheduled one, after rotating
+    /// through sources, or falling back to other sources.
+    pub source: Source,
+    /// Why the update failed.
+    pub error: SourceError,
+}
+
 /// A message indicating that a scheduled wallpaper update is due.
 #[derive(Debug)]
 pub struct ScheduledWallpaperUpdate {
@@ -48,14 +71,17 @@ pub struct ScheduledWallpaperUpdate {
     /// A cancellable indicating when automatic updates are inhibited.
     pub cancellable: gio::Cancellable,
     /// A channel to notify the scheduler about the result of the update.
-    pub response: oneshot::Sender<Result<(), SourceError>>,
+    pub response: oneshot::Sender<Result<WallpaperUpdateOutcome, FailedUpdate>>,
 }
 
 impl ScheduledWallpaperUpdate {
     fn for_source(
         source: Source,
         cancellable: gio::Cancellable,
-    ) -> (Self, oneshot::Receiver<Result<(), SourceError>>) {
+    ) -> (
+        Self,
+        oneshot::Receiver<Result<WallpaperUpdateOutcome, FailedUpdate>>,
+    ) {
         let (response, rx) = oneshot::channel();
         let update = Self {
             source,
@@ -81,6 +107,35 @@ impl AutomaticWallpaperUpdateScheduler {
     pub fn update_receiver(&self) -> async_channel::Receiver<ScheduledWallpaperUpdate> {
         self.imp().update_rx.clone()
     }
+
+    /// Update wallpapers according to `schedule`.
+    pub fn set_schedule(&self, schedule: UpdateSchedule) {
+        glib::info!("Updating wallpapers {schedule:?}");
+        self.imp().schedule.set(schedule);
+    }
+
+    /// Resume updates after settings changed.
+    ///
+    /// Forget about previous failures, and update again if an update is due,
+    /// even if the previous update failed permanently.
+    pub fn resume_after_settings_changed(&self) {
+        if self.imp().suspended.get() {
+            glib::info!("Settings changed, resuming suspended updates");
+        }
+        self.imp().reset_failures();
+    }
+
+    /// Remember the last update in `settings`.
+    ///
+    /// Restore the last update from `settings`, and save every subsequent
+    /// successful update to `settings`, so that we do not update again right
+    /// after a restart.
+    pub fn persist_last_update(&self, settings: &gio::Settings) {
+        let last_update = LastUpdate::from_settings(settings);
+        glib::info!("Restored last automatic update {last_update:?}");
+        self.imp().last_update.replace(last_update);
+        self.imp().settings.replace(Some(settings.clone()));
+    }
 }
 
 mod imp {
@@ -92,92 +147,206 @@ mod imp {
     use async_channel::{Receiver, Sender};
     use glib::prelude::*;
     use glib::subclass::prelude::*;
-    use gnome_app_utils::futures::{StreamExt, stream};
+    use gnome_app_utils::futures::StreamExt;
     use gtk::gio::{self, Cancellable, prelude::CancellableExt};
+    use jiff::{SignedDuration, civil::DateTime};
 
-    use crate::{config::G_LOG_DOMAIN, images::Source};
+    use crate::{
+        config::G_LOG_DOMAIN,
+        images::Source,
+        schedule::{LastUpdate, RETRY_INTERVAL, UpdateSchedule, retry_delay},
+    };
 
-    use super::{AutomaticWallpaperUpdateInhibitor, ScheduledWallpaperUpdate};
+    use super::{
+        AutomaticWallpaperUpdateInhibitor, FailedUpdate, ScheduledWallpaperUpdate,
+        WallpaperUpdateOutcome,
+    };
+
+    /// How often to check whether an update is due, in seconds.
+    const CHECK_INTERVAL_SECONDS: u32 = 5 * 60;
 
     #[derive(glib::Properties)]
     #[properties(wrapper_type = super::AutomaticWallpaperUpdateScheduler)]
     pub struct AutomaticWallpaperUpdateScheduler {
         #[property(get, set = Self::set_inhibitors)]
         inhibitors: Cell<AutomaticWallpaperUpdateInhibitor>,
-        #[property(get, set = Self::set_source, builder(Source::default()))]
-        source: Cell<Source>,
+        #[property(get, set = Self::set_source, nullable)]
+        source: RefCell<Option<Source>>,
         #[property(get = Self::get_is_scheduled, type = bool)]
         is_scheduled: RefCell<Option<Cancellable>>,
         update_tx: Sender<ScheduledWallpaperUpdate>,
         pub update_rx: Receiver<ScheduledWallpaperUpdate>,
+        pub schedule: Cell<UpdateSchedule>,
+        /// The last successful update.
+        pub last_update: RefCell<Option<LastUpdate>>,
+        /// Do not attempt to update the wallpaper before this time.
+        retry_at: Cell<Option<DateTime>>,
+        /// The number of consecutive failed updates.
+        failures: Cell<u32>,
+        /// Whether updates failed permanently.
+        ///
+        /// We do not update again until settings change.
+        pub suspended: Cell<bool>,
+        /// Settings to save the last update to.
+        pub settings: RefCell<Option<gio::Settings>>,
     }
 
     async fn schedule_automatic_updates(
         initial_delay: Duration,
+        scheduler: glib::WeakRef<super::AutomaticWallpaperUpdateScheduler>,
         source: Source,
         cancellable: Cancellable,
         tx: Sender<ScheduledWallpaperUpdate>,
     ) {
         // Delay the initial wallpaper update a bit, this behaves nicer when the
         // user changes the corresponding setting.
-        stream::once(glib::timeout_future_seconds(
-            initial_delay.as_secs().try_into().unwrap(),
-        ))
-        .chain(glib::interval_stream_seconds(30 * 60))
-        .map(|()| glib::DateTime::now_utc().unwrap())
-        .fold(
-            // This is definitely more than 12 hours ago ;)
-            glib::DateTime::from_unix_utc(0).unwrap(),
-            move |last_update, now| {
-                let tx = tx.clone();
-                let cancellable = cancellable.clone();
-                async move {
-                    let hours_since_last_update = now.difference(&last_update).as_hours();
-                    if hours_since_last_update < 12 {
-                        // If the last update's less than twelve hours ago
-                        // we don't do anything
-                        glib::info!(
-                            "Not updating wallpaper, \
-                            last update was {hours_since_last_update:?} \
-                            hours (< 12) ago"
-                        );
-                        last_update
-                    } else {
-                        // If the last update's more than twelve hours ago
-                        // pass true downstream to indicate that another
-                        // update is needed, and remember now as the time
-                        // of the last update
-                        glib::info!(
-                            "Signalling wallpaper update, \
-                        last update was more than {hours_since_last_update:?}\
-                         hours (>= 12) ago"
-                        );
-                        let (update, receive_response) =
-                            ScheduledWallpaperUpdate::for_source(source, cancellable.clone());
-                        // We can safely unwrap because we'll never drop this channel
-                        // while before stopping the updates, as the scheduler itself
-                        // retains a reference to one receiver
-                        tx.force_send(update).unwrap();
-                        match receive_response.await {
-                            Ok(Ok(())) => {
-                                // Update successful, remember so
-                                now
-                            }
-                            Err(_) | Ok(Err(_)) => {
-                                // If the update failed, or if the sender dropped
-                                // before it tell us how the update went, try
-                                // again next time
-                                last_update
-                            }
-                        }
-                    }
+        glib::timeout_future(initial_delay).await;
+        let mut ticks = glib::interval_stream_seconds(CHECK_INTERVAL_SECONDS);
+        loop {
+            // Only hold on to the scheduler while checking, to avoid keeping
+            // it alive while waiting for the update or the next tick.
+            let Some(due) = scheduler
+                .upgrade()
+                .map(|scheduler| scheduler.imp().begin_update_if_due())
+            else {
+                break;
+            };
+            if let Some(schedule) = due {
+                request_update(&scheduler, schedule, &source, &cancellable, &tx).await;
+            }
+            ticks.next().await;
+        }
+    }
+
+    /// Request a wallpaper update from `source`, and wait for its outcome.
+    ///
+    /// Remember the time of the update if the update succeeded according to
+    /// `schedule`.
+    async fn request_update(
+        scheduler: &glib::WeakRef<super::AutomaticWallpaperUpdateScheduler>,
+        schedule: UpdateSchedule,
+        source: &Source,
+        cancellable: &Cancellable,
+        tx: &Sender<ScheduledWallpaperUpdate>,
+    ) {
+        let (update, receive_response) =
+            ScheduledWallpaperUpdate::for_source(source.clone(), cancellable.clone());
+        // We can safely unwrap because we'll never drop this channel
+        // while before stopping the updates, as the scheduler itself
+        // retains a reference to one receiver
+        tx.force_send(update).unwrap();
+        let response = receive_response.await;
+        let Some(scheduler) = scheduler.upgrade() else {
+            return;
+        };
+        let scheduler = scheduler.imp();
+        match response {
+            Ok(Ok(WallpaperUpdateOutcome::Updated(image_url))) => {
+                scheduler.record_update(source, image_url);
+            }
+            Ok(Ok(WallpaperUpdateOutcome::Unchanged)) => {
+                glib::info!("Wallpaper unchanged");
+                scheduler.failures.set(0);
+                if schedule.accepts_unchanged_image() {
+                    let image_url = scheduler
+                        .last_update
+                        .borrow()
+                        .as_ref()
+                        .map(|update| update.image_url.clone())
+                        .unwrap_or_default();
+                    scheduler.record_update(source, image_url);
                 }
-            },
-        )
-        .await;
+            }
+            Ok(Err(failure)) => scheduler.record_failure(source, &failure),
+            Err(_) => {
+                // If the sender dropped before it told us how the update
+                // went, just try again later
+            }
+        }
     }
 
     impl AutomaticWallpaperUpdateScheduler {
+        /// Check whether an update is due now.
+        ///
+        /// If so, remember that we attempted an update now, and return the
+        /// current schedule.
+        fn begin_update_if_due(&self) -> Option<UpdateSchedule> {
+            let now = crate::date::now_local();
+            let schedule = self.schedule.get();
+            let last_update = self.last_update.borrow().as_ref().map(|update| update.at);
+            if self.suspended.get() {
+                glib::debug!("Not updating wallpaper, updates suspended until settings change");
+                None
+            } else if schedule.is_due(last_update, self.retry_at.get(), now) {
+                glib::info!(
+                    "Signalling wallpaper update for schedule {schedule:?}, last update at {last_update:?}"
+                );
+                // Never attempt again too soon, even if the update succeeds,
+                // e.g. while waiting for the source to publish a new image.
+                self.retry_at.set(now.checked_add(RETRY_INTERVAL).ok());
+                Some(schedule)
+            } else {
+                glib::debug!(
+                    "Not updating wallpaper for schedule {schedule:?}, last update at {last_update:?}"
+                );
+                None
+            }
+        }
+
+        /// Remember a successful update from `source` to `image_url`.
+        fn record_update(&self, source: &Source, image_url: String) {
+            self.failures.set(0);
+            let update = LastUpdate {
+                at: crate::date::now_local(),
+                source: source.id().to_owned(),
+                image_url,
+            };
+            if let Some(settings) = self.settings.borrow().as_ref()
+                && let Err(error) = update.save_to_settings(settings)
+            {
+                glib::warn!("Failed to save last update: {error}");
+            }
+            self.last_update.replace(Some(update));
+        }
+
+        /// Remember a failed update from the scheduled `source`.
+        ///
+        /// Suspend updates if `source` itself failed with an error only a
+        /// change of settings can fix.  Back off after all other errors, and
+        /// after errors of other sources, i.e. rotated or fallback sources;
+        /// the next update may well use a different source again.
+        fn record_failure(&self, source: &Source, failure: &FailedUpdate) {
+            let error = &failure.error;
+            if error.needs_settings_change() && failure.source.id() == source.id() {
+                glib::warn!(
+                    "Update from {} failed with permanent error, suspending updates until settings change",
+                    source.id()
+                );
+                self.suspended.set(true);
+                return;
+            }
+            let failures = self.failures.get().saturating_add(1);
+            self.failures.set(failures);
+            let retry_after = error
+                .retry_after()
+                .and_then(|retry_after| SignedDuration::try_from(retry_after).ok());
+            let delay = retry_delay(failures, retry_after);
+            glib::info!(
+                "Update from {} failed {failures} times, retrying in {} minutes",
+                failure.source.id(),
+                delay.as_mins()
+            );
+            self.retry_at
+                .set(crate::date::now_local().checked_add(delay).ok());
+        }
+
+        /// Forget about failed updates, and update again when due.
+        pub fn reset_failures(&self) {
+            self.failures.set(0);
+            self.retry_at.set(None);
+            self.suspended.set(false);
+        }
+
         fn get_is_scheduled(&self) -> bool {
             self.is_scheduled.borrow().is_some()
         }
@@ -189,8 +358,23 @@ mod imp {
             self.schedule_updates_unless_inhibited(Duration::from_secs(10));
         }
 
-        fn set_source(&self, source: Source) {
-            self.source.set(source);
+        fn set_source(&self, source: Option<Source>) {
+            let last_source = self
+                .last_update
+                .borrow()
+                .as_ref()
+                .map(|update| update.source.clone());
+            if let Some(last_source) = last_source
+                && source
+                    .as_ref()
+                    .is_some_and(|source| source.id() != last_source)
+            {
+                // Update from a new source right away.
+                glib::info!("Source changed from {last_source}, forgetting last update");
+                self.last_update.replace(None);
+                self.reset_failures();
+            }
+            self.source.replace(source);
             if let Some(cancellable) = self.is_scheduled.take() {
                 // If updates are already scheduled, cancel scheduled updates,
                 // in order to restart with the updated source.
@@ -228,20 +412,31 @@ mod imp {
         fn schedule_updates_unless_inhibited(&self, initial_delay: Duration) {
             let inhibitors = self.inhibitors.get();
             if inhibitors.is_empty() {
+                let source = self.source.borrow().clone();
                 if self.is_scheduled.borrow().is_some() {
                     glib::info!("Automatic wallpaper updates already scheduled.");
-                } else {
+                } else if let Some(source) = source {
                     let cancellable = gio::Cancellable::new();
                     self.is_scheduled.replace(Some(cancellable.clone()));
                     self.obj().notify_is_scheduled();
 
-                    let source = self.source.get();
-                    glib::info!("Scheduling automatic wallpaper updates from {source:?}");
+                    glib::info!(
+                        "Scheduling automatic wallpaper updates from {}",
+                        source.id()
+                    );
                     let tx = self.update_tx.clone();
                     glib::spawn_future_local(gio::CancellableFuture::new(
-                        schedule_automatic_updates(initial_delay, source, cancellable.clone(), tx),
+                        schedule_automatic_updates(
+                            initial_delay,
+                            self.obj().downgrade(),
+                            source,
+                            cancellable.clone(),
+                            tx,
+                        ),
                         cancellable,
                     ));
+                } else {
+                    glib::info!("Not scheduling automatic wallpaper updates, no source set");
                 }
             } else {
                 glib::info!(
@@ -264,7 +459,13 @@ mod imp {
                 is_scheduled: RefCell::new(None),
                 update_tx: tx,
                 update_rx: rx,
-                source: Cell::new(Source::default()),
+                source: RefCell::new(None),
+                schedule: Cell::new(UpdateSchedule::default()),
+                last_update: RefCell::new(None),
+                retry_at: Cell::new(None),
+                failures: Cell::new(0),
+                suspended: Cell::new(false),
+                settings: RefCell::new(None),
             }
         }
     }
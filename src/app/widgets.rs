@@ -6,14 +6,18 @@
 
 mod application_window;
 mod error_notification_page;
+mod history_page;
 mod image_page;
+mod image_properties;
 mod images_carousel;
 mod preferences_dialog;
 mod source_row;
 
 pub use application_window::ApplicationWindow;
 pub use error_notification_page::ErrorNotificationPage;
+pub use history_page::HistoryPage;
 pub use image_page::ImagePage;
+pub use image_properties::ImageProperties;
 pub use images_carousel::ImagesCarousel;
 pub use preferences_dialog::PreferencesDialog;
 pub use source_row::SourceRow;
@@ -11,9 +11,11 @@ use glib::{object::IsA, subclass::types::ObjectSubclassIsExt};
 use gtk::UriLauncher;
 use gtk::gio::{self, DBusConnection};
 
-use crate::app::model::{ErrorNotification, ErrorNotificationActions};
+use crate::app::model::{ErrorNotification, ErrorNotificationActions, History};
 use crate::config::G_LOG_DOMAIN;
 use crate::date::BoxedCivilDate;
+use crate::images::{Source, SourceRegistry};
+use crate::target::WallpaperTarget;
 
 glib::wrapper! {
     pub struct ApplicationWindow(ObjectSubclass<imp::ApplicationWindow>)
@@ -27,15 +29,20 @@ impl ApplicationWindow {
     /// Create a new window.
     ///
     /// The window belongs to `application` and keeps a hold on `application`.
-    /// It uses the `session` to fetch images for the selected source.
+    /// It offers all `sources`, and uses the `session` to fetch images for the
+    /// selected source.  It records all fetched images in `history`.
     pub fn new(
         application: &impl IsA<gtk::Application>,
+        sources: &SourceRegistry,
+        history: &History,
         session: &soup::Session,
         dbus_connection: &DBusConnection,
         date: Option<jiff::civil::Date>,
     ) -> Self {
         glib::Object::builder()
             .property("application", application)
+            .property("sources", sources)
+            .property("history", history)
             .property("http-session", session)
             .property("dbus-connection", dbus_connection)
             .property("date", date.map(BoxedCivilDate::from))
@@ -46,25 +53,34 @@ impl ApplicationWindow {
         self.imp().cancel_loading();
     }
 
+    /// Get the source for the selected source ID.
+    pub fn current_source(&self) -> Source {
+        self.sources().lookup_or_default(&self.selected_source())
+    }
+
     /// Load images for the selected source.
     pub async fn load_images(&self) {
         self.cancel_loading();
         let cancellable = self.imp().start_loading();
 
-        let source = self.selected_source();
+        let source = self.current_source();
         let result =
-            gio::CancellableFuture::new(self.imp().load_images_for_source(source), cancellable)
+            gio::CancellableFuture::new(self.imp().load_images_for_source(&source), cancellable)
                 .await;
         // If loading was cancelled or successful we don't need to do anything here
         if let Ok(Err(error)) = result {
-            self.imp().show_source_error(source, &error);
+            self.imp().show_source_error(&source, &error);
         }
 
         self.imp().finish_loading();
     }
 
     async fn open_source_url(&self) {
-        let url = self.selected_source().url();
+        let source = self.current_source();
+        let Some(url) = source.url() else {
+            // The action is disabled for sources without URL
+            return;
+        };
         if let Err(error) = UriLauncher::new(url).launch_future(Some(self)).await {
             glib::warn!("Failed to open source URL: {error}");
             let error = ErrorNotification::builder()
@@ -93,8 +109,8 @@ problem.",
         }
     }
 
-    async fn set_current_image_as_wallpaper(&self) {
-        if let Err(error) = self.imp().set_current_image_as_wallpaper().await {
+    async fn set_current_image_as_wallpaper(&self, target: WallpaperTarget) {
+        if let Err(error) = self.imp().set_current_image_as_wallpaper(target).await {
             glib::warn!("Failed to set current image as wallaper: {error}");
             let error = ErrorNotification::builder()
                 .title(dpgettext2(
@@ -262,36 +278,59 @@ mod imp {
     use glib::{Object, Properties, closure, dpgettext2};
     use gnome_app_utils::futures::future::join_all;
     use gnome_app_utils::io::ensure_directory_with_parents;
-    use gnome_app_utils::portal::{wallpaper, window::PortalWindowHandle};
+    use gnome_app_utils::portal::window::PortalWindowHandle;
     use gtk::CompositeTemplate;
     use gtk::gdk::{Key, ModifierType};
     use gtk::gio::{self, Cancellable, DBusConnection, FileCreateFlags, FileQueryInfoFlags};
-    use strum::IntoEnumIterator;
 
-    use crate::app::model::{ErrorNotification, Image};
-    use crate::app::widgets::{ErrorNotificationPage, ImagesCarousel, SourceRow};
+    use crate::app::model::{ErrorNotification, History, Image};
+    use crate::app::widgets::{
+        ErrorNotificationPage, HistoryPage, ImageProperties, ImagesCarousel, SourceRow,
+    };
     use crate::config::G_LOG_DOMAIN;
     use crate::date::BoxedCivilDate;
-    use crate::images::{Source, SourceError};
+    use crate::history::{HistoryEntry, favorite_entries};
+    use crate::images::{Source, SourceError, SourceRegistry};
+    use crate::net::http::download::DownloadProgress;
+    use crate::target::{WallpaperTarget, WallpaperTargets};
 
     #[derive(Default, CompositeTemplate, Properties)]
     #[properties(wrapper_type = super::ApplicationWindow)]
     #[template(resource = "/de/swsnr/pictureoftheday/ui/application-window.ui")]
     pub struct ApplicationWindow {
+        #[property(get, construct_only)]
+        sources: RefCell<SourceRegistry>,
+        #[property(get, construct_only)]
+        history: RefCell<History>,
         #[property(get, construct_only)]
         http_session: RefCell<soup::Session>,
         #[property(get, construct_only)]
         dbus_connection: RefCell<Option<DBusConnection>>,
         #[property(get, construct_only, nullable)]
         date: Cell<Option<BoxedCivilDate>>,
-        #[property(get, set, builder(Source::default()))]
-        selected_source: Cell<Source>,
+        #[property(get, set)]
+        selected_source: RefCell<String>,
         #[property(get, set)]
         set_wallpaper_automatically: Cell<bool>,
         #[property(get, set)]
         show_image_properties: Cell<bool>,
         #[property(get, set)]
         show_update_indicator: Cell<bool>,
+        /// Whether to show the history instead of the images of the current source.
+        #[property(get, set = Self::set_show_history)]
+        show_history: Cell<bool>,
+        /// Whether to show favorite images instead of the images of the current source.
+        #[property(get, set = Self::set_show_favorites)]
+        show_favorites: Cell<bool>,
+        /// URLs of favorite images.
+        #[property(get, set)]
+        favorite_images: RefCell<Vec<String>>,
+        /// URLs of images the user never wants to see again.
+        #[property(get, set)]
+        blocked_images: RefCell<Vec<String>>,
+        /// The image currently shown, either in the carousel or in the history.
+        #[property(get)]
+        current_image: RefCell<Option<Image>>,
         #[property(get = Self::is_loading, type = bool)]
         is_loading: RefCell<Option<Cancellable>>,
         #[template_child]
@@ -303,20 +342,34 @@ mod imp {
         #[template_child]
         images_carousel: TemplateChild<ImagesCarousel>,
         #[template_child]
+        history_view: TemplateChild<adw::OverlaySplitView>,
+        #[template_child]
+        history_page: TemplateChild<HistoryPage>,
+        #[template_child]
+        empty: TemplateChild<adw::StatusPage>,
+        #[template_child]
         toasts: TemplateChild<adw::ToastOverlay>,
     }
 
-    #[gtk::template_callbacks]
     impl ApplicationWindow {
-        #[template_callback(function)]
-        fn non_empty(s: Option<&str>) -> bool {
-            s.is_some_and(|s| !s.is_empty())
+        pub fn current_image(&self) -> Option<Image> {
+            self.current_image.borrow().clone()
         }
-    }
 
-    impl ApplicationWindow {
-        pub fn current_image(&self) -> Option<Image> {
-            self.images_carousel.current_image()
+        fn update_current_image(&self) {
+            let image = if self.show_history.get() || self.show_favorites.get() {
+                self.history_page.selected_image()
+            } else {
+                self.images_carousel.current_image()
+            };
+            self.current_image.replace(image);
+            self.obj().notify_current_image();
+        }
+
+        fn update_open_source_url(&self) {
+            let has_url = self.obj().current_source().url().is_some();
+            self.obj()
+                .action_set_enabled("win.open-source-url", has_url);
         }
 
         pub fn current_image_file(&self) -> Option<gio::File> {
@@ -336,9 +389,10 @@ mod imp {
             }
         }
 
-        fn switch_to_images_view(&self) {
-            if self.stack.visible_child().unwrap() != self.images_view.get() {
-                // Enable the side bar _before_ switching to the image view and
+        /// Switch to `view`, which shows the image properties sidebar.
+        fn switch_to_view(&self, view: &impl IsA<gtk::Widget>) {
+            if self.stack.visible_child().unwrap() == self.empty.get() {
+                // Enable the side bar _before_ switching to the view and
                 // thus realizing the overlay view with its sidebar; this
                 // ensures that the overlay view gets rendered with expanded
                 // sidebar right from the start, which prevents warnings about
@@ -349,10 +403,133 @@ mod imp {
                 // away from the empty start page.  Afterwards we always honour
                 // the users intention of whether to show the sidebar or not.
                 self.obj().set_show_image_properties(true);
-                self.stack.set_visible_child(&*self.images_view);
                 self.obj()
                     .action_set_enabled("win.show-image-properties", true);
             }
+            self.stack.set_visible_child(view);
+        }
+
+        fn switch_to_images_view(&self) {
+            if self.show_history.replace(false) {
+                self.obj().notify_show_history();
+            }
+            if self.show_favorites.replace(false) {
+                self.obj().notify_show_favorites();
+            }
+            self.update_view();
+        }
+
+        fn set_show_history(&self, show_history: bool) {
+            self.show_history.set(show_history);
+            if show_history && self.show_favorites.replace(false) {
+                self.obj().notify_show_favorites();
+            }
+            self.update_view();
+        }
+
+        fn set_show_favorites(&self, show_favorites: bool) {
+            self.show_favorites.set(show_favorites);
+            if show_favorites && self.show_history.replace(false) {
+                self.obj().notify_show_history();
+            }
+            self.update_view();
+        }
+
+        /// Show the history, favorites or images of the current source.
+        fn update_view(&self) {
+            if self.show_favorites.get() {
+                let entries = self.history.borrow().entries();
+                let favorites =
+                    favorite_entries(&entries, self.favorite_images.borrow().as_slice())
+                        .into_iter()
+                        .cloned()
+                        .collect::<Vec<_>>();
+                self.history_page
+                    .set_entries(&favorites, &self.obj().sources());
+                self.switch_to_view(&*self.history_view);
+            } else if self.show_history.get() {
+                self.history_page
+                    .set_entries(&self.history.borrow().entries(), &self.obj().sources());
+                self.switch_to_view(&*self.history_view);
+            } else if self.images_carousel.current_image().is_some() {
+                self.switch_to_view(&*self.images_view);
+            } else {
+                self.stack.set_visible_child(&*self.empty);
+                self.obj()
+                    .action_set_enabled("win.show-image-properties", false);
+            }
+            self.update_current_image();
+        }
+
+        /// Get the URL the current image was originally fetched from.
+        fn current_image_url(&self) -> Option<String> {
+            let path = self.current_image_file()?.path()?;
+            self.history.borrow().image_url_for_file(&path)
+        }
+
+        /// Update the state of the favorite and block actions for the current image.
+        fn update_image_action_states(&self) {
+            let url = self.current_image_url();
+            let contains_url = |urls: &[String]| url.as_ref().is_some_and(|url| urls.contains(url));
+            for (name, state) in [
+                (
+                    "favorite-image",
+                    contains_url(&self.favorite_images.borrow()),
+                ),
+                ("block-image", contains_url(&self.blocked_images.borrow())),
+            ] {
+                if let Some(action) = self
+                    .obj()
+                    .lookup_action(name)
+                    .and_downcast::<gio::SimpleAction>()
+                {
+                    action.set_state(&state.to_variant());
+                }
+            }
+        }
+
+        /// Add or remove the current image from favorites.
+        ///
+        /// Favorite images are never blocked.
+        fn toggle_favorite_image(&self) {
+            let Some(url) = self.current_image_url() else {
+                return;
+            };
+            let mut favorites = self.obj().favorite_images();
+            if favorites.contains(&url) {
+                favorites.retain(|favorite| *favorite != url);
+            } else {
+                glib::info!("Adding {url} to favorites");
+                let mut blocked = self.obj().blocked_images();
+                if blocked.contains(&url) {
+                    blocked.retain(|blocked| *blocked != url);
+                    self.obj().set_blocked_images(blocked);
+                }
+                favorites.push(url);
+            }
+            self.obj().set_favorite_images(favorites);
+        }
+
+        /// Block or unblock the current image.
+        ///
+        /// Blocked images are never favorites.
+        fn toggle_blocked_image(&self) {
+            let Some(url) = self.current_image_url() else {
+                return;
+            };
+            let mut blocked = self.obj().blocked_images();
+            if blocked.contains(&url) {
+                blocked.retain(|blocked| *blocked != url);
+            } else {
+                glib::info!("Blocking {url}");
+                let mut favorites = self.obj().favorite_images();
+                if favorites.contains(&url) {
+                    favorites.retain(|favorite| *favorite != url);
+                    self.obj().set_favorite_images(favorites);
+                }
+                blocked.push(url);
+            }
+            self.obj().set_blocked_images(blocked);
         }
 
         pub fn start_loading(&self) -> gio::Cancellable {
@@ -369,7 +546,7 @@ mod imp {
             self.obj().notify_is_loading();
         }
 
-        pub fn show_source_error(&self, source: Source, error: &SourceError) {
+        pub fn show_source_error(&self, source: &Source, error: &SourceError) {
             glib::error!("Fetching images failed: {error}");
             let error = ErrorNotification::from_error(source, error);
             self.show_error(&error);
@@ -400,8 +577,8 @@ mod imp {
             self.show_toast(toast);
         }
 
-        pub async fn load_images_for_source(&self, source: Source) -> Result<(), SourceError> {
-            glib::info!("Fetching images for source {source:?}");
+        pub async fn load_images_for_source(&self, source: &Source) -> Result<(), SourceError> {
+            glib::info!("Fetching images for source {}", source.id());
             let images = source
                 .get_images(&self.obj().http_session(), self.date.get().map(Into::into))
                 .await?;
@@ -412,7 +589,7 @@ mod imp {
             let images = images
                 .into_iter()
                 .map(|image| {
-                    let obj = Image::from(&image);
+                    let obj = Image::new(&image.metadata, source);
                     (image, obj)
                 })
                 .collect::<Vec<_>>();
@@ -433,28 +610,52 @@ mod imp {
 
             // Download all images
             let http_session = self.http_session.borrow().clone();
+            let history = self.history.borrow().clone();
+            let today = crate::date::today_local();
             let target_directory = Rc::new(target_directory);
+            let source = source.clone();
             join_all(images.into_iter().map(move |(image, image_obj)| {
                 glib::clone!(
+                    #[strong]
+                    source,
                     #[strong]
                     target_directory,
                     #[weak]
                     http_session,
+                    #[weak]
+                    history,
                     async move {
+                        let progress = |progress: DownloadProgress| {
+                            if let Some(fraction) = progress.fraction() {
+                                image_obj.set_download_progress(fraction);
+                            }
+                        };
                         match image
-                            .download_to_directory(&target_directory, &http_session)
+                            .download_to_directory_with_progress(
+                                &target_directory,
+                                &http_session,
+                                &progress,
+                            )
                             .await
                         {
                             Ok(target) => {
                                 glib::info!("Displaying image from {}", target.display());
-                                image_obj.set_downloaded_file(Some(&gio::File::for_path(target)));
+                                // Record the image first, so that we know the
+                                // URL of the image when we show it.
+                                history.record(HistoryEntry::new(
+                                    today,
+                                    &image,
+                                    Some(target.clone()),
+                                ));
+                                image_obj.set_downloaded_file(Some(&gio::File::for_path(&target)));
                             }
                             Err(error) => {
                                 glib::warn!(
                                     "Downloading image from {} failed: {error}",
                                     &image.image_url
                                 );
-                                let error = ErrorNotification::from_error(source, &error.into());
+                                history.record(HistoryEntry::new(today, &image, None));
+                                let error = ErrorNotification::from_error(&source, &error.into());
                                 image_obj.set_download_error(Some(error));
                             }
                         }
@@ -462,21 +663,39 @@ mod imp {
                 )
             }))
             .await;
+            crate::app::cleanup::clean_up_images(&self.obj().history()).await;
             Ok(())
         }
 
-        pub async fn set_current_image_as_wallpaper(&self) -> Result<(), glib::Error> {
-            if let Some(file) = self.current_image_file() {
-                let parent_window = PortalWindowHandle::new_for_native(&*self.obj()).await;
-                let result = wallpaper::set_wallpaper_file(
-                    &self.obj().dbus_connection().unwrap(),
-                    &parent_window,
-                    &file,
-                    wallpaper::Preview::NoPreview,
-                    wallpaper::SetOn::Both,
+        pub async fn set_current_image_as_wallpaper(
+            &self,
+            target: WallpaperTarget,
+        ) -> Result<(), glib::Error> {
+            let app = self
+                .obj()
+                .application()
+                .and_downcast::<crate::app::Application>();
+            if let Some(app) = app
+                && let Some(path) = self.current_image_file().and_then(|file| file.path())
+            {
+                // The file may have been cleaned up since we loaded the image
+                if !path.is_file() {
+                    return Err(glib::Error::new(
+                        gio::IOErrorEnum::NotFound,
+                        &format!("{} no longer exists", path.display()),
+                    ));
+                }
+                app.set_wallpaper_files(
+                    &path,
+                    None,
+                    &[],
+                    target,
+                    PortalWindowHandle::new_for_native(&*self.obj()),
                 )
-                .await;
-                glib::info!("Wallpaper request finished: {result:?}");
+                .await?;
+                if target.includes_background() {
+                    self.history.borrow().mark_set_as_wallpaper(&path);
+                }
             }
             Ok(())
         }
@@ -533,14 +752,17 @@ mod imp {
 
         fn class_init(klass: &mut Self::Class) {
             ImagesCarousel::ensure_type();
+            HistoryPage::ensure_type();
+            ImageProperties::ensure_type();
             Image::ensure_type();
             ErrorNotificationPage::ensure_type();
 
             klass.bind_template();
-            klass.bind_template_callbacks();
 
             klass.install_property_action("win.select-source", "selected-source");
             klass.install_property_action("win.show-image-properties", "show-image-properties");
+            klass.install_property_action("win.show-history", "show-history");
+            klass.install_property_action("win.show-favorites", "show-favorites");
             klass.install_property_action(
                 "win.set-wallpaper-automatically",
                 "set-wallpaper-automatically",
@@ -561,6 +783,7 @@ mod imp {
                 ModifierType::NO_MODIFIER_MASK,
                 "win.show-image-properties",
             );
+            klass.add_binding_action(Key::h, ModifierType::CONTROL_MASK, "win.show-history");
             klass.add_binding_action(
                 Key::Escape,
                 ModifierType::NO_MODIFIER_MASK,
@@ -578,17 +801,45 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            for source in Source::iter() {
-                let row = SourceRow::new(source);
-                row.set_action_name(Some("win.select-source"));
-                row.set_action_target(Some(source));
-                self.sources_list.get().append(&row);
-            }
+            // The date is construct-only, so we can decide about the
+            // sensitivity of source rows once.
+            let has_date = self.date.get().is_some();
+            self.sources_list
+                .bind_model(Some(&self.obj().sources()), move |item| {
+                    let source = item.downcast_ref::<Source>().unwrap();
+                    let row = SourceRow::new(source);
+                    row.set_action_name(Some("win.select-source"));
+                    row.set_action_target(Some(source.id().to_variant()));
+                    if has_date && !source.supports_date() {
+                        row.set_sensitive(false);
+                        row.set_tooltip_text(Some(&dpgettext2(
+                            None,
+                            "source-row.tooltip",
+                            "This source only provides images for today",
+                        )));
+                    }
+                    row.upcast()
+                });
 
             self.obj().connect_selected_source_notify(|window| {
-                glib::info!("Selected source updates: {:?}", window.selected_source());
+                glib::info!("Selected source updates: {}", window.selected_source());
+                window.imp().update_open_source_url();
                 gtk::prelude::WidgetExt::activate_action(window, "win.load-images", None).unwrap();
             });
+            self.update_open_source_url();
+
+            self.images_carousel
+                .connect_current_image_notify(glib::clone!(
+                    #[weak(rename_to = window)]
+                    self.obj(),
+                    move |_| window.imp().update_current_image()
+                ));
+            self.history_page
+                .connect_selected_image_notify(glib::clone!(
+                    #[weak(rename_to = window)]
+                    self.obj(),
+                    move |_| window.imp().update_current_image()
+                ));
 
             // We're not showing images initially, so let's disable the sidebar action.
             self.obj()
@@ -599,8 +850,34 @@ mod imp {
                 #[weak(rename_to = window)]
                 self.obj(),
                 move |_, _| {
+                    let target =
+                        WallpaperTargets::from_settings(&crate::config::get_settings()).target;
                     glib::spawn_future_local(async move {
-                        window.set_current_image_as_wallpaper().await;
+                        window.set_current_image_as_wallpaper(target).await;
+                    });
+                }
+            ));
+            let act_set_background = gio::SimpleAction::new("set-as-background", None);
+            act_set_background.connect_activate(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_, _| {
+                    glib::spawn_future_local(async move {
+                        window
+                            .set_current_image_as_wallpaper(WallpaperTarget::Background)
+                            .await;
+                    });
+                }
+            ));
+            let act_set_lock_screen = gio::SimpleAction::new("set-on-lock-screen", None);
+            act_set_lock_screen.connect_activate(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_, _| {
+                    glib::spawn_future_local(async move {
+                        window
+                            .set_current_image_as_wallpaper(WallpaperTarget::LockScreen)
+                            .await;
                     });
                 }
             ));
@@ -625,10 +902,33 @@ mod imp {
                 }
             ));
 
-            for action in &[act_set_wallpaper, act_open_default, act_save_image] {
+            let act_favorite_image =
+                gio::SimpleAction::new_stateful("favorite-image", None, &false.to_variant());
+            act_favorite_image.connect_activate(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_, _| window.imp().toggle_favorite_image()
+            ));
+            let act_block_image =
+                gio::SimpleAction::new_stateful("block-image", None, &false.to_variant());
+            act_block_image.connect_activate(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_, _| window.imp().toggle_blocked_image()
+            ));
+
+            for action in &[
+                act_set_wallpaper,
+                act_set_background,
+                act_set_lock_screen,
+                act_favorite_image,
+                act_block_image,
+                act_open_default,
+                act_save_image,
+            ] {
                 self.obj().add_action(action);
                 action.set_enabled(false);
-                self.images_carousel
+                self.obj()
                     .property_expression("current-image")
                     .chain_property::<Image>("downloaded-file")
                     .chain_closure::<bool>(closure!(
@@ -636,6 +936,27 @@ mod imp {
                     ))
                     .bind(action, "enabled", Object::NONE);
             }
+
+            self.obj()
+                .property_expression("current-image")
+                .chain_property::<Image>("downloaded-file")
+                .watch(
+                    Some(&*self.obj()),
+                    glib::clone!(
+                        #[weak(rename_to = window)]
+                        self.obj(),
+                        move || window.imp().update_image_action_states()
+                    ),
+                );
+            self.obj().connect_blocked_images_notify(|window| {
+                window.imp().update_image_action_states();
+            });
+            self.obj().connect_favorite_images_notify(|window| {
+                window.imp().update_image_action_states();
+                if window.show_favorites() {
+                    window.imp().update_view();
+                }
+            });
         }
     }
 
@@ -0,0 +1,197 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+use glib::{Object, subclass::types::ObjectSubclassIsExt};
+
+use crate::history::HistoryEntry;
+use crate::images::SourceRegistry;
+
+glib::wrapper! {
+    /// Browse previously fetched images.
+    pub struct HistoryPage(ObjectSubclass<imp::HistoryPage>)
+        @extends adw::Bin, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl HistoryPage {
+    /// Show all history `entries`, and look up their sources in `sources`.
+    pub fn set_entries(&self, entries: &[HistoryEntry], sources: &SourceRegistry) {
+        self.imp().set_entries(entries, sources);
+    }
+}
+
+impl Default for HistoryPage {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
+
+mod imp {
+    use std::cell::RefCell;
+
+    use adw::prelude::*;
+    use adw::subclass::prelude::*;
+    use formatx::formatx;
+    use glib::{Properties, dpgettext2, subclass::InitializingObject};
+    use gtk::CompositeTemplate;
+
+    use crate::app::model::Image;
+    use crate::history::{HistoryEntry, HistoryGroup, group_entries};
+    use crate::images::SourceRegistry;
+
+    /// Create a thumbnail widget for `image`.
+    fn thumbnail(image: &Image) -> gtk::Widget {
+        let widget: gtk::Widget = match image.downloaded_file() {
+            Some(file) => gtk::Picture::builder()
+                .file(&file)
+                .content_fit(gtk::ContentFit::Cover)
+                .build()
+                .upcast(),
+            // The file is gone, e.g. because the user deleted it.
+            None => gtk::Image::builder()
+                .icon_name("image-missing-symbolic")
+                .pixel_size(48)
+                .build()
+                .upcast(),
+        };
+        widget.set_size_request(192, 108);
+        widget.set_overflow(gtk::Overflow::Hidden);
+        widget.set_tooltip_text(Some(image.title().as_str()));
+        widget.add_css_class("card");
+        widget
+    }
+
+    #[derive(Default, CompositeTemplate, Properties)]
+    #[properties(wrapper_type = super::HistoryPage)]
+    #[template(resource = "/de/swsnr/pictureoftheday/ui/history-page.ui")]
+    pub struct HistoryPage {
+        /// The image the user selected.
+        #[property(get)]
+        selected_image: RefCell<Option<Image>>,
+        /// One flow box for every group of images.
+        flow_boxes: RefCell<Vec<gtk::FlowBox>>,
+        #[template_child]
+        stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        empty: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        history: TemplateChild<gtk::ScrolledWindow>,
+        #[template_child]
+        groups: TemplateChild<gtk::Box>,
+    }
+
+    impl HistoryPage {
+        fn select_image(&self, image: Option<Image>) {
+            self.selected_image.replace(image);
+            self.obj().notify_selected_image();
+        }
+
+        /// Select `image` in `flow_box`, and clear the selection of all other groups.
+        fn select_in_flow_box(&self, flow_box: &gtk::FlowBox, image: Image) {
+            for other in self.flow_boxes.borrow().iter() {
+                if other != flow_box {
+                    other.unselect_all();
+                }
+            }
+            self.select_image(Some(image));
+        }
+
+        fn add_group(&self, group: &HistoryGroup<'_>, sources: &SourceRegistry) {
+            let source = sources.lookup(group.source);
+            let source_name = source
+                .as_ref()
+                .map_or_else(|| group.source.into(), |source| source.i18n_name());
+            let title = formatx!(
+                dpgettext2(None, "history-page.group.title", "{date} – {source}"),
+                date = crate::date::format_date(group.date),
+                source = source_name
+            )
+            .unwrap();
+            let label = gtk::Label::builder()
+                .label(title)
+                .halign(gtk::Align::Start)
+                .margin_top(12)
+                .css_classes(["heading"])
+                .build();
+            self.groups.append(&label);
+
+            let images = group
+                .entries
+                .iter()
+                .map(|entry| Image::for_history_entry(entry, source.as_ref()))
+                .collect::<Vec<_>>();
+            let flow_box = gtk::FlowBox::builder()
+                .selection_mode(gtk::SelectionMode::Single)
+                .homogeneous(true)
+                .min_children_per_line(2)
+                .max_children_per_line(6)
+                .column_spacing(12)
+                .row_spacing(12)
+                .build();
+            for image in &images {
+                flow_box.append(&thumbnail(image));
+            }
+            flow_box.connect_selected_children_changed(glib::clone!(
+                #[weak(rename_to = page)]
+                self.obj(),
+                move |flow_box| {
+                    let image = flow_box
+                        .selected_children()
+                        .first()
+                        .and_then(|child| usize::try_from(child.index()).ok())
+                        .and_then(|index| images.get(index).cloned());
+                    if let Some(image) = image {
+                        page.imp().select_in_flow_box(flow_box, image);
+                    }
+                }
+            ));
+            self.groups.append(&flow_box);
+            self.flow_boxes.borrow_mut().push(flow_box);
+        }
+
+        pub fn set_entries(&self, entries: &[HistoryEntry], sources: &SourceRegistry) {
+            self.flow_boxes.borrow_mut().clear();
+            while let Some(child) = self.groups.first_child() {
+                self.groups.remove(&child);
+            }
+            self.select_image(None);
+
+            let groups = group_entries(entries);
+            for group in &groups {
+                self.add_group(group, sources);
+            }
+            if groups.is_empty() {
+                self.stack.set_visible_child(&*self.empty);
+            } else {
+                self.stack.set_visible_child(&*self.history);
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HistoryPage {
+        const NAME: &'static str = "PotDHistoryPage";
+
+        type Type = super::HistoryPage;
+
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for HistoryPage {}
+
+    impl WidgetImpl for HistoryPage {}
+
+    impl BinImpl for HistoryPage {}
+}
@@ -55,6 +55,11 @@ mod imp {
                 ImageState::DownloadFailed => self.error.get(),
             }
         }
+
+        #[template_callback(function)]
+        fn has_download_progress(progress: f64) -> bool {
+            0.0 < progress
+        }
     }
 
     #[glib::object_subclass]
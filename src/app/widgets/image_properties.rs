@@ -0,0 +1,73 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+use glib::Object;
+
+glib::wrapper! {
+    /// Show the metadata of an image.
+    pub struct ImageProperties(ObjectSubclass<imp::ImageProperties>)
+        @extends adw::Bin, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for ImageProperties {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
+
+mod imp {
+    use std::cell::RefCell;
+
+    use adw::subclass::prelude::*;
+    use glib::{Properties, subclass::InitializingObject};
+    use gtk::CompositeTemplate;
+
+    use crate::app::model::Image;
+
+    #[derive(Default, CompositeTemplate, Properties)]
+    #[properties(wrapper_type = super::ImageProperties)]
+    #[template(resource = "/de/swsnr/pictureoftheday/ui/image-properties.ui")]
+    pub struct ImageProperties {
+        #[property(get, set)]
+        image: RefCell<Option<Image>>,
+    }
+
+    #[gtk::template_callbacks]
+    impl ImageProperties {
+        #[template_callback(function)]
+        fn non_empty(s: Option<&str>) -> bool {
+            s.is_some_and(|s| !s.is_empty())
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ImageProperties {
+        const NAME: &'static str = "PotDImageProperties";
+
+        type Type = super::ImageProperties;
+
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            Image::ensure_type();
+
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for ImageProperties {}
+
+    impl WidgetImpl for ImageProperties {}
+
+    impl BinImpl for ImageProperties {}
+}
@@ -8,6 +8,8 @@ use adw::prelude::*;
 use glib::Object;
 use gtk::gio;
 
+use crate::images::SourceRegistry;
+
 glib::wrapper! {
     pub struct PreferencesDialog(ObjectSubclass<imp::PreferencesDialog>)
         @extends adw::PreferencesDialog, adw::Dialog, gtk::Widget,
@@ -17,6 +19,10 @@ glib::wrapper! {
 impl PreferencesDialog {
     pub fn bind(&self, settings: &gio::Settings) {
         settings.bind("apod-api-key", self, "apod-api-key").build();
+        settings.bind("local-folder", self, "local-folder").build();
+        settings
+            .bind("local-folder-shuffle", self, "local-folder-shuffle")
+            .build();
         settings
             .bind(
                 "update-wallpaper-over-metered-network",
@@ -31,6 +37,84 @@ impl PreferencesDialog {
                 "stalenhag-disabled-collections",
             )
             .build();
+        settings
+            .bind("images-keep-days", self, "images-keep-days")
+            .build();
+        settings
+            .bind("images-max-size", self, "images-max-size")
+            .build();
+        settings
+            .bind("update-schedule-hour", self, "update-schedule-hour")
+            .build();
+        settings
+            .bind("update-schedule-minute", self, "update-schedule-minute")
+            .build();
+        settings
+            .bind("update-schedule-interval", self, "update-schedule-interval")
+            .build();
+        settings.bind("slideshow", self, "slideshow").build();
+        settings
+            .bind("slideshow-interval", self, "slideshow-interval")
+            .build();
+        settings
+            .bind("slideshow-days", self, "slideshow-days")
+            .build();
+        settings
+            .bind("min-image-width", self, "min-image-width")
+            .build();
+        settings
+            .bind("min-image-height", self, "min-image-height")
+            .build();
+        settings
+            .bind("min-aspect-ratio", self, "min-aspect-ratio")
+            .build();
+        settings
+            .bind("max-aspect-ratio", self, "max-aspect-ratio")
+            .build();
+        settings
+            .bind("wallpaper-command", self, "wallpaper-command")
+            .build();
+        settings
+            .bind("dark-wallpaper-dimming", self, "dark-wallpaper-dimming")
+            .build();
+        settings.bind("caption", self, "caption").build();
+        settings
+            .bind("caption-font-size", self, "caption-font-size")
+            .build();
+        settings
+            .bind("caption-opacity", self, "caption-opacity")
+            .build();
+        self.imp().bind_update_schedule(settings);
+        self.imp().bind_wallpaper_fit(settings);
+        self.imp().bind_multi_monitor(settings);
+        self.imp().bind_wallpaper_target(settings);
+        self.imp().bind_wallpaper_backend(settings);
+        self.imp().bind_dark_wallpaper(settings);
+        self.imp().bind_caption_position(settings);
+        self.imp().bind_feeds(settings);
+    }
+
+    /// Bind settings which refer to `sources` to `settings`.
+    ///
+    /// This includes fallback sources, rotation through sources, and the
+    /// source for the lock screen.
+    pub fn bind_sources(&self, settings: &gio::Settings, sources: &SourceRegistry) {
+        self.imp().bind_fallback_sources(settings, sources);
+        self.imp().bind_rotation(settings);
+        self.imp().bind_lock_screen_source(settings);
+    }
+
+    /// Show how much disk space downloaded images of `sources` take.
+    pub fn show_disk_usage(&self, sources: &SourceRegistry) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = dialog)]
+            self,
+            #[strong]
+            sources,
+            async move {
+                dialog.imp().show_disk_usage(&sources).await;
+            }
+        ));
     }
 }
 
@@ -46,12 +130,19 @@ mod imp {
     use adw::prelude::*;
     use adw::subclass::prelude::*;
     use formatx::formatx;
-    use glib::{Properties, StrV, dngettext, subclass::InitializingObject};
+    use glib::{
+        Properties, SignalHandlerId, StrV, dngettext, dpgettext2, markup_escape_text,
+        subclass::InitializingObject,
+    };
     use gnome_app_utils::app::SessionLockedMonitor;
-    use gtk::CompositeTemplate;
+    use gtk::{CompositeTemplate, gio};
 
     use crate::config::G_LOG_DOMAIN;
-    use crate::images::{Source, stalenhag};
+    use crate::images::feed::{self, Feed, FeedImageRule};
+    use crate::images::{
+        Source, SourceCapabilities, SourceProvider, SourceRegistry, apod, local, stalenhag,
+    };
+    use crate::retention::{list_image_files, size_per_source};
 
     #[derive(Default, CompositeTemplate, Properties)]
     #[properties(wrapper_type = super::PreferencesDialog)]
@@ -61,16 +152,835 @@ mod imp {
         apod_api_key: RefCell<String>,
         #[property(get, set)]
         stalenhag_disabled_collections: RefCell<StrV>,
+        #[property(get, set)]
+        local_folder: RefCell<String>,
+        #[property(get, set)]
+        local_folder_shuffle: Cell<bool>,
         #[property(get)]
         connected_to_logind: Cell<bool>,
         #[property(get, set)]
         update_wallpaper_over_metered_network: Cell<bool>,
+        #[property(get, set)]
+        update_schedule_hour: Cell<u32>,
+        #[property(get, set)]
+        update_schedule_minute: Cell<u32>,
+        #[property(get, set)]
+        update_schedule_interval: Cell<u32>,
+        #[property(get, set)]
+        slideshow: Cell<bool>,
+        #[property(get, set)]
+        slideshow_interval: Cell<u32>,
+        #[property(get, set)]
+        slideshow_days: Cell<u32>,
+        #[property(get, set)]
+        min_image_width: Cell<u32>,
+        #[property(get, set)]
+        min_image_height: Cell<u32>,
+        #[property(get, set)]
+        min_aspect_ratio: Cell<f64>,
+        #[property(get, set)]
+        max_aspect_ratio: Cell<f64>,
+        #[property(get, set)]
+        wallpaper_command: RefCell<String>,
+        #[property(get, set)]
+        dark_wallpaper_dimming: Cell<u32>,
+        #[property(get, set)]
+        caption: Cell<bool>,
+        #[property(get, set)]
+        caption_font_size: Cell<u32>,
+        #[property(get, set)]
+        caption_opacity: Cell<u32>,
+        #[property(get, set)]
+        images_keep_days: Cell<u32>,
+        #[property(get, set)]
+        images_max_size: Cell<u32>,
         #[template_child]
         group_apod: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
         group_stalenhag: TemplateChild<adw::PreferencesGroup>,
         #[template_child]
         stalenhag_collections: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        group_local: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        group_feeds: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        feed_name: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        feed_url: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        feed_rule: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        update_schedule: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        wallpaper_fit: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        multi_monitor: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        dark_wallpaper: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        caption_position: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        wallpaper_backend: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        wallpaper_target: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        lock_screen_source: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        rotation_mode: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        rotation_sources: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        rotation_weekdays: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        group_fallback_sources: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        fallback_source: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        group_disk_usage: TemplateChild<adw::PreferencesGroup>,
+        feed_rows: RefCell<Vec<adw::ActionRow>>,
+        /// The settings feeds are bound to, and the handler for changed feeds.
+        feeds_changed_handler: RefCell<Option<(gio::Settings, SignalHandlerId)>>,
+        /// All sources, in the order of the fallback source combo row.
+        sources: RefCell<Vec<Source>>,
+        fallback_rows: RefCell<Vec<adw::ActionRow>>,
+        /// The settings fallback sources are bound to, and the handler for changed fallback sources.
+        fallback_sources_changed_handler: RefCell<Option<(gio::Settings, SignalHandlerId)>>,
+    }
+
+    impl PreferencesDialog {
+        pub fn bind_feeds(&self, settings: &gio::Settings) {
+            self.update_feed_rows(settings);
+            let handler = settings.connect_changed(
+                Some("feed-sources"),
+                glib::clone!(
+                    #[weak(rename_to = dialog)]
+                    self.obj(),
+                    move |settings, _| {
+                        dialog.imp().update_feed_rows(settings);
+                    }
+                ),
+            );
+            if let Some((old_settings, old_handler)) = self
+                .feeds_changed_handler
+                .replace(Some((settings.clone(), handler)))
+            {
+                old_settings.disconnect(old_handler);
+            }
+        }
+
+        pub fn bind_fallback_sources(&self, settings: &gio::Settings, sources: &SourceRegistry) {
+            let sources = sources.sources();
+            let names = gtk::StringList::new(&[]);
+            for source in &sources {
+                names.append(&source.i18n_name());
+            }
+            self.fallback_source.set_model(Some(&names));
+            self.sources.replace(sources);
+            self.update_fallback_rows(settings);
+            let handler = settings.connect_changed(
+                Some("fallback-sources"),
+                glib::clone!(
+                    #[weak(rename_to = dialog)]
+                    self.obj(),
+                    move |settings, _| {
+                        dialog.imp().update_fallback_rows(settings);
+                    }
+                ),
+            );
+            if let Some((old_settings, old_handler)) = self
+                .fallback_sources_changed_handler
+                .replace(Some((settings.clone(), handler)))
+            {
+                old_settings.disconnect(old_handler);
+            }
+        }
+
+        pub fn bind_rotation(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.rotation_mode,
+                settings,
+                "rotation-mode",
+                &rotation_modes(),
+            );
+
+            let sources = self.sources.borrow();
+            let weights = settings.get::<Vec<(String, u32)>>("rotation-sources");
+            for source in sources.iter() {
+                let weight = weights
+                    .iter()
+                    .find(|(id, _)| id == source.id())
+                    .map_or(0, |(_, weight)| *weight);
+                let row = adw::SpinRow::with_range(0.0, 10.0, 1.0);
+                row.set_title(&markup_escape_text(&source.i18n_name()));
+                row.set_value(f64::from(weight));
+                let id = source.id().to_owned();
+                row.connect_value_notify(glib::clone!(
+                    #[strong]
+                    settings,
+                    move |row| {
+                        let weight = row
+                            .property_value("value")
+                            .transform::<u32>()
+                            .ok()
+                            .and_then(|value| value.get::<u32>().ok())
+                            .unwrap_or(0);
+                        let mut weights = settings.get::<Vec<(String, u32)>>("rotation-sources");
+                        match weights.iter_mut().find(|(source, _)| *source == id) {
+                            Some((_, old_weight)) => *old_weight = weight,
+                            None => weights.push((id.clone(), weight)),
+                        }
+                        if let Err(error) = settings.set("rotation-sources", weights) {
+                            glib::warn!("Failed to store rotation sources: {error}");
+                        }
+                    }
+                ));
+                self.rotation_sources.add_row(&row);
+            }
+
+            let names = gtk::StringList::new(&[dpgettext2(
+                None,
+                "preferences-dialog.rotation-weekday",
+                "Selected source",
+            )
+            .as_str()]);
+            for source in sources.iter() {
+                names.append(&source.i18n_name());
+            }
+            let weekdays = settings.get::<Vec<String>>("rotation-weekdays");
+            for day in 0..7 {
+                // 2024-01-01 was a Monday
+                let title = glib::DateTime::from_local(2024, 1, 1 + day, 0, 0, 0.0)
+                    .and_then(|date| date.format("%A"))
+                    .map_or_else(|_| (day + 1).to_string(), Into::into);
+                let row = adw::ComboRow::builder().title(title).model(&names).build();
+                let weekday = usize::try_from(day).unwrap();
+                let selected = weekdays
+                    .get(weekday)
+                    .and_then(|id| sources.iter().position(|source| source.id() == id))
+                    .and_then(|index| u32::try_from(index + 1).ok())
+                    .unwrap_or(0);
+                row.set_selected(selected);
+                let ids = sources
+                    .iter()
+                    .map(|source| source.id().to_owned())
+                    .collect::<Vec<_>>();
+                row.connect_selected_notify(glib::clone!(
+                    #[strong]
+                    settings,
+                    move |row| {
+                        let id = usize::try_from(row.selected())
+                            .ok()
+                            .and_then(|index| index.checked_sub(1))
+                            .and_then(|index| ids.get(index))
+                            .cloned()
+                            .unwrap_or_default();
+                        let mut weekdays = settings.get::<Vec<String>>("rotation-weekdays");
+                        weekdays.resize(7, String::new());
+                        if let Some(entry) = weekdays.get_mut(weekday) {
+                            *entry = id;
+                        }
+                        if let Err(error) = settings.set("rotation-weekdays", weekdays) {
+                            glib::warn!("Failed to store rotation weekdays: {error}");
+                        }
+                    }
+                ));
+                self.rotation_weekdays.add_row(&row);
+            }
+        }
+
+        fn update_fallback_rows(&self, settings: &gio::Settings) {
+            for row in self.fallback_rows.take() {
+                self.group_fallback_sources.remove(&row);
+            }
+            let fallbacks = settings.get::<Vec<String>>("fallback-sources");
+            for (index, id) in fallbacks.iter().enumerate() {
+                let name = self
+                    .sources
+                    .borrow()
+                    .iter()
+                    .find(|source| source.id() == id)
+                    .map_or_else(|| id.clone(), |source| source.i18n_name().into());
+                let row = adw::ActionRow::builder()
+                    .title(markup_escape_text(&name))
+                    .build();
+                let raise = gtk::Button::builder()
+                    .icon_name("go-up-symbolic")
+                    .tooltip_text(dpgettext2(
+                        None,
+                        "preferences-dialog.button.tooltip",
+                        "Try this source earlier",
+                    ))
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .action_name("fallback-sources.raise")
+                    .sensitive(0 < index)
+                    .build();
+                raise.set_action_target(Some(id.to_variant()));
+                row.add_suffix(&raise);
+                let remove = gtk::Button::builder()
+                    .icon_name("cross-large-symbolic")
+                    .tooltip_text(dpgettext2(
+                        None,
+                        "preferences-dialog.button.tooltip",
+                        "Remove fallback source",
+                    ))
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .action_name("fallback-sources.remove")
+                    .build();
+                remove.set_action_target(Some(id.to_variant()));
+                row.add_suffix(&remove);
+                self.group_fallback_sources.add(&row);
+                self.fallback_rows.borrow_mut().push(row);
+            }
+        }
+
+        /// Change the list of fallback sources with `f`.
+        fn update_fallback_sources<F: FnOnce(&mut Vec<String>)>(&self, f: F) {
+            let Some(settings) = bound_settings(&self.fallback_sources_changed_handler) else {
+                return;
+            };
+            let mut fallbacks = settings.get::<Vec<String>>("fallback-sources");
+            f(&mut fallbacks);
+            if let Err(error) = settings.set("fallback-sources", fallbacks) {
+                glib::warn!("Failed to store fallback sources: {error}");
+            }
+        }
+
+        fn add_fallback_source(&self) {
+            let Some(id) = usize::try_from(self.fallback_source.selected())
+                .ok()
+                .and_then(|index| {
+                    self.sources
+                        .borrow()
+                        .get(index)
+                        .map(|source| source.id().to_owned())
+                })
+            else {
+                return;
+            };
+            self.update_fallback_sources(|fallbacks| {
+                if !fallbacks.contains(&id) {
+                    fallbacks.push(id);
+                }
+            });
+        }
+
+        fn raise_fallback_source(&self, id: &str) {
+            self.update_fallback_sources(|fallbacks| {
+                if let Some(index) = fallbacks.iter().position(|fallback| fallback == id)
+                    && 0 < index
+                {
+                    fallbacks.swap(index - 1, index);
+                }
+            });
+        }
+
+        fn remove_fallback_source(&self, id: &str) {
+            self.update_fallback_sources(|fallbacks| {
+                fallbacks.retain(|fallback| fallback != id);
+            });
+        }
+
+        async fn choose_local_folder(&self) {
+            let dialog = gtk::FileDialog::new();
+            dialog.set_title(&dpgettext2(
+                None,
+                "file-dialog.title",
+                "Choose folder with wallpapers",
+            ));
+            let current_folder = self.local_folder.borrow().clone();
+            if !current_folder.is_empty() {
+                dialog.set_initial_folder(Some(&gio::File::for_path(current_folder)));
+            }
+            let parent = self.obj().root().and_downcast::<gtk::Window>();
+            match dialog.select_folder_future(parent.as_ref()).await {
+                Ok(folder) => {
+                    if let Some(path) = folder.path() {
+                        self.obj()
+                            .set_local_folder(path.to_string_lossy().into_owned());
+                    } else {
+                        glib::warn!("Chosen folder {} has no local path", folder.uri());
+                    }
+                }
+                Err(error) if error.matches(gtk::DialogError::Dismissed) => {}
+                Err(error) => {
+                    glib::warn!("Failed to choose folder: {error}");
+                }
+            }
+        }
+
+        pub fn bind_update_schedule(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.update_schedule,
+                settings,
+                "update-schedule",
+                &update_schedules(),
+            );
+        }
+
+        pub fn bind_wallpaper_fit(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.wallpaper_fit,
+                settings,
+                "wallpaper-fit",
+                &wallpaper_fits(),
+            );
+        }
+
+        pub fn bind_wallpaper_target(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.wallpaper_target,
+                settings,
+                "wallpaper-target",
+                &wallpaper_targets(),
+            );
+        }
+
+        pub fn bind_multi_monitor(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.multi_monitor,
+                settings,
+                "multi-monitor",
+                &multi_monitor_modes(),
+            );
+        }
+
+        pub fn bind_wallpaper_backend(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.wallpaper_backend,
+                settings,
+                "wallpaper-backend",
+                &wallpaper_backends(),
+            );
+        }
+
+        pub fn bind_dark_wallpaper(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.dark_wallpaper,
+                settings,
+                "dark-wallpaper",
+                &dark_variants(),
+            );
+        }
+
+        pub fn bind_caption_position(&self, settings: &gio::Settings) {
+            bind_combo_row(
+                &self.caption_position,
+                settings,
+                "caption-position",
+                &caption_positions(),
+            );
+        }
+
+        pub fn bind_lock_screen_source(&self, settings: &gio::Settings) {
+            // An empty ID shows the same image as on the desktop
+            let same_image = (
+                String::new(),
+                dpgettext2(
+                    None,
+                    "preferences-dialog.lock-screen-source",
+                    "Same image as desktop",
+                ),
+            );
+            let choices = std::iter::once(same_image)
+                .chain(
+                    self.sources
+                        .borrow()
+                        .iter()
+                        .map(|source| (source.id().to_owned(), source.i18n_name())),
+                )
+                .collect::<Vec<_>>();
+            bind_combo_row(
+                &self.lock_screen_source,
+                settings,
+                "lock-screen-source",
+                &choices,
+            );
+        }
+
+        pub async fn show_disk_usage(&self, sources: &SourceRegistry) {
+            let directory = crate::config::images_directory();
+            let result = gio::spawn_blocking(move || {
+                list_image_files(&directory).map(|files| size_per_source(&files))
+            })
+            .await;
+            let sizes = match result {
+                Ok(Ok(sizes)) => sizes,
+                Ok(Err(error)) => {
+                    glib::warn!("Failed to compute disk usage: {error}");
+                    return;
+                }
+                Err(_) => {
+                    glib::warn!("Computing disk usage panicked");
+                    return;
+                }
+            };
+            let total = sizes.iter().map(|(_, size)| size).sum::<u64>();
+            self.group_disk_usage.set_description(Some(
+                &formatx!(
+                    dpgettext2(
+                        None,
+                        "preferences-dialog.group.description",
+                        "Downloaded images take {size} in total.",
+                    ),
+                    size = glib::format_size(total)
+                )
+                .unwrap(),
+            ));
+            for (source_id, size) in sizes {
+                let title = sources
+                    .lookup(&source_id)
+                    .map_or_else(|| source_id.clone(), |source| source.i18n_name().into());
+                let row = adw::ActionRow::builder()
+                    .title(markup_escape_text(&title))
+                    .build();
+                row.add_suffix(&gtk::Label::new(Some(&glib::format_size(size))));
+                self.group_disk_usage.add(&row);
+            }
+        }
+
+        fn update_feed_rows(&self, settings: &gio::Settings) {
+            for row in self.feed_rows.take() {
+                self.group_feeds.remove(&row);
+            }
+            for feed in feed::feeds_from_settings(settings) {
+                let row = adw::ActionRow::builder()
+                    .title(markup_escape_text(feed.name()))
+                    .subtitle(format!(
+                        "{} · {}",
+                        markup_escape_text(feed.url()),
+                        markup_escape_text(&feed.rule().i18n_name())
+                    ))
+                    .build();
+                let remove = gtk::Button::builder()
+                    .icon_name("cross-large-symbolic")
+                    .tooltip_text(dpgettext2(
+                        None,
+                        "preferences-dialog.button.tooltip",
+                        "Remove feed",
+                    ))
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .action_name("feeds.remove")
+                    .build();
+                remove.set_action_target(Some(feed.id().to_variant()));
+                row.add_suffix(&remove);
+                self.group_feeds.add(&row);
+                self.feed_rows.borrow_mut().push(row);
+            }
+        }
+
+        fn add_feed(&self) {
+            let Some(settings) = bound_settings(&self.feeds_changed_handler) else {
+                return;
+            };
+            let name = self.feed_name.text().trim().to_owned();
+            let url = self.feed_url.text().trim().to_owned();
+            let is_valid_url = glib::Uri::parse(&url, glib::UriFlags::NONE)
+                .is_ok_and(|uri| matches!(uri.scheme().as_str(), "http" | "https"));
+            for (row, is_valid) in [
+                (&self.feed_name, !name.is_empty()),
+                (&self.feed_url, is_valid_url),
+            ] {
+                if is_valid {
+                    row.remove_css_class("error");
+                } else {
+                    row.add_css_class("error");
+                }
+            }
+            if name.is_empty() || !is_valid_url {
+                return;
+            }
+
+            let rule = usize::try_from(self.feed_rule.selected())
+                .ok()
+                .and_then(|index| FeedImageRule::ALL.get(index))
+                .copied()
+                .unwrap_or(FeedImageRule::MediaContent);
+            let new_feed = Feed::new(name, url, rule);
+            let mut feeds = feed::feeds_from_settings(&settings);
+            // Replace an existing feed with the same URL
+            feeds.retain(|feed| feed.id() != new_feed.id());
+            feeds.push(new_feed);
+            match feed::store_feeds(&settings, &feeds) {
+                Ok(()) => {
+                    self.feed_name.set_text("");
+                    self.feed_url.set_text("");
+                }
+                Err(error) => {
+                    glib::warn!("Failed to store feeds: {error}");
+                }
+            }
+        }
+
+        fn remove_feed(&self, id: &str) {
+            let Some(settings) = bound_settings(&self.feeds_changed_handler) else {
+                return;
+            };
+            let mut feeds = feed::feeds_from_settings(&settings);
+            feeds.retain(|feed| feed.id() != id);
+            if let Err(error) = feed::store_feeds(&settings, &feeds) {
+                glib::warn!("Failed to store feeds: {error}");
+            } else if settings.string("selected-source") == id {
+                // Don't leave the removed feed selected
+                settings.reset("selected-source");
+            }
+        }
+    }
+
+    /// Get the settings bound together with `handler`, if any.
+    fn bound_settings(
+        handler: &RefCell<Option<(gio::Settings, SignalHandlerId)>>,
+    ) -> Option<gio::Settings> {
+        handler
+            .borrow()
+            .as_ref()
+            .map(|(settings, _)| settings.clone())
+    }
+
+    /// A choice in a combo row: the ID stored in settings, and the label shown.
+    type Choice = (&'static str, glib::GString);
+
+    /// Show `choices` in `row` and bind the selected choice to the string setting `key`.
+    fn bind_combo_row<I: AsRef<str>, L: AsRef<str>>(
+        row: &adw::ComboRow,
+        settings: &gio::Settings,
+        key: &str,
+        choices: &[(I, L)],
+    ) {
+        let labels = choices
+            .iter()
+            .map(|(_, label)| label.as_ref())
+            .collect::<Vec<_>>();
+        row.set_model(Some(&gtk::StringList::new(&labels)));
+        let ids = choices
+            .iter()
+            .map(|(id, _)| id.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let set_ids = ids.clone();
+        settings
+            .bind(key, row, "selected")
+            .mapping(move |variant, _| {
+                let id = variant.str()?;
+                let index = ids.iter().position(|choice| choice == id)?;
+                Some(u32::try_from(index).ok()?.to_value())
+            })
+            .set_mapping(move |value, _| {
+                let index = usize::try_from(value.get::<u32>().ok()?).ok()?;
+                Some(set_ids.get(index)?.to_variant())
+            })
+            .build();
+    }
+
+    /// Get the ID of the choice at `index`.
+    fn choice_id(choices: &[Choice], index: u32) -> Option<&'static str> {
+        choices.get(usize::try_from(index).ok()?).map(|(id, _)| *id)
+    }
+
+    fn update_schedules() -> [Choice; 3] {
+        [
+            (
+                "daily",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.update-schedule",
+                    "Daily at a fixed time",
+                ),
+            ),
+            (
+                "interval",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.update-schedule",
+                    "Every few hours",
+                ),
+            ),
+            (
+                "published",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.update-schedule",
+                    "As soon as the source publishes",
+                ),
+            ),
+        ]
+    }
+
+    fn rotation_modes() -> [Choice; 4] {
+        [
+            (
+                "off",
+                dpgettext2(None, "preferences-dialog.rotation-mode", "Off"),
+            ),
+            (
+                "round-robin",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.rotation-mode",
+                    "One after another, by day",
+                ),
+            ),
+            (
+                "weighted-random",
+                dpgettext2(None, "preferences-dialog.rotation-mode", "Weighted random"),
+            ),
+            (
+                "weekday",
+                dpgettext2(None, "preferences-dialog.rotation-mode", "By weekday"),
+            ),
+        ]
+    }
+
+    fn wallpaper_fits() -> [Choice; 4] {
+        [
+            (
+                "original",
+                dpgettext2(None, "preferences-dialog.wallpaper-fit", "Original image"),
+            ),
+            (
+                "scale",
+                dpgettext2(None, "preferences-dialog.wallpaper-fit", "Scale down"),
+            ),
+            (
+                "crop",
+                dpgettext2(None, "preferences-dialog.wallpaper-fit", "Crop to screen"),
+            ),
+            (
+                "letterbox",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-fit",
+                    "Letterbox with blurred background",
+                ),
+            ),
+        ]
+    }
+
+    fn wallpaper_targets() -> [Choice; 3] {
+        [
+            (
+                "both",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-target",
+                    "Desktop and lock screen",
+                ),
+            ),
+            (
+                "background",
+                dpgettext2(None, "preferences-dialog.wallpaper-target", "Desktop only"),
+            ),
+            (
+                "lock-screen",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-target",
+                    "Lock screen only",
+                ),
+            ),
+        ]
+    }
+
+    fn wallpaper_backends() -> [Choice; 3] {
+        [
+            (
+                "portal",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-backend",
+                    "Desktop portal",
+                ),
+            ),
+            (
+                "gsettings",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-backend",
+                    "GNOME settings",
+                ),
+            ),
+            (
+                "command",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.wallpaper-backend",
+                    "Custom command",
+                ),
+            ),
+        ]
+    }
+
+    fn dark_variants() -> [Choice; 3] {
+        [
+            (
+                "same",
+                dpgettext2(None, "preferences-dialog.dark-wallpaper", "Same image"),
+            ),
+            (
+                "dimmed",
+                dpgettext2(None, "preferences-dialog.dark-wallpaper", "Dimmed image"),
+            ),
+            (
+                "other-image",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.dark-wallpaper",
+                    "Different image of the day",
+                ),
+            ),
+        ]
+    }
+
+    fn caption_positions() -> [Choice; 4] {
+        [
+            (
+                "bottom-right",
+                dpgettext2(None, "preferences-dialog.caption-position", "Bottom right"),
+            ),
+            (
+                "bottom-left",
+                dpgettext2(None, "preferences-dialog.caption-position", "Bottom left"),
+            ),
+            (
+                "top-right",
+                dpgettext2(None, "preferences-dialog.caption-position", "Top right"),
+            ),
+            (
+                "top-left",
+                dpgettext2(None, "preferences-dialog.caption-position", "Top left"),
+            ),
+        ]
+    }
+
+    fn multi_monitor_modes() -> [Choice; 3] {
+        [
+            (
+                "same",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.multi-monitor",
+                    "Same image on every monitor",
+                ),
+            ),
+            (
+                "per-monitor",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.multi-monitor",
+                    "Different image on every monitor",
+                ),
+            ),
+            (
+                "span",
+                dpgettext2(
+                    None,
+                    "preferences-dialog.multi-monitor",
+                    "One image across all monitors",
+                ),
+            ),
+        ]
     }
 
     #[gtk::template_callbacks]
@@ -93,6 +1003,76 @@ mod imp {
             .unwrap()
         }
 
+        #[template_callback(function)]
+        fn label_local_folder(folder: Option<&str>) -> String {
+            match folder {
+                Some(folder) if !folder.is_empty() => folder.to_owned(),
+                _ => dpgettext2(
+                    None,
+                    "preferences-dialog.row.description",
+                    "No folder chosen",
+                )
+                .into(),
+            }
+        }
+
+        #[template_callback(function)]
+        fn is_dimmed_dark_wallpaper(selected: u32) -> bool {
+            choice_id(&dark_variants(), selected) == Some("dimmed")
+        }
+
+        #[template_callback(function)]
+        fn is_command_backend(selected: u32) -> bool {
+            choice_id(&wallpaper_backends(), selected) == Some("command")
+        }
+
+        #[template_callback(function)]
+        fn is_both_targets(selected: u32) -> bool {
+            choice_id(&wallpaper_targets(), selected) == Some("both")
+        }
+
+        #[template_callback(function)]
+        fn is_source_rotation(selected: u32) -> bool {
+            matches!(
+                choice_id(&rotation_modes(), selected),
+                Some("round-robin" | "weighted-random")
+            )
+        }
+
+        #[template_callback(function)]
+        fn label_rotation_sources(selected: u32) -> String {
+            if choice_id(&rotation_modes(), selected) == Some("weighted-random") {
+                dpgettext2(
+                    None,
+                    "preferences-dialog.row.description",
+                    "The weight of each source; 0 excludes a source",
+                )
+                .into()
+            } else {
+                dpgettext2(
+                    None,
+                    "preferences-dialog.row.description",
+                    "Rotate through every source with a weight above 0",
+                )
+                .into()
+            }
+        }
+
+        #[template_callback(function)]
+        fn is_weekday_rotation(selected: u32) -> bool {
+            choice_id(&rotation_modes(), selected) == Some("weekday")
+        }
+
+        #[template_callback(function)]
+        fn is_daily_schedule(selected: u32) -> bool {
+            choice_id(&update_schedules(), selected) == Some("daily")
+        }
+
+        #[template_callback(function)]
+        fn is_interval_schedule(selected: u32) -> bool {
+            choice_id(&update_schedules(), selected) == Some("interval")
+        }
+
         #[template_callback]
         fn flatpak_override_command() -> String {
             use crate::config::APP_ID;
@@ -125,6 +1105,48 @@ mod imp {
                 dialog.notify_connected_to_logind();
             });
 
+            klass.install_action_async("local-folder.choose", None, |dialog, _, _| async move {
+                dialog.imp().choose_local_folder().await;
+            });
+
+            klass.install_action("feeds.add", None, |dialog, _, _| {
+                dialog.imp().add_feed();
+            });
+
+            klass.install_action(
+                "feeds.remove",
+                Some(glib::VariantTy::STRING),
+                |dialog, _, parameter| {
+                    if let Some(id) = parameter.and_then(glib::Variant::str) {
+                        dialog.imp().remove_feed(id);
+                    }
+                },
+            );
+
+            klass.install_action("fallback-sources.add", None, |dialog, _, _| {
+                dialog.imp().add_fallback_source();
+            });
+
+            klass.install_action(
+                "fallback-sources.raise",
+                Some(glib::VariantTy::STRING),
+                |dialog, _, parameter| {
+                    if let Some(id) = parameter.and_then(glib::Variant::str) {
+                        dialog.imp().raise_fallback_source(id);
+                    }
+                },
+            );
+
+            klass.install_action(
+                "fallback-sources.remove",
+                Some(glib::VariantTy::STRING),
+                |dialog, _, parameter| {
+                    if let Some(id) = parameter.and_then(glib::Variant::str) {
+                        dialog.imp().remove_fallback_source(id);
+                    }
+                },
+            );
+
             klass.install_action("logind.copy", None, |dialog, _, _| {
                 dialog
                     .clipboard()
@@ -142,13 +1164,26 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            let source_groups = [
-                (Source::Apod, &self.group_apod),
-                (Source::Stalenhag, &self.group_stalenhag),
+            let source_groups: [(&dyn SourceProvider, _); 3] = [
+                (&apod::Apod, &self.group_apod),
+                (&stalenhag::Stalenhag, &self.group_stalenhag),
+                (&local::Local, &self.group_local),
             ];
             for (source, group) in source_groups {
                 group.set_title(&source.i18n_name());
-                group.set_description(Some(&format!("<a href=\"{0}\">{0}</a>", source.url())));
+                let link = source
+                    .url()
+                    .map_or_else(String::new, |url| format!("<a href=\"{url}\">{url}</a>"));
+                if source.capabilities().contains(SourceCapabilities::API_KEY) {
+                    let hint = dpgettext2(
+                        None,
+                        "preferences-dialog.group.description",
+                        "This source needs an API key.",
+                    );
+                    group.set_description(Some(&format!("{link}\n{hint}")));
+                } else if !link.is_empty() {
+                    group.set_description(Some(&link));
+                }
             }
 
             for collection in stalenhag::COLLECTIONS.iter() {
@@ -185,8 +1220,24 @@ mod imp {
                     .build();
             }
 
+            let rules = gtk::StringList::new(&[]);
+            for rule in FeedImageRule::ALL {
+                rules.append(&rule.i18n_name());
+            }
+            self.feed_rule.set_model(Some(&rules));
+
             self.obj().activate_action("logind.check", None).unwrap();
         }
+
+        fn dispose(&self) {
+            let handlers = [
+                self.feeds_changed_handler.take(),
+                self.fallback_sources_changed_handler.take(),
+            ];
+            for (settings, handler) in handlers.into_iter().flatten() {
+                settings.disconnect(handler);
+            }
+        }
     }
 
     impl WidgetImpl for PreferencesDialog {}
@@ -4,7 +4,7 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use crate::Source;
+use crate::images::Source;
 
 glib::wrapper! {
     pub struct SourceRow(ObjectSubclass<imp::SourceRow>)
@@ -13,40 +13,68 @@ glib::wrapper! {
 }
 
 impl SourceRow {
-    pub fn new(source: Source) -> Self {
+    pub fn new(source: &Source) -> Self {
         glib::Object::builder().property("source", source).build()
     }
 }
 
 mod imp {
-    use std::cell::Cell;
+    use std::cell::RefCell;
 
     use adw::prelude::*;
     use adw::subclass::prelude::*;
     use glib::subclass::InitializingObject;
-    use glib::{GString, Properties, markup_escape_text};
+    use glib::{GString, Properties, dpgettext2, markup_escape_text};
 
-    use crate::Source;
+    use crate::images::{Source, SourceCapabilities};
 
     #[derive(Default, Properties, gtk::CompositeTemplate)]
     #[properties(wrapper_type = super::SourceRow)]
     #[template(resource = "/de/swsnr/pictureoftheday/ui/source-row.ui")]
     pub struct SourceRow {
-        #[property(get, set, construct, builder(Source::default()))]
-        source: Cell<Source>,
+        #[property(get, construct_only)]
+        source: RefCell<Option<Source>>,
     }
 
     #[gtk::template_callbacks(functions)]
     impl SourceRow {
         #[template_callback]
-        fn source_title(source: Source) -> GString {
-            source.i18n_name()
+        #[allow(clippy::needless_pass_by_value)]
+        fn source_title(source: Option<Source>) -> GString {
+            source.map(|s| s.i18n_name()).unwrap_or_default()
         }
 
         #[template_callback]
-        fn source_subtitle(source: Source) -> String {
-            let url = markup_escape_text(source.url());
-            format!("<a href=\"{url}\">{url}</a>")
+        #[allow(clippy::needless_pass_by_value)]
+        fn source_subtitle(source: Option<Source>) -> String {
+            source.map_or_else(String::new, |source| {
+                let link = source.url().map_or_else(String::new, |url| {
+                    let url = markup_escape_text(url);
+                    format!("<a href=\"{url}\">{url}</a>")
+                });
+                let capabilities = source.capabilities();
+                let hints = [
+                    (
+                        SourceCapabilities::API_KEY,
+                        dpgettext2(None, "source-row.capability", "Needs API key"),
+                    ),
+                    (
+                        SourceCapabilities::FREE_LICENSE,
+                        dpgettext2(None, "source-row.capability", "Free license"),
+                    ),
+                ]
+                .into_iter()
+                .filter(|(capability, _)| capabilities.contains(*capability))
+                .map(|(_, hint)| markup_escape_text(&hint))
+                .collect::<Vec<_>>();
+                if hints.is_empty() {
+                    link
+                } else if link.is_empty() {
+                    hints.join(" · ")
+                } else {
+                    format!("{link}\n{}", hints.join(" · "))
+                }
+            })
         }
     }
 
@@ -0,0 +1,569 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Render a caption with title, source and copyright onto wallpapers.
+//!
+//! We draw the caption with cairo on the CPU, into a copy of the wallpaper.
+
+use std::path::{Path, PathBuf};
+
+use gtk::cairo::{self, Context, FontSlant, FontWeight, Format, ImageSurface};
+use gtk::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::fit::{Size, image_info, write_processed_image};
+use crate::images::ImageMetadata;
+
+/// The font family for captions.
+const CAPTION_FONT: &str = "Sans";
+
+/// The image height at which caption font sizes apply as is.
+///
+/// We scale captions with the height of the wallpaper, so that captions take
+/// the same share of the screen regardless of the resolution.
+const REFERENCE_HEIGHT: f64 = 1080.0;
+
+/// The opacity of the box behind the caption text.
+const BOX_ALPHA: f64 = 0.5;
+
+/// Where to put the caption on the wallpaper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptionPosition {
+    #[default]
+    BottomRight,
+    BottomLeft,
+    TopRight,
+    TopLeft,
+}
+
+impl CaptionPosition {
+    fn from_id(id: &str) -> Self {
+        match id {
+            "bottom-left" => Self::BottomLeft,
+            "top-right" => Self::TopRight,
+            "top-left" => Self::TopLeft,
+            _ => Self::BottomRight,
+        }
+    }
+
+    /// The identifier of this position, as stored in settings.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::BottomRight => "bottom-right",
+            Self::BottomLeft => "bottom-left",
+            Self::TopRight => "top-right",
+            Self::TopLeft => "top-left",
+        }
+    }
+
+    fn is_left(self) -> bool {
+        matches!(self, Self::BottomLeft | Self::TopLeft)
+    }
+
+    fn is_top(self) -> bool {
+        matches!(self, Self::TopRight | Self::TopLeft)
+    }
+}
+
+/// Settings for captions on wallpapers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptionSettings {
+    /// Whether to render a caption onto wallpapers.
+    pub enabled: bool,
+    /// Where to put the caption.
+    pub position: CaptionPosition,
+    /// The font size in pixels on a wallpaper of 1080 pixels height.
+    pub font_size: u32,
+    /// The opacity of the caption in percent.
+    pub opacity: u32,
+}
+
+impl Default for CaptionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            position: CaptionPosition::default(),
+            font_size: 18,
+            opacity: 80,
+        }
+    }
+}
+
+impl CaptionSettings {
+    /// Read settings for captions from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            enabled: settings.boolean("caption"),
+            position: CaptionPosition::from_id(&settings.string("caption-position")),
+            font_size: settings.uint("caption-font-size"),
+            opacity: settings.uint("caption-opacity"),
+        }
+    }
+
+    /// The suffix for captioned wallpapers with these settings.
+    fn file_suffix(&self) -> String {
+        format!(
+            "caption-{}-{}-{}",
+            self.position.id(),
+            self.font_size,
+            self.opacity
+        )
+    }
+}
+
+/// The text of a caption.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caption {
+    lines: Vec<String>,
+}
+
+impl Caption {
+    /// Create a caption for an image with `metadata` from the source `source_name`.
+    ///
+    /// Put the title in the first line, and the source and the copyright of
+    /// the image in the second line.
+    pub fn new(metadata: &ImageMetadata, source_name: &str) -> Self {
+        let details = match metadata.copyright.as_deref().map(str::trim) {
+            Some(copyright) if !copyright.is_empty() => format!("{source_name} · {copyright}"),
+            _ => source_name.to_owned(),
+        };
+        let lines = [metadata.title.trim().to_owned(), details]
+            .into_iter()
+            .filter(|line| !line.is_empty())
+            .collect();
+        Self { lines }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// A rectangle on the wallpaper, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rectangle {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// The font size of a caption with `font_size` on an image of `image_height`.
+fn scaled_font_size(font_size: u32, image_height: i32) -> f64 {
+    f64::from(font_size) * f64::from(image_height) / REFERENCE_HEIGHT
+}
+
+/// The space between the caption text and its box, for `font_size`.
+fn padding(font_size: f64) -> f64 {
+    font_size / 2.0
+}
+
+/// The space between the caption box and the edges of the image, for `font_size`.
+fn margin(font_size: f64) -> f64 {
+    font_size
+}
+
+/// The box for a caption with text of `text_width` and `text_height`.
+///
+/// Put the box at `position` on `image`, and leave room for padding and
+/// margin for `font_size`.
+fn caption_box(
+    position: CaptionPosition,
+    image: Size,
+    text_width: f64,
+    text_height: f64,
+    font_size: f64,
+) -> Rectangle {
+    let (margin, padding) = (margin(font_size), padding(font_size));
+    let width = text_width + 2.0 * padding;
+    let height = text_height + 2.0 * padding;
+    let x = if position.is_left() {
+        margin
+    } else {
+        f64::from(image.width) - margin - width
+    };
+    let y = if position.is_top() {
+        margin
+    } else {
+        f64::from(image.height) - margin - height
+    };
+    Rectangle {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+/// The width of the widest of `lines` with the current font of `context`.
+fn text_width(context: &Context, lines: &[String]) -> Result<f64, cairo::Error> {
+    lines.iter().try_fold(0.0_f64, |width, line| {
+        Ok(width.max(context.text_extents(line)?.x_advance()))
+    })
+}
+
+/// Draw `caption` with `settings` onto an image of `size` with `context`.
+///
+/// Shrink the caption if it's wider than the image.
+fn draw_caption(
+    context: &Context,
+    caption: &Caption,
+    size: Size,
+    settings: &CaptionSettings,
+    font_family: &str,
+) -> Result<(), cairo::Error> {
+    let mut font_size = scaled_font_size(settings.font_size, size.height);
+    context.select_font_face(font_family, FontSlant::Normal, FontWeight::Normal);
+    context.set_font_size(font_size);
+    let mut width = text_width(context, &caption.lines)?;
+    let available = f64::from(size.width) - 2.0 * margin(font_size) - 2.0 * padding(font_size);
+    if available < width {
+        font_size *= available / width;
+        context.set_font_size(font_size);
+        width = text_width(context, &caption.lines)?;
+    }
+    let font = context.font_extents()?;
+    let line_count = u32::try_from(caption.lines.len()).unwrap_or(u32::MAX);
+    let height = font.height() * f64::from(line_count);
+    let area = caption_box(settings.position, size, width, height, font_size);
+
+    // Draw box and text into a group first, to apply the opacity to the
+    // whole caption, and not to the box and the text separately.
+    context.push_group();
+    context.set_source_rgba(0.0, 0.0, 0.0, BOX_ALPHA);
+    context.rectangle(area.x, area.y, area.width, area.height);
+    context.fill()?;
+    context.set_source_rgb(1.0, 1.0, 1.0);
+    let padding = padding(font_size);
+    let mut baseline = area.y + padding + font.ascent();
+    for line in &caption.lines {
+        let x = if settings.position.is_left() {
+            area.x + padding
+        } else {
+            area.x + area.width - padding - context.text_extents(line)?.x_advance()
+        };
+        context.move_to(x, baseline);
+        context.show_text(line)?;
+        baseline += font.height();
+    }
+    context.pop_group_to_source()?;
+    context.paint_with_alpha(f64::from(settings.opacity.min(100)) / 100.0)?;
+    Ok(())
+}
+
+fn cairo_error(error: impl std::fmt::Display) -> glib::Error {
+    glib::Error::new(
+        IOErrorEnum::Failed,
+        &format!("Failed to draw caption: {error}"),
+    )
+}
+
+/// Convert a non-negative pixel dimension to `usize`.
+fn to_usize(value: i32) -> usize {
+    usize::try_from(value).unwrap_or_default()
+}
+
+/// Copy the pixels of `pixbuf` into a new cairo image surface.
+///
+/// Ignore the alpha channel of `pixbuf`, if any.
+fn surface_from_pixbuf(pixbuf: &Pixbuf) -> Result<ImageSurface, glib::Error> {
+    let (width, height) = (pixbuf.width(), pixbuf.height());
+    let mut surface = ImageSurface::create(Format::Rgb24, width, height).map_err(cairo_error)?;
+    let stride = to_usize(surface.stride());
+    let rowstride = to_usize(pixbuf.rowstride());
+    let channels = to_usize(pixbuf.n_channels());
+    let pixels = pixbuf.read_pixel_bytes();
+    {
+        let mut data = surface.data().map_err(cairo_error)?;
+        for (source, target) in pixels.chunks(rowstride).zip(data.chunks_mut(stride)) {
+            let source = source.chunks_exact(channels).take(to_usize(width));
+            for (source, target) in source.zip(target.chunks_exact_mut(4)) {
+                if let [red, green, blue, ..] = *source {
+                    target
+                        .copy_from_slice(&u32::from_be_bytes([0, red, green, blue]).to_ne_bytes());
+                }
+            }
+        }
+    }
+    surface.mark_dirty();
+    Ok(surface)
+}
+
+/// Copy the pixels of `surface` into a new pixbuf without alpha channel.
+fn pixbuf_from_surface(surface: &mut ImageSurface) -> Result<Pixbuf, glib::Error> {
+    surface.flush();
+    let (width, height) = (surface.width(), surface.height());
+    let stride = to_usize(surface.stride());
+    let data = surface.data().map_err(cairo_error)?;
+    let mut pixels = Vec::with_capacity(to_usize(width) * to_usize(height) * 3);
+    for row in data.chunks(stride).take(to_usize(height)) {
+        for pixel in row.chunks_exact(4).take(to_usize(width)) {
+            if let [a, b, c, d] = *pixel {
+                let [_, red, green, blue] = u32::from_ne_bytes([a, b, c, d]).to_be_bytes();
+                pixels.extend([red, green, blue]);
+            }
+        }
+    }
+    Ok(Pixbuf::from_bytes(
+        &glib::Bytes::from_owned(pixels),
+        Colorspace::Rgb,
+        false,
+        8,
+        width,
+        height,
+        width * 3,
+    ))
+}
+
+/// Render `caption` with `settings` onto a copy of `pixbuf`.
+fn render_caption(
+    pixbuf: &Pixbuf,
+    caption: &Caption,
+    settings: &CaptionSettings,
+    font_family: &str,
+) -> Result<Pixbuf, glib::Error> {
+    let mut surface = surface_from_pixbuf(pixbuf)?;
+    let size = Size {
+        width: pixbuf.width(),
+        height: pixbuf.height(),
+    };
+    {
+        let context = Context::new(&surface).map_err(cairo_error)?;
+        draw_caption(&context, caption, size, settings, font_family).map_err(cairo_error)?;
+    }
+    pixbuf_from_surface(&mut surface)
+}
+
+fn captioned_wallpaper_blocking(
+    source: &Path,
+    directory: &Path,
+    caption: &Caption,
+    settings: &CaptionSettings,
+) -> Result<PathBuf, glib::Error> {
+    let (format, _) = image_info(source)?;
+    write_processed_image(source, &format, directory, &settings.file_suffix(), || {
+        render_caption(&Pixbuf::from_file(source)?, caption, settings, CAPTION_FONT)
+    })
+}
+
+/// Render `caption` onto the wallpaper at `source`.
+///
+/// Write the captioned wallpaper to `directory` and return its path, or
+/// return `source` if `caption` is empty.  Reuse captioned wallpapers from
+/// earlier calls.
+///
+/// ## Errors
+///
+/// Return an error if we failed to load, draw or save the image.
+pub async fn captioned_wallpaper(
+    source: &Path,
+    directory: &Path,
+    caption: Caption,
+    settings: CaptionSettings,
+) -> Result<PathBuf, glib::Error> {
+    if caption.is_empty() {
+        return Ok(source.to_owned());
+    }
+    let source = source.to_owned();
+    let directory = directory.to_owned();
+    gio::spawn_blocking(move || {
+        captioned_wallpaper_blocking(&source, &directory, &caption, &settings)
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(glib::Error::new(
+            IOErrorEnum::Failed,
+            "Drawing caption panicked",
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identifiers of all caption positions, as stored in settings.
+    const CAPTION_POSITIONS: [&str; 4] = ["bottom-right", "bottom-left", "top-right", "top-left"];
+
+    /// The font for golden images, for stable results across systems.
+    const GOLDEN_FONT: &str = "DejaVu Sans";
+
+    fn metadata(title: &str, copyright: Option<&str>) -> ImageMetadata {
+        ImageMetadata {
+            title: title.into(),
+            description: None,
+            copyright: copyright.map(Into::into),
+            url: None,
+            source: "apod".into(),
+        }
+    }
+
+    fn size(width: i32, height: i32) -> Size {
+        Size { width, height }
+    }
+
+    #[test]
+    fn position_from_id() {
+        assert_eq!(
+            CAPTION_POSITIONS.map(CaptionPosition::from_id),
+            [
+                CaptionPosition::BottomRight,
+                CaptionPosition::BottomLeft,
+                CaptionPosition::TopRight,
+                CaptionPosition::TopLeft
+            ]
+        );
+        assert_eq!(
+            CaptionPosition::from_id("foo"),
+            CaptionPosition::BottomRight
+        );
+        for id in CAPTION_POSITIONS {
+            assert_eq!(CaptionPosition::from_id(id).id(), id);
+        }
+    }
+
+    #[test]
+    fn caption_lines() {
+        let caption = Caption::new(
+            &metadata("The Horsehead Nebula", Some(" Jane Doe ")),
+            "NASA Astronomy Picture of the Day",
+        );
+        assert_eq!(
+            caption.lines,
+            [
+                "The Horsehead Nebula",
+                "NASA Astronomy Picture of the Day · Jane Doe"
+            ]
+        );
+        let caption = Caption::new(&metadata("The Horsehead Nebula", Some("")), "APOD");
+        assert_eq!(caption.lines, ["The Horsehead Nebula", "APOD"]);
+        let caption = Caption::new(&metadata(" ", None), "APOD");
+        assert_eq!(caption.lines, ["APOD"]);
+        assert!(!caption.is_empty());
+    }
+
+    #[test]
+    fn font_size_scales_with_height() {
+        assert!((scaled_font_size(18, 1080) - 18.0).abs() < f64::EPSILON);
+        assert!((scaled_font_size(18, 2160) - 36.0).abs() < f64::EPSILON);
+        assert!((scaled_font_size(36, 540) - 18.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn caption_box_in_corners() {
+        let image = size(1920, 1080);
+        let boxes = CAPTION_POSITIONS
+            .map(CaptionPosition::from_id)
+            .map(|position| caption_box(position, image, 300.0, 40.0, 20.0));
+        let corners = boxes.map(|area| (area.x, area.y, area.width, area.height));
+        assert_eq!(
+            corners,
+            [
+                (1580.0, 1000.0, 320.0, 60.0),
+                (20.0, 1000.0, 320.0, 60.0),
+                (1580.0, 20.0, 320.0, 60.0),
+                (20.0, 20.0, 320.0, 60.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_suffix_includes_settings() {
+        let settings = CaptionSettings {
+            enabled: true,
+            position: CaptionPosition::TopLeft,
+            font_size: 24,
+            opacity: 60,
+        };
+        assert_eq!(settings.file_suffix(), "caption-top-left-24-60");
+    }
+
+    #[test]
+    fn pixbuf_roundtrip_through_surface() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, true, 8, 3, 2).unwrap();
+        pixbuf.fill(0x1234_56ff);
+        let copy = pixbuf_from_surface(&mut surface_from_pixbuf(&pixbuf).unwrap()).unwrap();
+        assert_eq!((copy.width(), copy.height()), (3, 2));
+        assert!(!copy.has_alpha());
+        let pixels = copy.read_pixel_bytes();
+        assert!(
+            pixels
+                .chunks(3)
+                .all(|pixel| pixel == [0x12, 0x34, 0x56].as_slice())
+        );
+    }
+
+    /// Render `caption` with `settings` onto a plain image, and compare it to
+    /// the golden image `name`.
+    ///
+    /// Set `$UPDATE_GOLDEN_IMAGES` to write the rendered image as new golden
+    /// image instead.
+    fn assert_golden(name: &str, caption: &Caption, settings: &CaptionSettings) {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 960, 540).unwrap();
+        pixbuf.fill(0x3366_99ff);
+        let rendered = render_caption(&pixbuf, caption, settings, GOLDEN_FONT).unwrap();
+
+        let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src")
+            .join("caption")
+            .join("golden")
+            .join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_GOLDEN_IMAGES").is_some() {
+            rendered.savev(&golden, "png", &[]).unwrap();
+            return;
+        }
+        let expected = Pixbuf::from_file(&golden).unwrap();
+        assert_eq!(
+            (rendered.width(), rendered.height()),
+            (expected.width(), expected.height())
+        );
+        assert!(!expected.has_alpha());
+        // Allow for small differences from font rendering and hinting across
+        // cairo and freetype versions.
+        let rendered = rendered.read_pixel_bytes();
+        let expected = expected.read_pixel_bytes();
+        let differing = rendered
+            .iter()
+            .zip(expected.iter())
+            .filter(|(a, b)| a.abs_diff(**b) > 16)
+            .count();
+        assert!(
+            differing * 1000 < rendered.len(),
+            "{differing} of {} values differ from golden image {name}",
+            rendered.len()
+        );
+    }
+
+    #[test]
+    fn golden_bottom_right() {
+        let caption = Caption::new(&metadata("The Horsehead Nebula", Some("Jane Doe")), "APOD");
+        let settings = CaptionSettings {
+            enabled: true,
+            position: CaptionPosition::BottomRight,
+            font_size: 36,
+            opacity: 80,
+        };
+        assert_golden("bottom-right", &caption, &settings);
+    }
+
+    #[test]
+    fn golden_top_left_shrinks_long_caption() {
+        let caption = Caption::new(
+            &metadata(
+                "A very long title which does not fit into the width of the wallpaper at all",
+                None,
+            ),
+            "Wikimedia Commons",
+        );
+        let settings = CaptionSettings {
+            enabled: true,
+            position: CaptionPosition::TopLeft,
+            font_size: 48,
+            opacity: 100,
+        };
+        assert_golden("top-left", &caption, &settings);
+    }
+}
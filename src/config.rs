@@ -172,10 +172,33 @@ pub fn get_settings() -> gio::Settings {
     }
 }
 
+/// The directory for all data of this application.
+pub fn data_directory() -> PathBuf {
+    glib::user_data_dir().join(crate::config::APP_ID)
+}
+
 pub fn images_directory() -> PathBuf {
-    glib::user_data_dir()
-        .join(crate::config::APP_ID)
-        .join("images")
+    data_directory().join("images")
+}
+
+/// The directory for cached data of this application.
+pub fn cache_directory() -> PathBuf {
+    glib::user_cache_dir().join(crate::config::APP_ID)
+}
+
+/// The directory for cached HTTP responses.
+pub fn http_cache_directory() -> PathBuf {
+    cache_directory().join("http")
+}
+
+/// The directory for wallpapers fitted to the monitor.
+pub fn fitted_wallpapers_directory() -> PathBuf {
+    cache_directory().join("wallpapers")
+}
+
+/// The file to store the history of images in.
+pub fn history_file() -> PathBuf {
+    data_directory().join("history.json")
 }
 
 #[cfg(test)]
@@ -0,0 +1,169 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Separate wallpapers for light and dark style.
+//!
+//! GNOME shows a different wallpaper in dark style, if the user sets one.  We
+//! either dim the wallpaper for dark style, or use a different image of the
+//! day.
+
+use std::path::{Path, PathBuf};
+
+use gtk::gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::fit::{image_info, write_processed_image};
+
+/// What to show as wallpaper in dark style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DarkVariant {
+    /// Show the same image in light and dark style.
+    #[default]
+    Same,
+    /// Show a dimmed variant of the image in dark style.
+    Dimmed,
+    /// Show a different image of the day in dark style.
+    OtherImage,
+}
+
+impl DarkVariant {
+    fn from_id(id: &str) -> Self {
+        match id {
+            "dimmed" => Self::Dimmed,
+            "other-image" => Self::OtherImage,
+            _ => Self::Same,
+        }
+    }
+}
+
+/// Settings for wallpapers in dark style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DarkVariantSettings {
+    /// What to show in dark style.
+    pub variant: DarkVariant,
+    /// How much to dim wallpapers for dark style, in percent.
+    pub dimming: u32,
+}
+
+impl DarkVariantSettings {
+    /// Read settings for wallpapers in dark style from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            variant: DarkVariant::from_id(&settings.string("dark-wallpaper")),
+            dimming: settings.uint("dark-wallpaper-dimming"),
+        }
+    }
+}
+
+/// The opacity of black over an image to dim the image by `dimming` percent.
+fn dimming_alpha(dimming: u32) -> i32 {
+    // This never exceeds 255, so the conversion won't fail
+    i32::try_from(dimming.min(100) * 255 / 100).unwrap()
+}
+
+/// Dim `pixbuf` in place by `dimming` percent.
+fn dim_pixbuf(pixbuf: &Pixbuf, dimming: u32) -> Result<(), glib::Error> {
+    let (width, height) = (pixbuf.width(), pixbuf.height());
+    let black = Pixbuf::new(Colorspace::Rgb, false, 8, width, height).ok_or_else(|| {
+        glib::Error::new(IOErrorEnum::Failed, "Failed to allocate image for dimming")
+    })?;
+    black.fill(0x0000_00ff);
+    black.composite(
+        pixbuf,
+        0,
+        0,
+        width,
+        height,
+        0.0,
+        0.0,
+        1.0,
+        1.0,
+        InterpType::Nearest,
+        dimming_alpha(dimming),
+    );
+    Ok(())
+}
+
+fn dimmed_variant_blocking(
+    source: &Path,
+    directory: &Path,
+    dimming: u32,
+) -> Result<PathBuf, glib::Error> {
+    let (format, _) = image_info(source)?;
+    let suffix = format!("dark{dimming}");
+    write_processed_image(source, &format, directory, &suffix, || {
+        let pixbuf = Pixbuf::from_file(source)?;
+        dim_pixbuf(&pixbuf, dimming)?;
+        Ok(pixbuf)
+    })
+}
+
+/// Dim the image at `source` by `dimming` percent for dark style.
+///
+/// Write the dimmed image to `directory` and return its path, or return
+/// `source` if `dimming` is zero.  Reuse dimmed images from earlier calls.
+///
+/// ## Errors
+///
+/// Return an error if we failed to load, process or save the image.
+pub async fn dimmed_variant(
+    source: &Path,
+    directory: &Path,
+    dimming: u32,
+) -> Result<PathBuf, glib::Error> {
+    if dimming == 0 {
+        return Ok(source.to_owned());
+    }
+    let source = source.to_owned();
+    let directory = directory.to_owned();
+    gio::spawn_blocking(move || dimmed_variant_blocking(&source, &directory, dimming))
+        .await
+        .unwrap_or_else(|_| {
+            Err(glib::Error::new(
+                IOErrorEnum::Failed,
+                "Dimming wallpaper panicked",
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identifiers of all dark variants, as stored in settings.
+    const DARK_VARIANTS: [&str; 3] = ["same", "dimmed", "other-image"];
+
+    #[test]
+    fn variant_from_id() {
+        assert_eq!(
+            DARK_VARIANTS.map(DarkVariant::from_id),
+            [
+                DarkVariant::Same,
+                DarkVariant::Dimmed,
+                DarkVariant::OtherImage
+            ]
+        );
+        assert_eq!(DarkVariant::from_id("foo"), DarkVariant::Same);
+    }
+
+    #[test]
+    fn alpha_for_dimming() {
+        assert_eq!(dimming_alpha(0), 0);
+        assert_eq!(dimming_alpha(40), 102);
+        assert_eq!(dimming_alpha(100), 255);
+        assert_eq!(dimming_alpha(150), 255);
+    }
+
+    #[test]
+    fn dim_white_image() {
+        let pixbuf = Pixbuf::new(Colorspace::Rgb, false, 8, 4, 4).unwrap();
+        pixbuf.fill(0xffff_ffff);
+        dim_pixbuf(&pixbuf, 40).unwrap();
+        let pixels = pixbuf.read_pixel_bytes();
+        // 255 * (1 - 102 / 255), give or take rounding
+        assert!(pixels.iter().all(|value| (152..=154).contains(value)));
+    }
+}
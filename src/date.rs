@@ -4,7 +4,7 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use jiff::civil::Date;
+use jiff::civil::{Date, DateTime};
 
 /// A boxed civil date.
 ///
@@ -25,15 +25,81 @@ impl From<BoxedCivilDate> for Date {
     }
 }
 
-/// Get the current date in the local timezone.
+/// Get the current date and time in the local timezone.
 ///
-/// Take the current date from [`glib::Datetime::now_local`].
-pub fn today_local() -> Date {
+/// Take the current time from [`glib::DateTime::now_local`].
+pub fn now_local() -> DateTime {
     let now = glib::DateTime::now_local().unwrap();
-    Date::new(
+    DateTime::new(
         i16::try_from(now.year()).unwrap(),
         i8::try_from(now.month()).unwrap(),
         i8::try_from(now.day_of_month()).unwrap(),
+        i8::try_from(now.hour()).unwrap(),
+        i8::try_from(now.minute()).unwrap(),
+        i8::try_from(now.second()).unwrap(),
+        0,
     )
     .unwrap()
 }
+
+/// Get the current date in the local timezone.
+///
+/// Take the current date from [`glib::DateTime::now_local`].
+pub fn today_local() -> Date {
+    now_local().date()
+}
+
+/// Format `date` for display, in the preferred representation of the locale.
+pub fn format_date(date: Date) -> glib::GString {
+    glib::DateTime::from_local(
+        i32::from(date.year()),
+        i32::from(date.month()),
+        i32::from(date.day()),
+        0,
+        0,
+        0.0,
+    )
+    .and_then(|datetime| datetime.format("%x"))
+    .unwrap_or_else(|_| date.to_string().into())
+}
+
+/// Cycle through a collection of `len` elements day by day.
+///
+/// Return the index of the element to use at `date`, counting days since
+/// `base`, or `None` if the collection is empty.
+pub fn cycle_index_for_date(base: Date, date: Date, len: usize) -> Option<usize> {
+    let len = i64::try_from(len).ok().filter(|len| 0 < *len)?;
+    let days = i64::from((date - base).get_days());
+    usize::try_from(days.rem_euclid(len)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::cycle_index_for_date;
+
+    #[test]
+    fn cycle_index_for_date_cycles_through_all_indexes() {
+        let base = date(2023, 11, 8);
+        let indexes = (0..6)
+            .map(|days| cycle_index_for_date(base, base + jiff::Span::new().days(days), 4))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            indexes,
+            vec![Some(0), Some(1), Some(2), Some(3), Some(0), Some(1)]
+        );
+    }
+
+    #[test]
+    fn cycle_index_for_date_before_base() {
+        let base = date(2023, 11, 8);
+        assert_eq!(cycle_index_for_date(base, date(2023, 11, 7), 4), Some(3));
+    }
+
+    #[test]
+    fn cycle_index_for_date_empty() {
+        let base = date(2023, 11, 8);
+        assert_eq!(cycle_index_for_date(base, base, 0), None);
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Filter wallpapers by resolution and aspect ratio.
+
+use std::path::Path;
+
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::gio::{self, prelude::*};
+
+/// The dimensions of an image in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Dimensions {
+    /// The aspect ratio of these dimensions, i.e. width over height.
+    ///
+    /// Return `None` if height is zero.
+    fn aspect_ratio(self) -> Option<f64> {
+        (0 < self.height).then(|| f64::from(self.width) / f64::from(self.height))
+    }
+}
+
+/// Get the dimensions of the image at `path`.
+///
+/// Only read the header of the image.  Return `None` if we can't tell the
+/// dimensions of the image, e.g. because gdk-pixbuf has no loader for its
+/// format.
+pub async fn dimensions_of_file(path: &Path) -> Option<Dimensions> {
+    let (_, width, height) = Pixbuf::file_info_future(path.to_owned()).await.ok()??;
+    Some(Dimensions {
+        width: u32::try_from(width).ok()?,
+        height: u32::try_from(height).ok()?,
+    })
+}
+
+/// Requirements for the dimensions of wallpapers.
+///
+/// A value of zero disables the corresponding requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DimensionsFilter {
+    /// The minimum width in pixels.
+    pub min_width: u32,
+    /// The minimum height in pixels.
+    pub min_height: u32,
+    /// The minimum aspect ratio, i.e. width over height.
+    pub min_aspect_ratio: f64,
+    /// The maximum aspect ratio, i.e. width over height.
+    pub max_aspect_ratio: f64,
+}
+
+impl DimensionsFilter {
+    /// Read the requirements for dimensions of wallpapers from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            min_width: settings.uint("min-image-width"),
+            min_height: settings.uint("min-image-height"),
+            min_aspect_ratio: settings.double("min-aspect-ratio"),
+            max_aspect_ratio: settings.double("max-aspect-ratio"),
+        }
+    }
+
+    /// Whether this filter accepts images of any dimensions.
+    pub fn accepts_all(&self) -> bool {
+        self.min_width == 0
+            && self.min_height == 0
+            && self.min_aspect_ratio <= 0.0
+            && self.max_aspect_ratio <= 0.0
+    }
+
+    /// Whether this filter accepts images with `dimensions`.
+    pub fn accepts(&self, dimensions: Dimensions) -> bool {
+        if dimensions.width < self.min_width || dimensions.height < self.min_height {
+            return false;
+        }
+        if self.min_aspect_ratio <= 0.0 && self.max_aspect_ratio <= 0.0 {
+            return true;
+        }
+        dimensions.aspect_ratio().is_some_and(|ratio| {
+            (self.min_aspect_ratio <= 0.0 || self.min_aspect_ratio <= ratio)
+                && (self.max_aspect_ratio <= 0.0 || ratio <= self.max_aspect_ratio)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimensions(width: u32, height: u32) -> Dimensions {
+        Dimensions { width, height }
+    }
+
+    #[test]
+    fn default_accepts_all() {
+        let filter = DimensionsFilter::default();
+        assert!(filter.accepts_all());
+        assert!(filter.accepts(dimensions(640, 480)));
+        assert!(filter.accepts(dimensions(1080, 1920)));
+        assert!(filter.accepts(dimensions(100, 0)));
+    }
+
+    #[test]
+    fn minimum_resolution() {
+        let filter = DimensionsFilter {
+            min_width: 1920,
+            min_height: 1080,
+            ..DimensionsFilter::default()
+        };
+        assert!(!filter.accepts_all());
+        assert!(filter.accepts(dimensions(3840, 2160)));
+        assert!(filter.accepts(dimensions(1920, 1080)));
+        assert!(!filter.accepts(dimensions(1919, 1080)));
+        assert!(!filter.accepts(dimensions(4000, 1000)));
+    }
+
+    #[test]
+    fn aspect_ratio_range() {
+        let filter = DimensionsFilter {
+            min_aspect_ratio: 1.3,
+            max_aspect_ratio: 2.4,
+            ..DimensionsFilter::default()
+        };
+        assert!(filter.accepts(dimensions(3840, 2160)));
+        assert!(filter.accepts(dimensions(3440, 1440)));
+        assert!(!filter.accepts(dimensions(1080, 1920)));
+        assert!(!filter.accepts(dimensions(5120, 1440)));
+        assert!(!filter.accepts(dimensions(100, 0)));
+    }
+
+    #[test]
+    fn only_minimum_aspect_ratio() {
+        let filter = DimensionsFilter {
+            min_aspect_ratio: 1.0,
+            ..DimensionsFilter::default()
+        };
+        assert!(filter.accepts(dimensions(5120, 1440)));
+        assert!(!filter.accepts(dimensions(1080, 1920)));
+    }
+}
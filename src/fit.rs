@@ -0,0 +1,543 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Fit wallpapers to the resolution of the monitor.
+//!
+//! Scale, crop or letterbox images before we set them as wallpaper, so that
+//! huge originals don't waste memory in the compositor, and portrait images
+//! don't get stretched across a landscape monitor.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use gtk::gdk_pixbuf::{InterpType, Pixbuf, PixbufFormat};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::config::G_LOG_DOMAIN;
+
+/// Delete fitted wallpapers older than this.
+const MAX_FITTED_WALLPAPER_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// A counter to make names of temporary files unique.
+static NEXT_TEMPORARY_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// How much to shrink the background of letterboxed images to blur it.
+const BLUR_FACTOR: i32 = 32;
+
+/// How to fit wallpapers to the monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// Use the original image.
+    #[default]
+    Original,
+    /// Scale large images down to cover the monitor.
+    Scale,
+    /// Scale large images down to cover the monitor, and crop the center to
+    /// the aspect ratio of the monitor.
+    Crop,
+    /// Scale images to fit into the monitor, and pad them with a blurred
+    /// background.
+    Letterbox,
+}
+
+impl FitMode {
+    /// Read the fit mode from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        match settings.string("wallpaper-fit").as_str() {
+            "scale" => Self::Scale,
+            "crop" => Self::Crop,
+            "letterbox" => Self::Letterbox,
+            _ => Self::Original,
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::Original => "original",
+            Self::Scale => "scale",
+            Self::Crop => "crop",
+            Self::Letterbox => "letterbox",
+        }
+    }
+}
+
+/// A size in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Size {
+    /// Scale `self` by `numerator / denominator`.
+    fn scale(self, numerator: i32, denominator: i32) -> Self {
+        let scale = |value: i32| {
+            i32::try_from(i64::from(value) * i64::from(numerator) / i64::from(denominator))
+                .unwrap_or(i32::MAX)
+                .max(1)
+        };
+        Self {
+            width: scale(self.width),
+            height: scale(self.height),
+        }
+    }
+
+    /// Whether `self` is relatively wider than `other`.
+    fn is_wider_than(self, other: Self) -> bool {
+        i64::from(self.width) * i64::from(other.height)
+            > i64::from(other.width) * i64::from(self.height)
+    }
+
+    fn is_larger_than(self, other: Self) -> bool {
+        other.width < self.width || other.height < self.height
+    }
+
+    /// The offset to center `self` in `outer`.
+    fn centered_in(self, outer: Self) -> (i32, i32) {
+        (
+            (outer.width - self.width) / 2,
+            (outer.height - self.height) / 2,
+        )
+    }
+}
+
+/// Scale `image` to the largest size which fits into `bounds`.
+fn scale_to_fit(image: Size, bounds: Size) -> Size {
+    if image.is_wider_than(bounds) {
+        image.scale(bounds.width, image.width)
+    } else {
+        image.scale(bounds.height, image.height)
+    }
+}
+
+/// Scale `image` to the smallest size which covers `bounds`.
+fn scale_to_cover(image: Size, bounds: Size) -> Size {
+    if image.is_wider_than(bounds) {
+        image.scale(bounds.height, image.height)
+    } else {
+        image.scale(bounds.width, image.width)
+    }
+}
+
+/// How to process an image to fit it to a monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitPlan {
+    /// Scale the image to the given size.
+    Scale(Size),
+    /// Scale the image to `scaled`, and crop an area of `size` at `offset`.
+    Crop {
+        scaled: Size,
+        offset: (i32, i32),
+        size: Size,
+    },
+    /// Scale the image to `foreground`, and center it on a `canvas` filled
+    /// with the image scaled to `background` and blurred.
+    Letterbox {
+        canvas: Size,
+        foreground: Size,
+        background: Size,
+    },
+}
+
+impl FitPlan {
+    /// Plan how to fit an `image` to a `monitor` according to `mode`.
+    ///
+    /// Never scale images up, except for blurred backgrounds.  Return `None`
+    /// if the image needs no processing.
+    pub fn new(mode: FitMode, image: Size, monitor: Size) -> Option<Self> {
+        match mode {
+            FitMode::Original => None,
+            FitMode::Scale => {
+                let scaled = scale_to_cover(image, monitor);
+                image.is_larger_than(scaled).then_some(Self::Scale(scaled))
+            }
+            FitMode::Crop => {
+                let cover = scale_to_cover(image, monitor);
+                let scaled = if image.is_larger_than(cover) {
+                    cover
+                } else {
+                    image
+                };
+                // The largest area with the aspect ratio of the monitor
+                let size = scale_to_fit(monitor, scaled);
+                (scaled != image || size != scaled).then(|| Self::Crop {
+                    scaled,
+                    offset: size.centered_in(scaled),
+                    size,
+                })
+            }
+            FitMode::Letterbox => {
+                if !image.is_wider_than(monitor) && !monitor.is_wider_than(image) {
+                    // The image has the aspect ratio of the monitor already,
+                    // so there's nothing to pad.
+                    return Self::new(FitMode::Scale, image, monitor);
+                }
+                let fit = scale_to_fit(image, monitor);
+                Some(Self::Letterbox {
+                    canvas: monitor,
+                    foreground: if image.is_larger_than(fit) {
+                        fit
+                    } else {
+                        image
+                    },
+                    background: scale_to_cover(image, monitor),
+                })
+            }
+        }
+    }
+}
+
+fn scale(pixbuf: &Pixbuf, size: Size) -> Result<Pixbuf, glib::Error> {
+    pixbuf
+        .scale_simple(size.width, size.height, InterpType::Bilinear)
+        .ok_or_else(|| glib::Error::new(IOErrorEnum::Failed, "Failed to scale image"))
+}
+
+/// Process the image at `source` according to `plan`.
+fn apply_plan(plan: FitPlan, source: &Path) -> Result<Pixbuf, glib::Error> {
+    match plan {
+        FitPlan::Scale(size) => Pixbuf::from_file_at_scale(source, size.width, size.height, false),
+        FitPlan::Crop {
+            scaled,
+            offset: (x, y),
+            size,
+        } => {
+            Ok(
+                Pixbuf::from_file_at_scale(source, scaled.width, scaled.height, false)?
+                    .new_subpixbuf(x, y, size.width, size.height),
+            )
+        }
+        FitPlan::Letterbox {
+            canvas,
+            foreground,
+            background,
+        } => {
+            let original = Pixbuf::from_file(source)?;
+            // Blur the background by shrinking it a lot and scaling it back up
+            let shrunk = scale(&original, background.scale(1, BLUR_FACTOR))?;
+            let blurred = scale(&shrunk, background)?;
+            let (x, y) = canvas.centered_in(background);
+            let target = blurred
+                .new_subpixbuf(x, y, canvas.width, canvas.height)
+                .copy()
+                .ok_or_else(|| glib::Error::new(IOErrorEnum::Failed, "Failed to copy image"))?;
+            let (x, y) = foreground.centered_in(canvas);
+            scale(&original, foreground)?.copy_area(
+                0,
+                0,
+                foreground.width,
+                foreground.height,
+                &target,
+                x,
+                y,
+            );
+            Ok(target)
+        }
+    }
+}
+
+/// Delete processed wallpapers in `directory` older than [`MAX_FITTED_WALLPAPER_AGE`].
+///
+/// Never delete `protected` wallpapers, i.e. those currently set.
+pub fn delete_old_fitted_wallpapers(directory: &Path, protected: &[PathBuf]) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.filter_map(Result::ok) {
+        if protected.contains(&entry.path()) {
+            continue;
+        }
+        let is_old = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| MAX_FITTED_WALLPAPER_AGE < age);
+        if is_old && let Err(error) = std::fs::remove_file(entry.path()) {
+            glib::warn!("Failed to delete {}: {error}", entry.path().display());
+        }
+    }
+}
+
+/// Get the format and the size of the image at `source`.
+///
+/// ## Errors
+///
+/// Return an error if gdk-pixbuf does not support the format of the image.
+pub fn image_info(source: &Path) -> Result<(PixbufFormat, Size), glib::Error> {
+    Pixbuf::file_info(source)
+        .map(|(format, width, height)| (format, Size { width, height }))
+        .ok_or_else(|| {
+            glib::Error::new(
+                IOErrorEnum::NotSupported,
+                &format!("Unsupported image format of {}", source.display()),
+            )
+        })
+}
+
+/// Mark the processed image at `path` as used just now.
+///
+/// This keeps [`delete_old_fitted_wallpapers`] from deleting processed images
+/// we still reuse.
+fn touch(path: &Path) {
+    let result = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(error) = result {
+        glib::warn!("Failed to touch {}: {error}", path.display());
+    }
+}
+
+/// Write the image at `source` in `format` processed by `process` to `directory`.
+///
+/// `suffix` must identify the processing, e.g. the mode and the size to fit
+/// the image to.  Name the processed image after a checksum of the full path
+/// of `source` and `suffix`, and reuse an existing processed image of the
+/// same name.  Save the processed image to a temporary file first, so that
+/// we never reuse a partially written image.
+///
+/// ## Errors
+///
+/// Return an error if `process` failed, or if we failed to save the image.
+pub fn write_processed_image<F>(
+    source: &Path,
+    format: &PixbufFormat,
+    directory: &Path,
+    suffix: &str,
+    process: F,
+) -> Result<PathBuf, glib::Error>
+where
+    F: FnOnce() -> Result<Pixbuf, glib::Error>,
+{
+    let stem = source
+        .file_stem()
+        .map_or_else(|| "wallpaper".into(), |stem| stem.to_string_lossy());
+    // JPEG does not support transparency
+    let (extension, format) = if format.name().is_some_and(|name| name == "png") {
+        ("png", "png")
+    } else {
+        ("jpg", "jpeg")
+    };
+    // Images of the same name in different directories must not share
+    // processed images.
+    let key = format!("{}\n{suffix}", source.display());
+    let checksum = glib::compute_checksum_for_string(glib::ChecksumType::Sha256, &key)
+        .map_or_else(String::new, |checksum| checksum.chars().take(16).collect());
+    let name = format!("{stem}.{checksum}.{extension}");
+    let target = directory.join(&name);
+    if target.is_file() {
+        touch(&target);
+        return Ok(target);
+    }
+    glib::info!("Processing {} to {}", source.display(), target.display());
+    std::fs::create_dir_all(directory).map_err(|error| {
+        glib::Error::new(
+            IOErrorEnum::Failed,
+            &format!("Failed to create {}: {error}", directory.display()),
+        )
+    })?;
+    let options: &[(&str, &str)] = if format == "jpeg" {
+        &[("quality", "92")]
+    } else {
+        &[]
+    };
+    // Hidden, and unique per process and call, so that concurrent processing
+    // of the same image never writes to the same temporary file.
+    let temporary = directory.join(format!(
+        ".{name}.{}-{}",
+        std::process::id(),
+        NEXT_TEMPORARY_FILE.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = process()
+        .and_then(|image| image.savev(&temporary, format, options))
+        .and_then(|()| {
+            std::fs::rename(&temporary, &target).map_err(|error| {
+                glib::Error::new(
+                    IOErrorEnum::Failed,
+                    &format!("Failed to move image to {}: {error}", target.display()),
+                )
+            })
+        });
+    if result.is_err()
+        && temporary.exists()
+        && let Err(error) = std::fs::remove_file(&temporary)
+    {
+        glib::warn!("Failed to delete {}: {error}", temporary.display());
+    }
+    result.map(|()| target)
+}
+
+fn fit_wallpaper_blocking(
+    source: &Path,
+    directory: &Path,
+    mode: FitMode,
+    monitor: Size,
+) -> Result<PathBuf, glib::Error> {
+    let (format, size) = image_info(source)?;
+    let Some(plan) = FitPlan::new(mode, size, monitor) else {
+        return Ok(source.to_owned());
+    };
+    let suffix = format!("{}.{}x{}", mode.id(), monitor.width, monitor.height);
+    write_processed_image(source, &format, directory, &suffix, || {
+        apply_plan(plan, source)
+    })
+}
+
+/// Fit the image at `source` to a `monitor` according to `mode`.
+///
+/// Write the fitted image to `directory`, and return its path, or return
+/// `source` if the image needs no processing.  Reuse fitted images from
+/// earlier calls.
+///
+/// ## Errors
+///
+/// Return an error if we failed to load, process or save the image.
+pub async fn fit_wallpaper(
+    source: &Path,
+    directory: &Path,
+    mode: FitMode,
+    monitor: Size,
+) -> Result<PathBuf, glib::Error> {
+    if mode == FitMode::Original {
+        return Ok(source.to_owned());
+    }
+    let source = source.to_owned();
+    let directory = directory.to_owned();
+    gio::spawn_blocking(move || fit_wallpaper_blocking(&source, &directory, mode, monitor))
+        .await
+        .unwrap_or_else(|_| {
+            Err(glib::Error::new(
+                IOErrorEnum::Failed,
+                "Fitting wallpaper panicked",
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UHD: Size = Size {
+        width: 3840,
+        height: 2160,
+    };
+
+    fn size(width: i32, height: i32) -> Size {
+        Size { width, height }
+    }
+
+    #[test]
+    fn original_keeps_image() {
+        assert_eq!(FitPlan::new(FitMode::Original, size(8000, 6000), UHD), None);
+    }
+
+    #[test]
+    fn scale_shrinks_large_images_to_cover_monitor() {
+        assert_eq!(
+            FitPlan::new(FitMode::Scale, size(8000, 6000), UHD),
+            Some(FitPlan::Scale(size(3840, 2880)))
+        );
+        // Covering the monitor would scale this panorama up
+        assert_eq!(FitPlan::new(FitMode::Scale, size(8000, 2000), UHD), None);
+        assert_eq!(FitPlan::new(FitMode::Scale, size(1920, 1080), UHD), None);
+    }
+
+    #[test]
+    fn crop_cuts_center_to_monitor_aspect_ratio() {
+        assert_eq!(
+            FitPlan::new(FitMode::Crop, size(8000, 6000), UHD),
+            Some(FitPlan::Crop {
+                scaled: size(3840, 2880),
+                offset: (0, 360),
+                size: UHD,
+            })
+        );
+        // Don't scale small images up, but still crop them
+        assert_eq!(
+            FitPlan::new(FitMode::Crop, size(1000, 1000), UHD),
+            Some(FitPlan::Crop {
+                scaled: size(1000, 1000),
+                offset: (0, 219),
+                size: size(1000, 562),
+            })
+        );
+        assert_eq!(FitPlan::new(FitMode::Crop, size(1920, 1080), UHD), None);
+    }
+
+    #[test]
+    fn letterbox_pads_portrait_images() {
+        assert_eq!(
+            FitPlan::new(FitMode::Letterbox, size(3000, 6000), UHD),
+            Some(FitPlan::Letterbox {
+                canvas: UHD,
+                foreground: size(1080, 2160),
+                background: size(3840, 7680),
+            })
+        );
+        assert_eq!(
+            FitPlan::new(FitMode::Letterbox, size(7680, 4320), UHD),
+            Some(FitPlan::Scale(UHD))
+        );
+    }
+
+    #[test]
+    fn delete_old_fitted_wallpapers_keeps_protected_wallpapers() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let old = SystemTime::now() - MAX_FITTED_WALLPAPER_AGE - Duration::from_secs(60);
+        for name in ["old.jpg", "applied.jpg", "new.jpg"] {
+            std::fs::write(directory.join(name), b"12345").unwrap();
+        }
+        for name in ["old.jpg", "applied.jpg"] {
+            std::fs::File::options()
+                .write(true)
+                .open(directory.join(name))
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        delete_old_fitted_wallpapers(&directory, &[directory.join("applied.jpg")]);
+        let exists =
+            ["old.jpg", "applied.jpg", "new.jpg"].map(|name| directory.join(name).exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(exists, [false, true, true]);
+    }
+
+    #[test]
+    fn write_processed_image_keys_on_full_source_path() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let format = Pixbuf::formats()
+            .into_iter()
+            .find(|format| format.name().is_some_and(|name| name == "png"))
+            .unwrap();
+        let process = || {
+            let pixbuf = Pixbuf::new(gtk::gdk_pixbuf::Colorspace::Rgb, false, 8, 2, 2).unwrap();
+            pixbuf.fill(0);
+            Ok(pixbuf)
+        };
+        let apod = directory.join("apod").join("image.jpg");
+        let bing = directory.join("bing").join("image.jpg");
+        let fitted = directory.join("fitted");
+        let apod_scaled = write_processed_image(&apod, &format, &fitted, "scale", process).unwrap();
+        let apod_cropped = write_processed_image(&apod, &format, &fitted, "crop", process).unwrap();
+        let bing_scaled = write_processed_image(&bing, &format, &fitted, "scale", process).unwrap();
+        let reused = write_processed_image(&apod, &format, &fitted, "scale", || {
+            Err(glib::Error::new(
+                IOErrorEnum::Failed,
+                "Must not process again",
+            ))
+        });
+        let files = std::fs::read_dir(&fitted).unwrap().count();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_ne!(apod_scaled, apod_cropped);
+        assert_ne!(apod_scaled, bing_scaled);
+        assert_eq!(reused.unwrap(), apod_scaled);
+        // No temporary files left behind
+        assert_eq!(files, 3);
+    }
+}
@@ -0,0 +1,724 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! History of fetched images and wallpapers.
+//!
+//! We store the history as a plain JSON file in the data directory.
+
+use std::path::{Path, PathBuf};
+
+use jiff::civil::Date;
+use serde::{Deserialize, Serialize};
+
+use crate::images::{DownloadableImage, ImageMetadata};
+
+/// The version of the history file format.
+const VERSION: u32 = 1;
+
+/// Keep at most this many entries without a downloaded file.
+///
+/// Entries with a file live as long as their file, i.e. according to the
+/// retention policy for downloaded images.
+const MAX_ENTRIES_WITHOUT_FILE: usize = 500;
+
+/// A single image in the history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The day on which we fetched this image.
+    pub date: Date,
+    /// Metadata of the image.
+    pub metadata: ImageMetadata,
+    /// The URL we fetched the image from.
+    pub image_url: String,
+    /// The date the image was published at, if known.
+    pub pubdate: Option<Date>,
+    /// The file we downloaded the image to, if any.
+    pub file: Option<PathBuf>,
+    /// Whether we set the image as wallpaper.
+    pub set_as_wallpaper: bool,
+}
+
+impl HistoryEntry {
+    /// Create a new entry for `image` fetched at `date`, and downloaded to `file`.
+    pub fn new(date: Date, image: &DownloadableImage, file: Option<PathBuf>) -> Self {
+        Self {
+            date,
+            metadata: image.metadata.clone(),
+            image_url: image.image_url.clone(),
+            pubdate: image.pubdate,
+            file,
+            set_as_wallpaper: false,
+        }
+    }
+
+    /// Mark this entry as set as wallpaper.
+    pub fn with_set_as_wallpaper(mut self) -> Self {
+        self.set_as_wallpaper = true;
+        self
+    }
+
+    /// Whether `other` denotes the same image on the same day.
+    fn is_same_image(&self, other: &Self) -> bool {
+        self.date == other.date
+            && self.metadata.source == other.metadata.source
+            && self.image_url == other.image_url
+    }
+}
+
+/// All entries of a single day from a single source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryGroup<'a> {
+    /// The day on which we fetched the images.
+    pub date: Date,
+    /// The identifier of the source of the images.
+    pub source: &'a str,
+    /// The entries, from newest to oldest.
+    pub entries: Vec<&'a HistoryEntry>,
+}
+
+/// Group `entries` by date and source.
+///
+/// Return groups from newest to oldest date; groups of the same date are in
+/// order of their most recent entry.
+pub fn group_entries(entries: &[HistoryEntry]) -> Vec<HistoryGroup<'_>> {
+    let mut groups: Vec<HistoryGroup<'_>> = Vec::new();
+    for entry in entries.iter().rev() {
+        match groups
+            .iter_mut()
+            .find(|group| group.date == entry.date && group.source == entry.metadata.source)
+        {
+            Some(group) => group.entries.push(entry),
+            None => groups.push(HistoryGroup {
+                date: entry.date,
+                source: &entry.metadata.source,
+                entries: vec![entry],
+            }),
+        }
+    }
+    // A stable sort retains the order of groups of the same date
+    groups.sort_by(|a, b| b.date.cmp(&a.date));
+    groups
+}
+
+/// Find the entries for all `favorites`.
+///
+/// For every favorite image URL, find the most recent entry with a downloaded
+/// file, and return these entries in order of `favorites`.  Skip favorites
+/// without any such entry.
+pub fn favorite_entries<'a>(
+    entries: &'a [HistoryEntry],
+    favorites: &[impl AsRef<str>],
+) -> Vec<&'a HistoryEntry> {
+    favorites
+        .iter()
+        .filter_map(|url| {
+            entries
+                .iter()
+                .rev()
+                .find(|entry| entry.image_url == url.as_ref() && entry.file.is_some())
+        })
+        .collect()
+}
+
+/// The contents of the history file.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryFile<E, P> {
+    version: u32,
+    entries: E,
+    /// Older history files lack the current wallpaper.
+    #[serde(default)]
+    current_wallpaper: Option<P>,
+}
+
+/// The history of all images.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    /// The file of the current wallpaper.
+    current_wallpaper: Option<PathBuf>,
+}
+
+impl History {
+    /// Parse history from JSON.
+    ///
+    /// # Errors
+    ///
+    /// If `data` is not valid JSON or does not denote a history.
+    pub fn from_json(data: &[u8]) -> serde_json::Result<Self> {
+        let file: HistoryFile<Vec<HistoryEntry>, PathBuf> = serde_json::from_slice(data)?;
+        // Older history files did not record the current wallpaper, so take
+        // the most recent entry set as wallpaper instead.
+        let current_wallpaper = file.current_wallpaper.or_else(|| {
+            file.entries
+                .iter()
+                .rev()
+                .find(|entry| entry.set_as_wallpaper)
+                .and_then(|entry| entry.file.clone())
+        });
+        let mut history = Self {
+            entries: file.entries,
+            current_wallpaper,
+        };
+        history.prune();
+        Ok(history)
+    }
+
+    /// Serialize this history to JSON.
+    pub fn to_json(&self) -> Vec<u8> {
+        let file = HistoryFile {
+            version: VERSION,
+            entries: &self.entries,
+            current_wallpaper: self.current_wallpaper.as_deref(),
+        };
+        // Serializing plain data structures to JSON never fails
+        serde_json::to_vec(&file).unwrap()
+    }
+
+    /// All entries in this history, from oldest to newest.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Record `entry` in this history.
+    ///
+    /// If the history already contains the same image from the same source on
+    /// the same day, update the existing entry, and move it to the end.  If
+    /// `entry` was set as wallpaper, make its file the current wallpaper.
+    ///
+    /// Drop the oldest entries without a file if there are too many.
+    pub fn record(&mut self, entry: HistoryEntry) {
+        if entry.set_as_wallpaper && entry.file.is_some() {
+            self.current_wallpaper.clone_from(&entry.file);
+        }
+        match self
+            .entries
+            .iter()
+            .position(|existing| existing.is_same_image(&entry))
+        {
+            Some(index) => {
+                let existing = self.entries.remove(index);
+                self.entries.push(HistoryEntry {
+                    file: entry.file.or(existing.file),
+                    set_as_wallpaper: entry.set_as_wallpaper || existing.set_as_wallpaper,
+                    ..entry
+                });
+            }
+            None => self.entries.push(entry),
+        }
+        self.prune();
+    }
+
+    /// Drop the oldest entries without a file, if there are too many.
+    ///
+    /// See [`MAX_ENTRIES_WITHOUT_FILE`].
+    fn prune(&mut self) {
+        let without_file = self
+            .entries
+            .iter()
+            .filter(|entry| entry.file.is_none())
+            .count();
+        let mut excess = without_file.saturating_sub(MAX_ENTRIES_WITHOUT_FILE);
+        if 0 < excess {
+            self.entries.retain(|entry| {
+                if 0 < excess && entry.file.is_none() {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+
+    /// Mark the most recent entry for `file` as set as wallpaper, and make
+    /// `file` the current wallpaper.
+    ///
+    /// Return `false` if no entry exists for `file`.
+    pub fn mark_set_as_wallpaper(&mut self, file: &Path) -> bool {
+        match self
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.file.as_deref() == Some(file))
+        {
+            Some(entry) => {
+                entry.set_as_wallpaper = true;
+                self.current_wallpaper = Some(file.to_owned());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget about all `files`, e.g. because we deleted them.
+    ///
+    /// Keep the entries for these files, but clear their file, and forget the
+    /// current wallpaper if it is among `files`.  Return `false` if no entry
+    /// referred to any of `files`.
+    pub fn forget_files(&mut self, files: &[PathBuf]) -> bool {
+        let mut changed = false;
+        for entry in &mut self.entries {
+            if entry.file.as_ref().is_some_and(|file| files.contains(file)) {
+                entry.file = None;
+                changed = true;
+            }
+        }
+        if self
+            .current_wallpaper
+            .as_ref()
+            .is_some_and(|file| files.contains(file))
+        {
+            self.current_wallpaper = None;
+            changed = true;
+        }
+        if changed {
+            self.prune();
+        }
+        changed
+    }
+
+    /// The file of the current wallpaper, if any.
+    pub fn current_wallpaper(&self) -> Option<&Path> {
+        self.current_wallpaper.as_deref()
+    }
+
+    /// Get the URL we originally fetched `file` from.
+    ///
+    /// Use the oldest entry for `file`, because later entries may refer to
+    /// the local file directly, e.g. if the image came from favorites.
+    pub fn image_url_for_file(&self, file: &Path) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.file.as_deref() == Some(file))
+            .map(|entry| entry.image_url.as_str())
+    }
+
+    /// Get metadata of the image downloaded to `file`.
+    ///
+    /// Use the most recent entry for `file`.
+    pub fn metadata_for_file(&self, file: &Path) -> Option<&ImageMetadata> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.file.as_deref() == Some(file))
+            .map(|entry| &entry.metadata)
+    }
+
+    /// Merge all entries of `other` into this history.
+    ///
+    /// Prefer the current wallpaper of `other`, if any.
+    pub fn merge(&mut self, other: Self) {
+        let current_wallpaper = other
+            .current_wallpaper
+            .or_else(|| self.current_wallpaper.take());
+        for entry in other.entries {
+            self.record(entry);
+        }
+        self.current_wallpaper = current_wallpaper;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use jiff::civil::date;
+
+    use crate::images::{DownloadableImage, ImageMetadata};
+
+    use super::{History, HistoryEntry, MAX_ENTRIES_WITHOUT_FILE, favorite_entries, group_entries};
+
+    fn image(url: &str) -> DownloadableImage {
+        image_from_source(url, "feed-0123456789abcdef")
+    }
+
+    fn image_from_source(url: &str, source: &str) -> DownloadableImage {
+        DownloadableImage {
+            metadata: ImageMetadata {
+                title: "A lake".into(),
+                description: Some("A lake in the morning".into()),
+                copyright: None,
+                url: Some("https://photos.example.com/lake".into()),
+                source: source.into(),
+            },
+            image_url: url.into(),
+            pubdate: Some(date(2025, 10, 16)),
+            suggested_filename: None,
+            dimensions: None,
+        }
+    }
+
+    #[test]
+    fn record_new_entries() {
+        let mut history = History::default();
+        let first = HistoryEntry::new(date(2025, 10, 16), &image("https://a.jpg"), None);
+        let second = HistoryEntry::new(date(2025, 10, 17), &image("https://a.jpg"), None);
+        history.record(first.clone());
+        history.record(second.clone());
+        assert_eq!(history.entries(), &[first, second]);
+    }
+
+    #[test]
+    fn record_merges_same_image_on_same_day() {
+        let mut history = History::default();
+        let day = date(2025, 10, 17);
+        history.record(
+            HistoryEntry::new(day, &image("https://a.jpg"), Some("/images/a.jpg".into()))
+                .with_set_as_wallpaper(),
+        );
+        history.record(HistoryEntry::new(day, &image("https://b.jpg"), None));
+        history.record(HistoryEntry::new(day, &image("https://a.jpg"), None));
+
+        let urls = history
+            .entries()
+            .iter()
+            .map(|entry| entry.image_url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, vec!["https://b.jpg", "https://a.jpg"]);
+        let merged = history.entries().last().unwrap();
+        assert_eq!(merged.file.as_deref(), Some(Path::new("/images/a.jpg")));
+        assert!(merged.set_as_wallpaper);
+    }
+
+    #[test]
+    fn mark_set_as_wallpaper() {
+        let mut history = History::default();
+        let file = PathBuf::from("/images/a.jpg");
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://a.jpg"),
+            Some(file.clone()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image("https://a.jpg"),
+            Some(file.clone()),
+        ));
+        assert!(history.mark_set_as_wallpaper(&file));
+        assert!(!history.mark_set_as_wallpaper(Path::new("/images/b.jpg")));
+        let flags = history
+            .entries()
+            .iter()
+            .map(|entry| entry.set_as_wallpaper)
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![false, true]);
+    }
+
+    #[test]
+    fn forget_files() {
+        let mut history = History::default();
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://a.jpg"),
+            Some("/images/a.jpg".into()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://b.jpg"),
+            Some("/images/b.jpg".into()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image("https://a.jpg"),
+            Some("/images/a.jpg".into()),
+        ));
+        assert!(history.forget_files(&[PathBuf::from("/images/a.jpg")]));
+        assert!(!history.forget_files(&[PathBuf::from("/images/c.jpg")]));
+        let files = history
+            .entries()
+            .iter()
+            .map(|entry| entry.file.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec![None, Some(Path::new("/images/b.jpg")), None]);
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let mut history = History::default();
+        history.record(
+            HistoryEntry::new(
+                date(2025, 10, 17),
+                &image("https://a.jpg"),
+                Some("/images/a.jpg".into()),
+            )
+            .with_set_as_wallpaper(),
+        );
+        let parsed = History::from_json(&history.to_json()).unwrap();
+        assert_eq!(parsed, history);
+        assert_eq!(parsed.current_wallpaper(), Some(Path::new("/images/a.jpg")));
+    }
+
+    #[test]
+    fn current_wallpaper_of_history_without_current_wallpaper() {
+        let mut history = History::default();
+        history.record(
+            HistoryEntry::new(
+                date(2025, 10, 17),
+                &image("https://a.jpg"),
+                Some("/images/a.jpg".into()),
+            )
+            .with_set_as_wallpaper(),
+        );
+        let json = serde_json::json!({"version": 1, "entries": history.entries()});
+        let parsed = History::from_json(json.to_string().as_bytes()).unwrap();
+        assert_eq!(parsed.current_wallpaper(), Some(Path::new("/images/a.jpg")));
+    }
+
+    #[test]
+    fn merge() {
+        let day = date(2025, 10, 17);
+        let mut history = History::default();
+        history.record(HistoryEntry::new(day, &image("https://a.jpg"), None));
+        let mut other = History::default();
+        other.record(HistoryEntry::new(day, &image("https://a.jpg"), None).with_set_as_wallpaper());
+        other.record(HistoryEntry::new(day, &image("https://b.jpg"), None));
+        history.merge(other);
+        assert_eq!(history.entries().len(), 2);
+        assert!(history.entries().iter().any(|entry| entry.set_as_wallpaper));
+    }
+
+    #[test]
+    fn merge_prefers_current_wallpaper_of_other() {
+        let day = date(2025, 10, 17);
+        let mut history = History::default();
+        history.record(
+            HistoryEntry::new(day, &image("https://a.jpg"), Some("/images/a.jpg".into()))
+                .with_set_as_wallpaper(),
+        );
+        let mut other = History::default();
+        other.record(
+            HistoryEntry::new(day, &image("https://b.jpg"), Some("/images/b.jpg".into()))
+                .with_set_as_wallpaper(),
+        );
+        history.merge(other);
+        assert_eq!(
+            history.current_wallpaper(),
+            Some(Path::new("/images/b.jpg"))
+        );
+        // Keep our own current wallpaper if other has none
+        history.merge(History::default());
+        assert_eq!(
+            history.current_wallpaper(),
+            Some(Path::new("/images/b.jpg"))
+        );
+    }
+
+    #[test]
+    fn group_entries_by_date_and_source() {
+        let mut history = History::default();
+        let yesterday = date(2025, 10, 16);
+        let today = date(2025, 10, 17);
+        history.record(HistoryEntry::new(
+            yesterday,
+            &image_from_source("https://a.jpg", "bing"),
+            None,
+        ));
+        history.record(HistoryEntry::new(
+            today,
+            &image_from_source("https://b.jpg", "bing"),
+            None,
+        ));
+        history.record(HistoryEntry::new(
+            today,
+            &image_from_source("https://c.jpg", "apod"),
+            None,
+        ));
+        history.record(HistoryEntry::new(
+            today,
+            &image_from_source("https://d.jpg", "bing"),
+            None,
+        ));
+
+        let groups = group_entries(history.entries())
+            .into_iter()
+            .map(|group| {
+                let urls = group
+                    .entries
+                    .iter()
+                    .map(|entry| entry.image_url.as_str())
+                    .collect::<Vec<_>>();
+                (group.date, group.source, urls)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (today, "bing", vec!["https://d.jpg", "https://b.jpg"]),
+                (today, "apod", vec!["https://c.jpg"]),
+                (yesterday, "bing", vec!["https://a.jpg"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn image_url_for_file() {
+        let mut history = History::default();
+        let file = PathBuf::from("/images/a.jpg");
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://a.jpg"),
+            Some(file.clone()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image("file:///images/a.jpg"),
+            Some(file.clone()),
+        ));
+        assert_eq!(history.image_url_for_file(&file), Some("https://a.jpg"));
+        assert_eq!(history.image_url_for_file(Path::new("/images/b.jpg")), None);
+    }
+
+    #[test]
+    fn metadata_for_file() {
+        let mut history = History::default();
+        let file = PathBuf::from("/images/a.jpg");
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://a.jpg"),
+            Some(file.clone()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image_from_source("file:///images/a.jpg", "favorites"),
+            Some(file.clone()),
+        ));
+        assert_eq!(
+            history
+                .metadata_for_file(&file)
+                .map(|metadata| metadata.source.as_str()),
+            Some("favorites")
+        );
+        assert!(
+            history
+                .metadata_for_file(Path::new("/images/b.jpg"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn favorite_entries_in_order_of_favorites() {
+        let mut history = History::default();
+        history.record(HistoryEntry::new(
+            date(2025, 10, 15),
+            &image("https://a.jpg"),
+            Some("/images/old/a.jpg".into()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://a.jpg"),
+            Some("/images/a.jpg".into()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 16),
+            &image("https://b.jpg"),
+            Some("/images/b.jpg".into()),
+        ));
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image("https://c.jpg"),
+            None,
+        ));
+
+        let files = favorite_entries(
+            history.entries(),
+            &[
+                "https://b.jpg",
+                "https://c.jpg",
+                "https://a.jpg",
+                "https://d.jpg",
+            ],
+        )
+        .into_iter()
+        .map(|entry| entry.file.as_deref().unwrap())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![Path::new("/images/b.jpg"), Path::new("/images/a.jpg")]
+        );
+    }
+
+    #[test]
+    fn current_wallpaper() {
+        let mut history = History::default();
+        assert!(history.current_wallpaper().is_none());
+        history.record(
+            HistoryEntry::new(
+                date(2025, 10, 16),
+                &image("https://a.jpg"),
+                Some("/images/a.jpg".into()),
+            )
+            .with_set_as_wallpaper(),
+        );
+        history.record(HistoryEntry::new(
+            date(2025, 10, 17),
+            &image("https://b.jpg"),
+            Some("/images/b.jpg".into()),
+        ));
+        assert_eq!(
+            history.current_wallpaper(),
+            Some(Path::new("/images/a.jpg"))
+        );
+    }
+
+    #[test]
+    fn current_wallpaper_does_not_depend_on_order_of_entries() {
+        let mut history = History::default();
+        let day = date(2025, 10, 17);
+        history.record(
+            HistoryEntry::new(day, &image("https://a.jpg"), Some("/images/a.jpg".into()))
+                .with_set_as_wallpaper(),
+        );
+        history.record(
+            HistoryEntry::new(day, &image("https://b.jpg"), Some("/images/b.jpg".into()))
+                .with_set_as_wallpaper(),
+        );
+        // Recording the first image again, e.g. for the lock screen, moves its
+        // entry to the end, but does not make it the current wallpaper again
+        history.record(HistoryEntry::new(
+            day,
+            &image("https://a.jpg"),
+            Some("/images/a.jpg".into()),
+        ));
+        assert_eq!(
+            history.current_wallpaper(),
+            Some(Path::new("/images/b.jpg"))
+        );
+        // Setting an older image as wallpaper makes it the current wallpaper
+        assert!(history.mark_set_as_wallpaper(Path::new("/images/a.jpg")));
+        assert_eq!(
+            history.current_wallpaper(),
+            Some(Path::new("/images/a.jpg"))
+        );
+        history.forget_files(&[PathBuf::from("/images/a.jpg")]);
+        assert!(history.current_wallpaper().is_none());
+    }
+
+    #[test]
+    fn record_drops_oldest_entries_without_file() {
+        let mut history = History::default();
+        let day = date(2025, 10, 17);
+        history.record(HistoryEntry::new(
+            day,
+            &image("https://kept.jpg"),
+            Some("/images/kept.jpg".into()),
+        ));
+        for index in 0..=MAX_ENTRIES_WITHOUT_FILE {
+            history.record(HistoryEntry::new(
+                day,
+                &image(&format!("https://{index}.jpg")),
+                None,
+            ));
+        }
+        assert_eq!(history.entries().len(), MAX_ENTRIES_WITHOUT_FILE + 1);
+        let urls = history
+            .entries()
+            .iter()
+            .take(2)
+            .map(|entry| entry.image_url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(urls, vec!["https://kept.jpg", "https://1.jpg"]);
+    }
+}
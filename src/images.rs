@@ -9,6 +9,6 @@ mod source;
 mod sources;
 
 pub use image::{DownloadableImage, ImageMetadata};
-pub use source::{Source, SourceError};
+pub use source::{Source, SourceCapabilities, SourceError, SourceProvider, SourceRegistry};
 
-pub use sources::stalenhag;
+pub use sources::{apod, favorites, feed, local, stalenhag};
@@ -4,19 +4,23 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use gtk::gio::{self, FileQueryInfoFlags, IOErrorEnum, prelude::FileExt};
+use gtk::gio::{self, FileQueryInfoFlags, prelude::FileExt};
 use std::{
     borrow::Cow,
     path::{Path, PathBuf},
 };
 
-use crate::config::G_LOG_DOMAIN;
-use crate::net::http::download::{DownloadError, download_file_to_directory};
+use gnome_app_utils::io::delete_file_ignore_error;
+use serde::{Deserialize, Serialize};
 
-use super::Source;
+use crate::config::G_LOG_DOMAIN;
+use crate::dimensions::Dimensions;
+use crate::net::http::download::{
+    DownloadError, DownloadProgress, download_file_to_directory, verify_image_file,
+};
 
 /// Metadata of an image.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ImageMetadata {
     /// The image title.
     pub title: String,
@@ -26,8 +30,8 @@ pub struct ImageMetadata {
     pub copyright: Option<String>,
     /// The direct URL for this image.
     pub url: Option<String>,
-    /// The source this image comes from.
-    pub source: Source,
+    /// The identifier of the source this image comes from.
+    pub source: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -42,18 +46,38 @@ pub struct DownloadableImage {
     pub pubdate: Option<jiff::civil::Date>,
     /// The suggested file name for this image.
     pub suggested_filename: Option<String>,
+    /// The dimensions of this image, if the source tells them.
+    pub dimensions: Option<Dimensions>,
+}
+
+/// Whether `name` is usable as a file name within a directory.
+fn is_usable_filename(name: &str) -> bool {
+    !matches!(name.trim(), "" | "." | "..")
 }
 
 impl DownloadableImage {
+    /// Guess a file name for this image.
+    ///
+    /// Use the last segment of the path of the image URL, without query and
+    /// fragment.  Fall back to the title, and then to a checksum of the image
+    /// URL.
     fn guess_filename(&self) -> Cow<'_, str> {
-        self.image_url
-            .split('/')
-            .next_back()
-            .filter(|s| !s.is_empty())
-            .map_or_else(
-                || Cow::Owned(self.metadata.title.replace(['/', '\n'], "_")),
-                Cow::Borrowed,
-            )
+        let path = self.image_url.split(['?', '#']).next().unwrap_or_default();
+        if let Some(name) = path.rsplit('/').next()
+            && is_usable_filename(name)
+        {
+            return Cow::Borrowed(name);
+        }
+        let title = self.metadata.title.replace(['/', '\n'], "_");
+        if is_usable_filename(&title) {
+            Cow::Owned(title)
+        } else {
+            // SHA256 is always supported, so this won't panic.
+            glib::compute_checksum_for_string(glib::ChecksumType::Sha256, &self.image_url)
+                .unwrap()
+                .to_string()
+                .into()
+        }
     }
 
     pub fn with_pubdate(mut self, date: jiff::civil::Date) -> Self {
@@ -72,9 +96,20 @@ impl DownloadableImage {
         }
     }
 
+    /// The local path of this image, if the image URL denotes a local file.
+    pub fn local_path(&self) -> Option<PathBuf> {
+        if self.image_url.starts_with("file://") {
+            gio::File::for_uri(&self.image_url).path()
+        } else {
+            None
+        }
+    }
+
     /// Download this image to a directory.
     ///
     /// Download this image to `directory`, using the provided HTTP `session.`
+    /// If this image is a local file, do not download it, and return its path
+    /// directly.
     ///
     /// Return the full path to the downloaded image if successful.
     pub async fn download_to_directory(
@@ -82,9 +117,27 @@ impl DownloadableImage {
         directory: &Path,
         session: &soup::Session,
     ) -> Result<PathBuf, DownloadError> {
+        self.download_to_directory_with_progress(directory, session, &|_| {})
+            .await
+    }
+
+    /// Download this image to a directory, and report download `progress`.
+    ///
+    /// See [`Self::download_to_directory`].
+    pub async fn download_to_directory_with_progress(
+        &self,
+        directory: &Path,
+        session: &soup::Session,
+        progress: &dyn Fn(DownloadProgress),
+    ) -> Result<PathBuf, DownloadError> {
+        if let Some(path) = self.local_path() {
+            glib::debug!("Using local file at {}", path.display());
+            return Ok(path);
+        }
         let file_name = self.filename();
         let target_file = directory.join(file_name.as_ref());
-        let exists = gio::File::for_path(&target_file)
+        let target = gio::File::for_path(&target_file);
+        let exists = target
             .query_info_future(
                 gio::FILE_ATTRIBUTE_STANDARD_TYPE,
                 FileQueryInfoFlags::NONE,
@@ -93,21 +146,78 @@ impl DownloadableImage {
             .await
             .is_ok();
         if exists {
-            // If the target file exists already just return it
-            glib::debug!("Using existing file at {}", target_file.display());
-            Ok(target_file)
-        } else {
-            let result =
-                download_file_to_directory(session, &self.image_url, directory, &file_name).await;
-            match result {
-                Err(error) if error.matches(IOErrorEnum::Exists) => {
-                    // If the target file already exists, assume that a parallel download
-                    // finished first, i.e. that `target_file` was downloaded successfully
-                    Ok(target_file)
+            match verify_image_file(&target).await {
+                Ok(()) => {
+                    // If the target file exists already just return it
+                    glib::debug!("Using existing file at {}", target_file.display());
+                    return Ok(target_file);
+                }
+                Err(error) => {
+                    // Remove corrupt files from earlier downloads, and download again
+                    glib::warn!(
+                        "Removing invalid image at {}: {error}",
+                        target_file.display()
+                    );
+                    delete_file_ignore_error(&target).await;
                 }
-                Err(error) => Err(error),
-                Ok(()) => Ok(target_file),
             }
         }
+        download_file_to_directory(session, &self.image_url, directory, &file_name, progress)
+            .await?;
+        Ok(target_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(image_url: &str, title: &str) -> DownloadableImage {
+        DownloadableImage {
+            metadata: ImageMetadata {
+                title: title.into(),
+                description: None,
+                copyright: None,
+                url: None,
+                source: "test".into(),
+            },
+            image_url: image_url.into(),
+            pubdate: None,
+            suggested_filename: None,
+            dimensions: None,
+        }
+    }
+
+    #[test]
+    fn guess_filename_from_url_path() {
+        assert_eq!(
+            image("https://example.com/images/nebula.jpg", "Nebula").guess_filename(),
+            "nebula.jpg"
+        );
+        assert_eq!(
+            image("https://example.com/images/nebula.jpg?w=1920#top", "Nebula").guess_filename(),
+            "nebula.jpg"
+        );
+    }
+
+    #[test]
+    fn guess_filename_rejects_unusable_names() {
+        assert_eq!(
+            image("https://example.com/images/", "A nebula/galaxy").guess_filename(),
+            "A nebula_galaxy"
+        );
+        assert_eq!(
+            image("https://example.com/images/..?w=1", "..").guess_filename(),
+            glib::compute_checksum_for_string(
+                glib::ChecksumType::Sha256,
+                "https://example.com/images/..?w=1"
+            )
+            .unwrap()
+            .as_str()
+        );
+        assert_eq!(
+            image("https://example.com/.", "").guess_filename().len(),
+            64
+        );
     }
 }
@@ -6,122 +6,171 @@
 
 //! Sources of wallpapers.
 
-// See <https://github.com/gtk-rs/gtk-rs-core/discussions/1625>
-#![allow(clippy::as_conversions)]
-
-use glib::{GString, dpgettext2};
+use std::fmt::Debug;
 use std::path::PathBuf;
 
+use glib::GString;
+use glib::subclass::types::ObjectSubclassIsExt;
+use gnome_app_utils::futures::future::LocalBoxFuture;
+use jiff::civil::Date;
+
 use crate::config::G_LOG_DOMAIN;
 
 use super::DownloadableImage;
 
 mod error;
+mod registry;
 #[cfg(test)]
 pub mod testutil;
 
 pub use error::SourceError;
+pub use registry::SourceRegistry;
+
+#[glib::flags(name = "PotDSourceCapabilities")]
+pub enum SourceCapabilities {
+    /// The source can provide images for arbitrary dates.
+    DATE = 1,
+    /// The source can provide more than one image at a time.
+    MULTIPLE_IMAGES = 1 << 1,
+    /// The source needs an API key configured by the user.
+    API_KEY = 1 << 2,
+    /// All images of the source are available under free licenses.
+    FREE_LICENSE = 1 << 3,
+}
 
-#[derive(
-    Debug,
-    Default,
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    glib::Enum,
-    glib::Variant,
-    strum::EnumIter,
-    strum::IntoStaticStr,
-)]
-#[enum_type(name = "PotDSource")]
-#[strum(serialize_all = "kebab-case")]
-pub enum Source {
-    Apod,
-    Bing,
-    // This source provides images under free licenses
-    #[default]
-    Wikimedia,
-    Stalenhag,
-    Eoiod,
+impl Default for SourceCapabilities {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+/// A provider of images.
+///
+/// Implement this trait in a module under `images::sources` and add the
+/// provider to [`super::sources::builtin_sources`] to make a new source
+/// available to the application.
+pub trait SourceProvider {
+    /// The unique identifier of this source.
+    ///
+    /// We store this identifier in settings, and use it as name of the
+    /// directory to download images to, so it must never change.
+    fn id(&self) -> &str;
+
+    /// The translated human-readable name of this source.
+    fn i18n_name(&self) -> GString;
+
+    /// The URL of the homepage of this source, if it has one.
+    fn url(&self) -> Option<&str>;
+
+    /// What this source can do.
+    fn capabilities(&self) -> SourceCapabilities;
+
+    /// Fetch metadata for images offered by this source.
+    ///
+    /// `date` is only set if this source has [`SourceCapabilities::DATE`];
+    /// otherwise fetch images for today.
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>>;
+}
+
+impl Debug for dyn SourceProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SourceProvider({})", self.id())
+    }
+}
+
+glib::wrapper! {
+    /// A source of images.
+    ///
+    /// Wraps a [`SourceProvider`] in a glib object, to put sources into list
+    /// models and properties.
+    pub struct Source(ObjectSubclass<imp::Source>);
 }
 
-#[deny(clippy::as_conversions)]
 impl Source {
-    pub fn i18n_name(self) -> GString {
-        match self {
-            Source::Apod => dpgettext2(None, "source name", "NASA Astronomy Picture of the Day"),
-            Source::Bing => dpgettext2(None, "source name", "Bing"),
-            Source::Wikimedia => dpgettext2(None, "source name", "Wikimedia Picture of the Day"),
-            Source::Stalenhag => dpgettext2(None, "source name", "Simon Stålenhag"),
-            Source::Eoiod => dpgettext2(
-                None,
-                "source name",
-                "NASA Earth Observatory Image of the Day",
-            ),
-        }
+    /// Create a new source for the given `provider`.
+    pub fn new(provider: impl SourceProvider + 'static) -> Self {
+        let source: Self = glib::Object::builder().build();
+        source.imp().provider.set(Box::new(provider)).unwrap();
+        source
     }
 
-    pub fn id(self) -> &'static str {
-        self.into()
+    fn provider(&self) -> &dyn SourceProvider {
+        // We always set the provider right after construction in `Source::new`
+        self.imp().provider.get().unwrap().as_ref()
     }
 
-    pub fn url(self) -> &'static str {
-        match self {
-            Source::Apod => "https://apod.nasa.gov/",
-            Source::Bing => "https://bing.com",
-            Source::Wikimedia => "https://commons.wikimedia.org/wiki/Main_Page",
-            Source::Stalenhag => "https://simonstalenhag.se/",
-            Source::Eoiod => "https://earthobservatory.nasa.gov",
-        }
+    pub fn id(&self) -> &str {
+        self.provider().id()
     }
 
-    pub fn images_directory(self) -> PathBuf {
+    pub fn i18n_name(&self) -> GString {
+        self.provider().i18n_name()
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.provider().url()
+    }
+
+    pub fn capabilities(&self) -> SourceCapabilities {
+        self.provider().capabilities()
+    }
+
+    /// Whether this source can provide images for arbitrary dates.
+    pub fn supports_date(&self) -> bool {
+        self.capabilities().contains(SourceCapabilities::DATE)
+    }
+
+    pub fn images_directory(&self) -> PathBuf {
         crate::config::images_directory().join(self.id())
     }
 
-    /// Get metadata for images offer by this source for today.
+    /// Get metadata for images offer by this source for today, or `date`.
+    ///
+    /// Return a non-empty list of images.  Return an error if I/O failed, or
+    /// if the source did not provide any image.
     ///
-    /// Return a non-empty list of images for today.  Return an error if I/O
-    /// failed, or if the source did not provide any image.
+    /// Callers must only pass a `date` if this source [supports dates](Self::supports_date);
+    /// we return [`SourceError::DateNotSupported`] otherwise.
     pub async fn get_images(
-        self,
+        &self,
         session: &soup::Session,
-        date: Option<jiff::civil::Date>,
+        date: Option<Date>,
     ) -> Result<Vec<DownloadableImage>, SourceError> {
-        #[allow(clippy::wildcard_imports)]
-        use super::sources::*;
-
-        let today = crate::date::today_local();
-        let images = match self {
-            Source::Apod => vec![apod::fetch_picture_of_the_day(session, date).await?],
-            Source::Bing => {
-                date.inspect(|_| {
-                    glib::warn!("Bing does not support overriding the date");
-                });
-                bing::fetch_daily_images(session).await?
-            }
-            Source::Wikimedia => {
-                vec![wikimedia::fetch_featured_image(session, date.unwrap_or(today)).await?]
-            }
-            Source::Stalenhag => vec![stalenhag::pick_image_for_date_from_configured_collections(
-                date.unwrap_or(today),
-            )],
-            Source::Eoiod => {
-                date.inspect(|_| {
-                    glib::warn!(
-                        "NASA Earth Observatory Image of the Day does not support overriding the date"
-                    );
-                });
-                vec![eoiod::fetch_image_of_the_day(session).await?]
-            }
-        };
-
+        if date.is_some() && !self.supports_date() {
+            return Err(SourceError::DateNotSupported);
+        }
+        let images = self.provider().fetch_images(session, date).await?;
         if images.is_empty() {
-            glib::warn!("Source {self:?} returned an empty list of images!");
+            glib::warn!("Source {} returned an empty list of images!", self.id());
             Err(SourceError::NoImage)
         } else {
             Ok(images)
         }
     }
 }
+
+mod imp {
+    use std::cell::OnceCell;
+
+    use glib::subclass::prelude::*;
+
+    use super::SourceProvider;
+
+    #[derive(Default)]
+    pub struct Source {
+        pub provider: OnceCell<Box<dyn SourceProvider>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Source {
+        const NAME: &'static str = "PotDSource";
+
+        type Type = super::Source;
+    }
+
+    impl ObjectImpl for Source {}
+}
@@ -4,20 +4,24 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, time::Duration};
 
-use glib::GString;
+use glib::{GString, translate::IntoGlib};
 use gtk::gio::IOErrorEnum;
 
-use crate::net::http::{HttpError, download::DownloadError};
+use crate::net::http::{
+    HttpError,
+    download::{DownloadError, InvalidImage},
+};
 use crate::xml::rss::RssError;
 
 #[derive(Debug)]
 pub enum SourceError {
     /// IO failed.
     IO(glib::Error),
-    /// An unexpected HTTP status code, with an optional reason.
-    HttpStatus(soup::Status, Option<GString>),
+    /// An unexpected HTTP status code, with an optional reason, and an
+    /// optional delay after which to retry.
+    HttpStatus(soup::Status, Option<GString>, Option<Duration>),
     /// A deserialization error.
     InvalidJson(serde_json::Error),
     /// A deserialization error.
@@ -28,12 +32,50 @@ pub enum SourceError {
     NoImage,
     /// Invalid API key for the source
     InvalidApiKey,
-    /// The client was rate-limited.
-    RateLimited,
+    /// The client was rate-limited, with an optional delay after which to
+    /// retry.
+    RateLimited(Option<Duration>),
     /// The source did provide data, but the data does not denote an image.
     ///
     /// The source may have returned a video, for instance.
     NotAnImage,
+    /// The caller asked for images of a specific date, but the source does
+    /// not support dates.
+    DateNotSupported,
+    /// The source needs configuration by the user before it can provide images.
+    NotConfigured,
+}
+
+impl SourceError {
+    /// Whether only the user can fix this error, by changing settings.
+    ///
+    /// The source needs configuration, or rejected our credentials.  Retrying
+    /// later may succeed after any other error.
+    pub fn needs_settings_change(&self) -> bool {
+        match self {
+            Self::InvalidApiKey | Self::NotConfigured => true,
+            // Unauthorized
+            Self::HttpStatus(status, _, _) => status.into_glib() == 401,
+            _ => false,
+        }
+    }
+
+    /// Whether to fall back to another source after this error.
+    ///
+    /// We fall back if the source has no image for today, or needs
+    /// configuration, but not for e.g. network errors which likely affect all
+    /// sources.
+    pub fn should_fall_back(&self) -> bool {
+        matches!(self, Self::NoImage | Self::NotAnImage | Self::NotConfigured)
+    }
+
+    /// How long the source asked us to wait before retrying, if at all.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::HttpStatus(_, _, retry_after) | Self::RateLimited(retry_after) => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 impl From<glib::Error> for SourceError {
@@ -62,6 +104,12 @@ impl From<DownloadError> for SourceError {
                 soup::Status::NotFound => SourceError::NoImage,
                 _ => glib::Error::new(IOErrorEnum::Failed, &error.to_string()).into(),
             },
+            // A truncated download is not a broken image, but a network
+            // failure, and we resume it with the next attempt.
+            DownloadError::InvalidImage(ref invalid @ InvalidImage::Truncated { .. }) => {
+                glib::Error::new(IOErrorEnum::PartialInput, &invalid.to_string()).into()
+            }
+            DownloadError::InvalidImage(_) => SourceError::NotAnImage,
         }
     }
 }
@@ -73,7 +121,9 @@ impl From<HttpError> for SourceError {
             // We deliberately discard the body here: At this point we should never inspect the
             // source-specific body again; if there was anything interesting in the body the
             // source backend itself should've analyzed it by now.
-            HttpError::HttpStatus(status, reason, _) => Self::HttpStatus(status, reason),
+            HttpError::HttpStatus(status, reason, retry_after, _) => {
+                Self::HttpStatus(status, reason, retry_after)
+            }
             HttpError::InvalidJson(error) => Self::from(error),
         }
     }
@@ -84,19 +134,23 @@ impl Display for SourceError {
         match self {
             SourceError::IO(error) => write!(f, "{error}"),
             #[allow(clippy::use_debug)]
-            SourceError::HttpStatus(status, None) => write!(f, "HTTP status {status:?}"),
+            SourceError::HttpStatus(status, None, _) => write!(f, "HTTP status {status:?}"),
             #[allow(clippy::use_debug)]
-            SourceError::HttpStatus(status, Some(reason)) => {
+            SourceError::HttpStatus(status, Some(reason), _) => {
                 write!(f, "HTTP status {status:?} {reason}")
             }
             SourceError::InvalidJson(error) => write!(f, "Invalid JSON: {error}"),
             SourceError::InvalidRss(error) => write!(f, "Invalid RSS: {error}"),
             SourceError::NoImage => write!(f, "No image available"),
             SourceError::InvalidApiKey => write!(f, "The API key used was invalid"),
-            SourceError::RateLimited => write!(f, "The client was rate limited"),
+            SourceError::RateLimited(_) => write!(f, "The client was rate limited"),
             SourceError::NotAnImage => {
                 write!(f, "The source return no image data but e.g. a video")
             }
+            SourceError::DateNotSupported => {
+                write!(f, "The source does not provide images for other dates")
+            }
+            SourceError::NotConfigured => write!(f, "The source is not configured"),
             SourceError::ScrapingFailed(message) => write!(f, "Failed to scrape data: {message}"),
         }
     }
@@ -112,3 +166,84 @@ impl Error for SourceError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use gtk::gio::IOErrorEnum;
+
+    use crate::net::http::download::{DownloadError, InvalidImage};
+
+    use super::SourceError;
+
+    #[test]
+    fn needs_settings_change() {
+        assert!(SourceError::InvalidApiKey.needs_settings_change());
+        assert!(SourceError::NotConfigured.needs_settings_change());
+        assert!(
+            SourceError::HttpStatus(soup::Status::Unauthorized, None, None).needs_settings_change()
+        );
+        assert!(!SourceError::RateLimited(None).needs_settings_change());
+        assert!(!SourceError::NoImage.needs_settings_change());
+        assert!(!SourceError::DateNotSupported.needs_settings_change());
+        for status in [
+            soup::Status::BadRequest,
+            soup::Status::Forbidden,
+            soup::Status::NotFound,
+            soup::Status::ServiceUnavailable,
+            soup::Status::InternalServerError,
+        ] {
+            assert!(!SourceError::HttpStatus(status, None, None).needs_settings_change());
+        }
+    }
+
+    #[test]
+    fn should_fall_back() {
+        assert!(SourceError::NoImage.should_fall_back());
+        assert!(SourceError::NotAnImage.should_fall_back());
+        assert!(SourceError::NotConfigured.should_fall_back());
+        assert!(!SourceError::RateLimited(None).should_fall_back());
+        assert!(!SourceError::InvalidApiKey.should_fall_back());
+    }
+
+    #[test]
+    fn retry_after() {
+        let delay = Duration::from_secs(3600);
+        assert_eq!(
+            SourceError::RateLimited(Some(delay)).retry_after(),
+            Some(delay)
+        );
+        assert_eq!(SourceError::RateLimited(None).retry_after(), None);
+        assert_eq!(
+            SourceError::HttpStatus(soup::Status::ServiceUnavailable, None, Some(delay))
+                .retry_after(),
+            Some(delay)
+        );
+        assert_eq!(SourceError::NoImage.retry_after(), None);
+    }
+
+    #[test]
+    fn from_truncated_download() {
+        let error = SourceError::from(DownloadError::InvalidImage(InvalidImage::Truncated {
+            expected: 2048,
+            actual: 1024,
+        }));
+        assert!(
+            matches!(&error, SourceError::IO(error) if error.matches(IOErrorEnum::PartialInput))
+        );
+        assert!(!error.needs_settings_change());
+        assert!(!error.should_fall_back());
+    }
+
+    #[test]
+    fn from_invalid_image_download() {
+        for invalid in [
+            InvalidImage::ContentType("text/html".into()),
+            InvalidImage::UnknownFormat,
+        ] {
+            let error = SourceError::from(DownloadError::InvalidImage(invalid));
+            assert!(matches!(error, SourceError::NotAnImage));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! The registry of all available sources.
+
+use glib::subclass::types::ObjectSubclassIsExt;
+use gtk::gio::{self, prelude::ListModelExt};
+
+use crate::config::G_LOG_DOMAIN;
+
+use super::super::sources::feed::{self, Feed};
+use super::Source;
+
+glib::wrapper! {
+    /// All sources available to the application.
+    ///
+    /// A list model of [`Source`] objects, to enumerate sources in the UI, and
+    /// to resolve source identifiers from settings to sources.
+    pub struct SourceRegistry(ObjectSubclass<imp::SourceRegistry>)
+        @implements gio::ListModel;
+}
+
+impl SourceRegistry {
+    /// The identifier of the default source.
+    ///
+    /// This source provides images under free licenses.
+    pub const DEFAULT_SOURCE_ID: &'static str = super::super::sources::wikimedia::ID;
+
+    /// Get all sources in this registry.
+    pub fn sources(&self) -> Vec<Source> {
+        self.imp().sources.borrow().clone()
+    }
+
+    /// Find the source with the given `id`.
+    pub fn lookup(&self, id: &str) -> Option<Source> {
+        self.imp()
+            .sources
+            .borrow()
+            .iter()
+            .find(|source| source.id() == id)
+            .cloned()
+    }
+
+    /// Get `primary` followed by all sources in `fallbacks`.
+    ///
+    /// Skip unknown fallbacks, and sources which are already in the chain.
+    pub fn fallback_chain(&self, primary: &Source, fallbacks: &[impl AsRef<str>]) -> Vec<Source> {
+        let mut chain = vec![primary.clone()];
+        for id in fallbacks {
+            let id = id.as_ref();
+            if chain.iter().any(|source| source.id() == id) {
+                continue;
+            }
+            match self.lookup(id) {
+                Some(source) => chain.push(source),
+                None => glib::warn!("Skipping unknown fallback source {id}"),
+            }
+        }
+        chain
+    }
+
+    /// Get the default source.
+    pub fn default_source(&self) -> Source {
+        // We always register the default source
+        self.lookup(Self::DEFAULT_SOURCE_ID).unwrap()
+    }
+
+    /// Replace all feed sources in this registry with `feeds`.
+    ///
+    /// Feeds always come after all built-in sources.
+    pub fn set_feeds(&self, feeds: Vec<Feed>) {
+        let (position, removed, added) = {
+            let mut sources = self.imp().sources.borrow_mut();
+            let position = sources
+                .iter()
+                .position(|source| source.id().starts_with(feed::ID_PREFIX))
+                .unwrap_or(sources.len());
+            let removed = sources.len() - position;
+            sources.truncate(position);
+            sources.extend(feeds.into_iter().map(Source::new));
+            (position, removed, sources.len() - position)
+        };
+        glib::debug!("Replaced {removed} feed sources with {added} feed sources");
+        self.items_changed(
+            u32::try_from(position).unwrap(),
+            u32::try_from(removed).unwrap(),
+            u32::try_from(added).unwrap(),
+        );
+    }
+
+    /// Find the source with the given `id`, or fall back to the default source.
+    pub fn lookup_or_default(&self, id: &str) -> Source {
+        self.lookup(id).unwrap_or_else(|| {
+            glib::warn!("Unknown source {id}, falling back to default source");
+            self.default_source()
+        })
+    }
+}
+
+impl Default for SourceRegistry {
+    fn default() -> Self {
+        glib::Object::builder().build()
+    }
+}
+
+mod imp {
+    use std::cell::RefCell;
+
+    use glib::prelude::*;
+    use glib::subclass::prelude::*;
+    use gtk::gio::{self, subclass::prelude::*};
+
+    use super::super::super::sources::builtin_sources;
+    use super::super::Source;
+
+    pub struct SourceRegistry {
+        pub sources: RefCell<Vec<Source>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SourceRegistry {
+        const NAME: &'static str = "PotDSourceRegistry";
+
+        type Type = super::SourceRegistry;
+
+        type Interfaces = (gio::ListModel,);
+
+        fn new() -> Self {
+            Self {
+                sources: RefCell::new(builtin_sources()),
+            }
+        }
+    }
+
+    impl ObjectImpl for SourceRegistry {}
+
+    impl ListModelImpl for SourceRegistry {
+        fn item_type(&self) -> glib::Type {
+            Source::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            u32::try_from(self.sources.borrow().len()).unwrap()
+        }
+
+        fn item(&self, position: u32) -> Option<glib::Object> {
+            self.sources
+                .borrow()
+                .get(usize::try_from(position).ok()?)
+                .map(|source| source.clone().upcast())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::images::{SourceCapabilities, SourceProvider};
+
+    use super::*;
+
+    #[test]
+    fn source_ids_are_unique() {
+        let sources = SourceRegistry::default().sources();
+        let ids = sources.iter().map(Source::id).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), sources.len());
+    }
+
+    #[test]
+    fn fallback_chain_skips_unknown_and_duplicate_sources() {
+        let registry = SourceRegistry::default();
+        let primary = registry.lookup("apod").unwrap();
+        let chain = registry.fallback_chain(
+            &primary,
+            &[
+                "wikimedia",
+                "apod",
+                "does-not-exist",
+                "stalenhag",
+                "wikimedia",
+            ],
+        );
+        let ids = chain.iter().map(Source::id).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["apod", "wikimedia", "stalenhag"]);
+    }
+
+    #[test]
+    fn set_feeds_replaces_feeds() {
+        let registry = SourceRegistry::default();
+        let n_builtin = registry.n_items();
+        let feed = |url: &str| {
+            Feed::new(
+                "Photos".into(),
+                url.into(),
+                feed::FeedImageRule::MediaContent,
+            )
+        };
+
+        registry.set_feeds(vec![
+            feed("https://example.com/a.rss"),
+            feed("https://example.com/b.rss"),
+        ]);
+        assert_eq!(registry.n_items(), n_builtin + 2);
+
+        let b = feed("https://example.com/b.rss");
+        registry.set_feeds(vec![b.clone()]);
+        assert_eq!(registry.n_items(), n_builtin + 1);
+        assert!(registry.lookup(SourceProvider::id(&b)).is_some());
+        assert_eq!(
+            registry.lookup_or_default("feed-unknown").id(),
+            SourceRegistry::DEFAULT_SOURCE_ID
+        );
+    }
+
+    #[test]
+    fn default_source_has_free_license() {
+        let source = SourceRegistry::default().default_source();
+        assert!(
+            source
+                .capabilities()
+                .contains(SourceCapabilities::FREE_LICENSE)
+        );
+    }
+}
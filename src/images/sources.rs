@@ -4,8 +4,26 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
+use super::Source;
+
 pub mod apod;
 pub mod bing;
 pub mod eoiod;
+pub mod favorites;
+pub mod feed;
+pub mod local;
 pub mod stalenhag;
 pub mod wikimedia;
+
+/// Get all built-in sources, in the order we show them in the UI.
+pub fn builtin_sources() -> Vec<Source> {
+    vec![
+        Source::new(apod::Apod),
+        Source::new(bing::Bing),
+        Source::new(wikimedia::Wikimedia),
+        Source::new(stalenhag::Stalenhag),
+        Source::new(eoiod::Eoiod),
+        Source::new(local::Local),
+        Source::new(favorites::Favorites),
+    ]
+}
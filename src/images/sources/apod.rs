@@ -4,7 +4,8 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use glib::Priority;
+use glib::{GString, Priority, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
 use gtk::gio::prelude::SettingsExt;
 use serde::Deserialize;
 use url::Url;
@@ -14,7 +15,12 @@ use crate::{
     net::http::{HttpError, SoupSessionExt},
 };
 
-use super::super::{DownloadableImage, ImageMetadata, Source, SourceError};
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the APOD source.
+pub const ID: &str = "apod";
 
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -57,11 +63,12 @@ impl TryFrom<ApodMetadata> for DownloadableImage {
                     description: Some(metadata.explanation),
                     copyright: metadata.copyright,
                     url: Some(url),
-                    source: Source::Apod,
+                    source: ID.to_owned(),
                 },
                 image_url: metadata.hdurl.unwrap_or(metadata.url),
                 pubdate: Some(metadata.date),
                 suggested_filename: None,
+                dimensions: None,
             })
         } else {
             Err(SourceError::NotAnImage)
@@ -80,12 +87,12 @@ struct ApodErrorBody {
 }
 
 fn to_source_error(error: HttpError) -> SourceError {
-    if let HttpError::HttpStatus(_, _, data) = &error
+    if let HttpError::HttpStatus(_, _, retry_after, data) = &error
         && let Ok(body) = serde_json::from_slice::<ApodErrorBody>(data)
     {
         match body.error.code.as_str() {
             "API_KEY_INVALID" => return SourceError::InvalidApiKey,
-            "OVER_RATE_LIMIT" => return SourceError::RateLimited,
+            "OVER_RATE_LIMIT" => return SourceError::RateLimited(*retry_after),
             _ => (),
         }
     }
@@ -125,6 +132,35 @@ pub async fn fetch_picture_of_the_day(
     query_metadata(session, date, &api_key).await?.try_into()
 }
 
+/// NASA Astronomy Picture of the Day.
+pub struct Apod;
+
+impl SourceProvider for Apod {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "NASA Astronomy Picture of the Day")
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some("https://apod.nasa.gov/")
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::DATE | SourceCapabilities::API_KEY
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        date: Option<jiff::civil::Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        async move { Ok(vec![fetch_picture_of_the_day(session, date).await?]) }.boxed_local()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use glib::async_test;
@@ -132,6 +168,20 @@ mod tests {
     use super::*;
     use crate::images::source::testutil::soup_session;
 
+    #[test]
+    fn rate_limited_with_retry_after() {
+        let body = br#"{"error":{"code":"OVER_RATE_LIMIT","message":"You have exceeded your rate limit."}}"#;
+        let retry_after = std::time::Duration::from_secs(3600);
+        let error = to_source_error(HttpError::HttpStatus(
+            soup::Status::Forbidden,
+            None,
+            Some(retry_after),
+            glib::Bytes::from_static(body),
+        ));
+        assert!(matches!(error, SourceError::RateLimited(Some(delay)) if delay == retry_after));
+        assert_eq!(error.retry_after(), Some(retry_after));
+    }
+
     #[async_test]
     async fn fetch_apod() {
         // We use a separate API key for testing, with account ID 431bacf7-4e26-407f-9ca3-06a17d8d7400
@@ -159,7 +209,7 @@ mod tests {
             metadata.url.unwrap(),
             "https://apod.nasa.gov/apod/ap250327.html"
         );
-        assert_eq!(metadata.source, Source::Apod);
+        assert_eq!(metadata.source, ID);
         assert_eq!(
             image.image_url,
             "https://apod.nasa.gov/apod/image/2503/291_lorand_fenyes_m81_kicsi.jpg"
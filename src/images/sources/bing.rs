@@ -6,14 +6,20 @@
 
 use std::borrow::Cow;
 
-use glib::{Priority, dpgettext2};
+use glib::{GString, Priority, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
 use jiff::civil::Date;
 use serde::{Deserialize, Deserializer, de};
 use url::Url;
 
 use crate::{config::G_LOG_DOMAIN, net::http::SoupSessionExt};
 
-use super::super::{DownloadableImage, ImageMetadata, Source, SourceError};
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the Bing source.
+pub const ID: &str = "bing";
 
 #[derive(Debug, Deserialize)]
 struct BingImage {
@@ -77,11 +83,12 @@ impl TryFrom<BingImage> for DownloadableImage {
                             dpgettext2(None, "source.bing.copyright", "All rights reserved.")
                                 .into(),
                         ),
-                        source: Source::Bing,
+                        source: ID.to_owned(),
                     },
                     image_url: image_url.into(),
                     pubdate: Some(image.startdate),
                     suggested_filename,
+                    dimensions: None,
                 }
             })
             .inspect_err(|error| {
@@ -146,6 +153,35 @@ pub async fn fetch_daily_images(
         .collect())
 }
 
+/// Bing images of the day.
+pub struct Bing;
+
+impl SourceProvider for Bing {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "Bing")
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some("https://bing.com")
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::MULTIPLE_IMAGES
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        _date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        fetch_daily_images(session).boxed_local()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use glib::async_test;
@@ -6,16 +6,21 @@
 
 //! Earth Observatory Image Of The Day
 
-use glib::dpgettext2;
+use glib::{GString, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
 use quick_xml::NsReader;
-use soup::prelude::SessionExt;
 
 use crate::{
-    net::http::HttpError,
+    net::http::SoupSessionExt,
     xml::rss::{RssItem, read_rss_channel},
 };
 
-use super::super::{DownloadableImage, ImageMetadata, Source, SourceError};
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the EOIOD source.
+pub const ID: &str = "eoiod";
 
 fn get_feed_message() -> soup::Message {
     soup::Message::new(
@@ -36,7 +41,7 @@ fn image_from_item(item: RssItem) -> Result<DownloadableImage, SourceError> {
             dpgettext2(None, "source.eoiod.copyright", "NASA Earth Observatory").into(),
         ),
         url: item.link,
-        source: Source::Eoiod,
+        source: ID.to_owned(),
     };
     let thumbnail = item.thumbnail.ok_or_else(|| {
         SourceError::ScrapingFailed(
@@ -48,6 +53,7 @@ fn image_from_item(item: RssItem) -> Result<DownloadableImage, SourceError> {
         image_url: thumbnail.replace("_th.", "_lrg."),
         pubdate: item.pubdate.map(|dt| dt.date()),
         suggested_filename: None,
+        dimensions: None,
     };
     Ok(image)
 }
@@ -65,11 +71,40 @@ pub async fn fetch_image_of_the_day(
 ) -> Result<DownloadableImage, SourceError> {
     let message = get_feed_message();
     let body = session
-        .send_and_read_future(&message, glib::Priority::DEFAULT)
+        .send_and_read_cached(&message, glib::Priority::DEFAULT)
         .await?;
-    if message.status() == soup::Status::Ok {
-        get_first_image_from_feed(&body)
-    } else {
-        Err(HttpError::HttpStatus(message.status(), message.reason_phrase(), body).into())
+    get_first_image_from_feed(&body)
+}
+
+/// NASA Earth Observatory Image of the Day.
+pub struct Eoiod;
+
+impl SourceProvider for Eoiod {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(
+            None,
+            "source name",
+            "NASA Earth Observatory Image of the Day",
+        )
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some("https://earthobservatory.nasa.gov")
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::empty()
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        _date: Option<jiff::civil::Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        async move { Ok(vec![fetch_image_of_the_day(session).await?]) }.boxed_local()
     }
 }
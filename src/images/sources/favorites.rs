@@ -0,0 +1,155 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Rotate through favorite images.
+
+use glib::{GString, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::{self, IOErrorEnum, prelude::FileExt, prelude::SettingsExtManual};
+use jiff::civil::Date;
+
+use crate::config::G_LOG_DOMAIN;
+use crate::history::{History, HistoryEntry, favorite_entries};
+
+use super::super::{DownloadableImage, SourceCapabilities, SourceError, SourceProvider};
+
+/// The identifier of the favorites source.
+pub const ID: &str = "favorites";
+
+/// The date from which we count days to cycle through favorites.
+const BASE_DATE: Date = jiff::civil::date(1970, 1, 1);
+
+/// Create an image for the downloaded file of a favorite `entry`.
+///
+/// Refer to the downloaded file directly, so that we never download favorites
+/// again.
+fn image_from_entry(entry: &HistoryEntry) -> Option<DownloadableImage> {
+    let file = entry.file.as_deref().filter(|file| file.is_file())?;
+    Some(DownloadableImage {
+        metadata: entry.metadata.clone(),
+        image_url: gio::File::for_path(file).uri().into(),
+        pubdate: entry.pubdate,
+        suggested_filename: None,
+        dimensions: None,
+    })
+}
+
+/// Pick the favorite for `date` from the history in `data`.
+fn pick_favorite(
+    data: &[u8],
+    favorites: &[String],
+    date: Date,
+) -> Result<Option<DownloadableImage>, SourceError> {
+    let history = History::from_json(data)?;
+    let images = favorite_entries(history.entries(), favorites)
+        .into_iter()
+        .filter_map(image_from_entry)
+        .collect::<Vec<_>>();
+    let index = crate::date::cycle_index_for_date(BASE_DATE, date, images.len());
+    Ok(index.and_then(|index| images.into_iter().nth(index)))
+}
+
+/// Favorite images of the user.
+pub struct Favorites;
+
+impl SourceProvider for Favorites {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "Favorites")
+    }
+
+    fn url(&self) -> Option<&str> {
+        // Favorites have no homepage
+        None
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::DATE
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        _session: &'a soup::Session,
+        date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        let favorites = crate::config::get_settings().get::<Vec<String>>("favorite-images");
+        let date = date.unwrap_or_else(crate::date::today_local);
+        async move {
+            if favorites.is_empty() {
+                return Err(SourceError::NoImage);
+            }
+            glib::debug!("Picking one of {} favorites for {date}", favorites.len());
+            let file = gio::File::for_path(crate::config::history_file());
+            let (data, _) = file.load_contents_future().await?;
+            // Checking whether favorites still exist may block, so let's move
+            // it off the main loop.
+            let image = gio::spawn_blocking(move || pick_favorite(&data, &favorites, date))
+                .await
+                .map_err(|_| glib::Error::new(IOErrorEnum::Failed, "Picking favorite failed"))??;
+            Ok(image.into_iter().collect())
+        }
+        .boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use crate::history::{History, HistoryEntry};
+    use crate::images::{DownloadableImage, ImageMetadata};
+
+    use super::pick_favorite;
+
+    #[test]
+    fn pick_favorite_skips_missing_files() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let existing = directory.join("a.jpg");
+        std::fs::write(&existing, b"").unwrap();
+        let mut history = History::default();
+        for (url, file) in [
+            ("https://a.jpg", existing.clone()),
+            ("https://b.jpg", directory.join("b.jpg")),
+        ] {
+            let image = DownloadableImage {
+                metadata: ImageMetadata {
+                    title: "A lake".into(),
+                    description: None,
+                    copyright: None,
+                    url: None,
+                    source: "bing".into(),
+                },
+                image_url: url.into(),
+                pubdate: None,
+                suggested_filename: None,
+                dimensions: None,
+            };
+            history.record(HistoryEntry::new(date(2025, 10, 16), &image, Some(file)));
+        }
+        let favorites = vec!["https://a.jpg".to_owned(), "https://b.jpg".to_owned()];
+
+        let picked = (17..19)
+            .map(|day| pick_favorite(&history.to_json(), &favorites, date(2025, 10, day)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let paths = picked
+            .into_iter()
+            .map(|image| image.unwrap().local_path().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec![existing.clone(), existing]);
+    }
+
+    #[test]
+    fn pick_favorite_without_favorites() {
+        let history = History::default();
+        let picked = pick_favorite(&history.to_json(), &[], date(2025, 10, 17)).unwrap();
+        assert!(picked.is_none());
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! User-defined RSS and Media RSS feeds.
+
+use glib::{GString, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::{self, IOErrorEnum, prelude::SettingsExtManual};
+use quick_xml::NsReader;
+
+use crate::config::G_LOG_DOMAIN;
+use crate::net::http::SoupSessionExt;
+use crate::xml::rss::{RssItem, read_rss_channel};
+
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The prefix of identifiers of feed sources.
+pub const ID_PREFIX: &str = "feed-";
+
+/// How to find the full-resolution image in a feed item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedImageRule {
+    /// Use the largest image in `media:content`.
+    MediaContent,
+    /// Use the `media:thumbnail` of the item.
+    MediaThumbnail,
+    /// Use the `<enclosure>` of the item.
+    Enclosure,
+}
+
+impl FeedImageRule {
+    /// All rules, in the order we show them in the UI.
+    pub const ALL: [Self; 3] = [Self::MediaContent, Self::MediaThumbnail, Self::Enclosure];
+
+    /// The identifier of this rule, as stored in settings.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::MediaContent => "media-content",
+            Self::MediaThumbnail => "media-thumbnail",
+            Self::Enclosure => "enclosure",
+        }
+    }
+
+    /// Find the rule with the given `id`.
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.id() == id)
+    }
+
+    /// The translated human-readable name of this rule.
+    pub fn i18n_name(self) -> GString {
+        match self {
+            Self::MediaContent => dpgettext2(None, "feed-image-rule.name", "Largest media content"),
+            Self::MediaThumbnail => dpgettext2(None, "feed-image-rule.name", "Media thumbnail"),
+            Self::Enclosure => dpgettext2(None, "feed-image-rule.name", "Enclosure"),
+        }
+    }
+
+    /// Get the image URL from `item` according to this rule.
+    fn image_url(self, item: &RssItem) -> Option<&str> {
+        match self {
+            Self::MediaContent => item
+                .media_content
+                .iter()
+                .filter(|media| media.is_image())
+                .max_by_key(|media| media.width.unwrap_or_default())
+                .map(|media| media.url.as_str()),
+            Self::MediaThumbnail => item.thumbnail.as_deref(),
+            Self::Enclosure => item
+                .enclosure
+                .as_ref()
+                .filter(|media| media.is_image())
+                .map(|media| media.url.as_str()),
+        }
+    }
+}
+
+/// A feed configured by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feed {
+    id: String,
+    name: String,
+    url: String,
+    rule: FeedImageRule,
+}
+
+impl Feed {
+    /// Create a new feed named `name` for the given `url`.
+    ///
+    /// Derive the identifier of the feed from its URL, so that the identifier
+    /// remains stable as long as the URL does not change.
+    pub fn new(name: String, url: String, rule: FeedImageRule) -> Self {
+        let checksum = glib::compute_checksum_for_string(glib::ChecksumType::Sha256, url.as_str())
+            .unwrap_or_default();
+        let id = format!(
+            "{ID_PREFIX}{}",
+            checksum.as_str().get(..16).unwrap_or_default()
+        );
+        Self {
+            id,
+            name,
+            url,
+            rule,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rule(&self) -> FeedImageRule {
+        self.rule
+    }
+}
+
+/// Read all configured feeds from `settings`.
+///
+/// Skip over feeds with an unknown image rule.
+pub fn feeds_from_settings(settings: &gio::Settings) -> Vec<Feed> {
+    settings
+        .get::<Vec<(String, String, String)>>("feed-sources")
+        .into_iter()
+        .filter_map(|(name, url, rule)| match FeedImageRule::from_id(&rule) {
+            Some(rule) => Some(Feed::new(name, url, rule)),
+            None => {
+                glib::warn!("Ignoring feed {url} with unknown image rule {rule}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Store `feeds` in `settings`.
+pub fn store_feeds(settings: &gio::Settings, feeds: &[Feed]) -> Result<(), glib::BoolError> {
+    let value = feeds
+        .iter()
+        .map(|feed| {
+            (
+                feed.name.clone(),
+                feed.url.clone(),
+                feed.rule.id().to_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    settings.set("feed-sources", value)
+}
+
+fn image_from_item(feed: &Feed, item: &RssItem) -> Option<DownloadableImage> {
+    let Some(image_url) = feed.rule.image_url(item) else {
+        glib::debug!(
+            "Skipping item {:?} of feed {} without image",
+            item.title,
+            feed.url
+        );
+        return None;
+    };
+    let metadata = ImageMetadata {
+        title: item.title.clone().unwrap_or_else(|| feed.name.clone()),
+        description: item.description.clone(),
+        copyright: None,
+        url: item.link.clone(),
+        source: feed.id.clone(),
+    };
+    Some(DownloadableImage {
+        metadata,
+        image_url: image_url.to_owned(),
+        pubdate: item.pubdate.as_ref().map(jiff::Zoned::date),
+        suggested_filename: None,
+        dimensions: None,
+    })
+}
+
+/// Get images of the most recent day from a feed.
+///
+/// If items have publication dates return images of all items published on
+/// the same day as the newest item; otherwise return the image of the first
+/// item which has an image.
+fn images_from_feed(feed: &Feed, xml: &[u8]) -> Result<Vec<DownloadableImage>, SourceError> {
+    let images = read_rss_channel(NsReader::from_reader(xml))?
+        .filter_map(|item| item.map(|item| image_from_item(feed, &item)).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    match images.iter().filter_map(|image| image.pubdate).max() {
+        Some(newest) => Ok(images
+            .into_iter()
+            .filter(|image| image.pubdate == Some(newest))
+            .collect()),
+        None => Ok(images.into_iter().take(1).collect()),
+    }
+}
+
+impl SourceProvider for Feed {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn i18n_name(&self) -> GString {
+        self.name.as_str().into()
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some(&self.url)
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::MULTIPLE_IMAGES
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        _date: Option<jiff::civil::Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        async move {
+            let message = soup::Message::new("GET", &self.url).map_err(|error| {
+                glib::Error::new(
+                    IOErrorEnum::InvalidArgument,
+                    &format!("Invalid URL: {}: {error}", self.url),
+                )
+            })?;
+            let body = session
+                .send_and_read_cached(&message, glib::Priority::DEFAULT)
+                .await?;
+            images_from_feed(self, &body)
+        }
+        .boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Feed, FeedImageRule, ID_PREFIX};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Photos</title>
+    <item>
+      <title>A lake</title>
+      <link>https://photos.example.com/lake</link>
+      <pubDate>Sat, 17 Oct 2026 08:00:00 +0000</pubDate>
+      <media:content url="https://photos.example.com/lake_small.jpg" medium="image" width="640" />
+      <media:content url="https://photos.example.com/lake_large.jpg" medium="image" width="4000" />
+      <media:thumbnail url="https://photos.example.com/lake_th.jpg" />
+    </item>
+    <item>
+      <title>A video</title>
+      <pubDate>Sat, 17 Oct 2026 07:00:00 +0000</pubDate>
+      <media:content url="https://photos.example.com/river.mp4" medium="video" />
+      <enclosure url="https://photos.example.com/river.mp4" type="video/mp4" />
+    </item>
+    <item>
+      <title>A forest</title>
+      <pubDate>Sat, 17 Oct 2026 06:00:00 +0000</pubDate>
+      <media:content url="https://photos.example.com/forest.jpg" type="image/jpeg" />
+      <enclosure url="https://photos.example.com/forest_enclosure.jpg" type="image/jpeg" />
+    </item>
+    <item>
+      <title>A mountain</title>
+      <pubDate>Fri, 16 Oct 2026 08:00:00 +0000</pubDate>
+      <media:content url="https://photos.example.com/mountain.jpg" medium="image" />
+    </item>
+  </channel>
+</rss>"#;
+
+    fn image_urls(rule: FeedImageRule) -> Vec<String> {
+        let feed = Feed::new(
+            "Photos".into(),
+            "https://photos.example.com/feed.rss".into(),
+            rule,
+        );
+        super::images_from_feed(&feed, XML.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|image| image.image_url)
+            .collect()
+    }
+
+    #[test]
+    fn feed_id_is_stable() {
+        let feed = Feed::new(
+            "Photos".into(),
+            "https://photos.example.com/feed.rss".into(),
+            FeedImageRule::MediaContent,
+        );
+        let renamed = Feed::new(
+            "Other".into(),
+            "https://photos.example.com/feed.rss".into(),
+            FeedImageRule::Enclosure,
+        );
+        assert!(feed.id.starts_with(ID_PREFIX));
+        assert_eq!(feed.id.len(), ID_PREFIX.len() + 16);
+        assert_eq!(feed.id, renamed.id);
+    }
+
+    #[test]
+    fn rule_ids_roundtrip() {
+        for rule in FeedImageRule::ALL {
+            assert_eq!(FeedImageRule::from_id(rule.id()), Some(rule));
+        }
+        assert_eq!(FeedImageRule::from_id("foo"), None);
+    }
+
+    #[test]
+    fn images_from_feed_media_content() {
+        assert_eq!(
+            image_urls(FeedImageRule::MediaContent),
+            vec![
+                "https://photos.example.com/lake_large.jpg",
+                "https://photos.example.com/forest.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn images_from_feed_media_thumbnail() {
+        assert_eq!(
+            image_urls(FeedImageRule::MediaThumbnail),
+            vec!["https://photos.example.com/lake_th.jpg"]
+        );
+    }
+
+    #[test]
+    fn images_from_feed_enclosure() {
+        assert_eq!(
+            image_urls(FeedImageRule::Enclosure),
+            vec!["https://photos.example.com/forest_enclosure.jpg"]
+        );
+    }
+
+    #[test]
+    fn images_from_feed_metadata() {
+        let feed = Feed::new(
+            "Photos".into(),
+            "https://photos.example.com/feed.rss".into(),
+            FeedImageRule::MediaContent,
+        );
+        let image = super::images_from_feed(&feed, XML.as_bytes())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert_eq!(image.metadata.title, "A lake");
+        assert_eq!(
+            image.metadata.url.as_deref(),
+            Some("https://photos.example.com/lake")
+        );
+        assert_eq!(image.metadata.source, feed.id);
+        assert_eq!(image.pubdate, Some(jiff::civil::date(2026, 10, 17)));
+    }
+}
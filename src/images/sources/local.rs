@@ -0,0 +1,326 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Images from a local folder.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use glib::{GString, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::{
+    self, IOErrorEnum,
+    prelude::{FileExt, SettingsExt},
+};
+use jiff::civil::Date;
+
+use crate::config::G_LOG_DOMAIN;
+use crate::xml::xmp::{XmpMetadata, find_xmp_packet, read_xmp};
+
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the local folder source.
+pub const ID: &str = "local";
+
+/// File extensions of images we pick from the folder.
+const IMAGE_EXTENSIONS: [&str; 6] = ["avif", "jpeg", "jpg", "jxl", "png", "webp"];
+
+/// The date from which we count days to cycle through images.
+const BASE_DATE: Date = jiff::civil::date(1970, 1, 1);
+
+fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        })
+        && path.is_file()
+}
+
+/// List all images in `folder`, sorted by file name.
+fn list_images(folder: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut images = std::fs::read_dir(folder)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| is_image_file(path))
+        .collect::<Vec<_>>();
+    images.sort();
+    Ok(images)
+}
+
+/// Shuffle `images` deterministically by the given `seed`.
+fn shuffle(images: &[PathBuf], seed: i64) -> Vec<&Path> {
+    let mut shuffled = images
+        .iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let key = glib::compute_checksum_for_string(
+                glib::ChecksumType::Sha256,
+                format!("{seed}/{name}"),
+            );
+            (key, path.as_path())
+        })
+        .collect::<Vec<_>>();
+    shuffled.sort();
+    shuffled.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Pick an image for `date` from `images`.
+///
+/// Cycle through all images in order, or, if `shuffled`, in a random order
+/// which changes after every cycle through all images.
+fn pick_image_for_date(images: &[PathBuf], date: Date, shuffled: bool) -> Option<&Path> {
+    let index = crate::date::cycle_index_for_date(BASE_DATE, date, images.len())?;
+    if shuffled {
+        let days = i64::from((date - BASE_DATE).get_days());
+        let cycle = days.div_euclid(i64::try_from(images.len()).ok()?);
+        shuffle(images, cycle).get(index).copied()
+    } else {
+        images.get(index).map(PathBuf::as_path)
+    }
+}
+
+fn read_xmp_file(path: &Path) -> Option<XmpMetadata> {
+    let data = std::fs::read(path).ok()?;
+    read_xmp(&data)
+        .inspect_err(|error| {
+            glib::warn!("Failed to read XMP sidecar {}: {error}", path.display());
+        })
+        .ok()
+}
+
+/// Read metadata of the image at `path`.
+///
+/// Prefer XMP sidecar files, i.e. `image.xmp` or `image.jpg.xmp`, and fall
+/// back to XMP metadata embedded in the image.
+fn read_metadata(path: &Path) -> XmpMetadata {
+    let mut sidecar_with_extension = OsString::from(path);
+    sidecar_with_extension.push(".xmp");
+    let sidecars = [
+        path.with_extension("xmp"),
+        PathBuf::from(sidecar_with_extension),
+    ];
+    sidecars
+        .iter()
+        .find_map(|sidecar| read_xmp_file(sidecar))
+        .or_else(|| {
+            let data = std::fs::read(path).ok()?;
+            read_xmp(find_xmp_packet(&data)?)
+                .inspect_err(|error| {
+                    glib::warn!("Failed to read XMP from {}: {error}", path.display());
+                })
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn image_from_path(path: &Path, metadata: XmpMetadata) -> DownloadableImage {
+    let title = metadata.title.unwrap_or_else(|| {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    });
+    DownloadableImage {
+        metadata: ImageMetadata {
+            title,
+            description: metadata.description,
+            copyright: metadata.rights.or(metadata.creator),
+            url: None,
+            source: ID.to_owned(),
+        },
+        image_url: gio::File::for_path(path).uri().into(),
+        // We cycle through the images, so they don't have a date.
+        pubdate: None,
+        suggested_filename: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
+        dimensions: None,
+    }
+}
+
+fn pick_image_from_folder(
+    folder: &Path,
+    date: Date,
+    shuffled: bool,
+) -> Result<Vec<DownloadableImage>, glib::Error> {
+    let images = list_images(folder).map_err(|error| {
+        glib::Error::new(
+            IOErrorEnum::Failed,
+            &format!("Failed to list images in {}: {error}", folder.display()),
+        )
+    })?;
+    Ok(pick_image_for_date(&images, date, shuffled)
+        .map(|path| image_from_path(path, read_metadata(path)))
+        .into_iter()
+        .collect())
+}
+
+/// Images from a local folder chosen by the user.
+pub struct Local;
+
+impl SourceProvider for Local {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "Local folder")
+    }
+
+    fn url(&self) -> Option<&str> {
+        // A local folder has no homepage
+        None
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::DATE
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        _session: &'a soup::Session,
+        date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        let settings = crate::config::get_settings();
+        let folder = PathBuf::from(settings.string("local-folder").as_str());
+        let shuffled = settings.boolean("local-folder-shuffle");
+        let date = date.unwrap_or_else(crate::date::today_local);
+        async move {
+            if folder.as_os_str().is_empty() {
+                return Err(SourceError::NotConfigured);
+            }
+            glib::debug!("Picking image for {date} from {}", folder.display());
+            // Scanning the folder and reading metadata may block for a while,
+            // e.g. on network shares, so let's move it off the main loop.
+            let images =
+                gio::spawn_blocking(move || pick_image_from_folder(&folder, date, shuffled))
+                    .await
+                    .map_err(|_| {
+                        glib::Error::new(IOErrorEnum::Failed, "Picking image from folder failed")
+                    })??;
+            Ok(images)
+        }
+        .boxed_local()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use jiff::civil::date;
+
+    use super::*;
+
+    fn images() -> Vec<PathBuf> {
+        ["a.jpg", "b.png", "c.jpeg", "d.webp"]
+            .into_iter()
+            .map(|name| PathBuf::from("/wallpapers").join(name))
+            .collect()
+    }
+
+    #[test]
+    fn list_images_only_lists_image_files() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        for name in ["a.jpg", "b.JPEG", "c.txt", "d.xmp"] {
+            std::fs::write(directory.join(name), b"").unwrap();
+        }
+        std::fs::create_dir(directory.join("e.png")).unwrap();
+        let names = list_images(&directory)
+            .unwrap()
+            .into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(names, vec!["a.jpg", "b.JPEG"]);
+    }
+
+    #[test]
+    fn pick_image_for_date_in_order() {
+        let images = images();
+        let picked = (0..5)
+            .map(|day| {
+                let date = BASE_DATE + jiff::Span::new().days(day);
+                pick_image_for_date(&images, date, false)
+                    .unwrap()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(picked, vec!["a.jpg", "b.png", "c.jpeg", "d.webp", "a.jpg"]);
+    }
+
+    #[test]
+    fn pick_image_for_date_shuffled_covers_all_images_per_cycle() {
+        let images = images();
+        let mut picked = (0..4)
+            .map(|day| {
+                let date = BASE_DATE + jiff::Span::new().days(day);
+                pick_image_for_date(&images, date, true).unwrap().to_owned()
+            })
+            .collect::<Vec<_>>();
+        picked.sort();
+        assert_eq!(picked, images);
+    }
+
+    #[test]
+    fn pick_image_for_date_shuffled_is_deterministic() {
+        let images = images();
+        let date = date(2025, 10, 17);
+        assert_eq!(
+            pick_image_for_date(&images, date, true),
+            pick_image_for_date(&images, date, true)
+        );
+    }
+
+    #[test]
+    fn pick_image_for_date_empty() {
+        assert!(pick_image_for_date(&[], date(2025, 10, 17), false).is_none());
+        assert!(pick_image_for_date(&[], date(2025, 10, 17), true).is_none());
+    }
+
+    #[test]
+    fn image_from_path_without_metadata() {
+        let image = image_from_path(
+            Path::new("/wallpapers/harbour at dawn.jpg"),
+            XmpMetadata::default(),
+        );
+        assert_eq!(image.metadata.title, "harbour at dawn");
+        assert_eq!(image.metadata.source, ID);
+        assert!(image.metadata.copyright.is_none());
+        assert_eq!(
+            image.image_url,
+            "file:///wallpapers/harbour%20at%20dawn.jpg"
+        );
+        assert_eq!(
+            image.suggested_filename.as_deref(),
+            Some("harbour at dawn.jpg")
+        );
+    }
+
+    #[test]
+    fn image_from_path_with_metadata() {
+        let image = image_from_path(
+            Path::new("/wallpapers/harbour.jpg"),
+            XmpMetadata {
+                title: Some("Harbour at dawn".into()),
+                description: Some("Fishing boats".into()),
+                rights: None,
+                creator: Some("Jane Doe".into()),
+            },
+        );
+        assert_eq!(image.metadata.title, "Harbour at dawn");
+        assert_eq!(image.metadata.description.as_deref(), Some("Fishing boats"));
+        assert_eq!(image.metadata.copyright.as_deref(), Some("Jane Doe"));
+    }
+}
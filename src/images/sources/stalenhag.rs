@@ -7,12 +7,19 @@
 use std::sync::LazyLock;
 
 use formatx::formatx;
-use glib::dpgettext2;
+use glib::{GString, dpgettext2};
+use gnome_app_utils::futures::FutureExt;
+use gnome_app_utils::futures::future::{LocalBoxFuture, ready};
 use gtk::gio::{self, ResourceLookupFlags, prelude::SettingsExtManual};
 use jiff::civil::Date;
 use serde::Deserialize;
 
-use super::super::{DownloadableImage, ImageMetadata, Source};
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the Stålenhag source.
+pub const ID: &str = "stalenhag";
 
 #[derive(Debug, Deserialize)]
 pub struct Collection {
@@ -86,9 +93,8 @@ fn images(collections: impl Iterator<Item = &'static Collection>) -> Vec<ImageIn
 fn pick_image_for_date(date: Date, images: &[ImageInCollection]) -> DownloadableImage {
     // The 84th anniversary of Georg Elsner's heroic act of resistance against the nazi regime
     let base_date = jiff::civil::date(2023, 11, 8);
-    let days = (date - base_date).get_days();
-    let index = usize::try_from(days.rem_euclid(i32::try_from(images.len()).unwrap())).unwrap();
-    // The modulus above makes sure we don't index out of bounds here
+    let index = crate::date::cycle_index_for_date(base_date, date, images.len()).unwrap();
+    // cycle_index_for_date makes sure we don't index out of bounds here
     #[allow(clippy::indexing_slicing)]
     let image = &images[index];
     // The URL of the image is guaranteed to have at least one slash.
@@ -105,13 +111,14 @@ fn pick_image_for_date(date: Date, images: &[ImageInCollection]) -> Downloadable
             description: Some(description),
             copyright: Some(copyright.into()),
             url: Some(image.url.to_owned()),
-            source: Source::Stalenhag,
+            source: ID.to_owned(),
         },
         image_url: image.image.to_owned(),
         // We do not add a date to the image here, because we cycle through these
         // images and will eventually hit this image again.
         pubdate: None,
         suggested_filename: Some(format!("{}-{base_name}", image.tag)),
+        dimensions: None,
     }
 }
 
@@ -126,6 +133,39 @@ pub fn pick_image_for_date_from_configured_collections(date: Date) -> Downloadab
     pick_image_for_date_from_collections(date, enabled_collections())
 }
 
+/// Artwork by Simon Stålenhag.
+pub struct Stalenhag;
+
+impl SourceProvider for Stalenhag {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "Simon Stålenhag")
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some("https://simonstalenhag.se/")
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::DATE
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        _session: &'a soup::Session,
+        date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        let date = date.unwrap_or_else(crate::date::today_local);
+        ready(Ok(vec![pick_image_for_date_from_configured_collections(
+            date,
+        )]))
+        .boxed_local()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use jiff::civil::date;
@@ -150,7 +190,7 @@ mod tests {
             metadata.url.unwrap(),
             "https://simonstalenhag.se/svema.html"
         );
-        assert_eq!(metadata.source, Source::Stalenhag);
+        assert_eq!(metadata.source, ID);
         assert!(image.pubdate.is_none());
         assert_eq!(
             image.image_url,
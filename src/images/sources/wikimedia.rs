@@ -9,18 +9,27 @@
 //! See <https://commons.m.wikimedia.org/wiki/Commons:Picture_of_the_day>.
 
 use formatx::formatx;
-use glib::{Priority, dpgettext2};
+use glib::{GString, Priority, dpgettext2};
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
 use jiff::civil::Date;
 use serde::Deserialize;
 
 use crate::config::G_LOG_DOMAIN;
+use crate::dimensions::Dimensions;
 use crate::net::http::SoupSessionExt;
 
-use super::super::{DownloadableImage, ImageMetadata, Source, SourceError};
+use super::super::{
+    DownloadableImage, ImageMetadata, SourceCapabilities, SourceError, SourceProvider,
+};
+
+/// The identifier of the Wikimedia source.
+pub const ID: &str = "wikimedia";
 
 #[derive(Debug, Deserialize)]
 struct FeaturedImageImage {
     source: String,
+    width: Option<u32>,
+    height: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,6 +107,11 @@ impl From<FeaturedImage> for DownloadableImage {
         let title = image.pretty_title().to_owned();
         let copyright = Some(image.copyright());
         let url = Some(image.file_page);
+        let dimensions = image
+            .image
+            .width
+            .zip(image.image.height)
+            .map(|(width, height)| Dimensions { width, height });
         let image_url = image.image.source;
         let description = image.description.map(|s| s.text);
         DownloadableImage {
@@ -106,11 +120,12 @@ impl From<FeaturedImage> for DownloadableImage {
                 description,
                 copyright,
                 url,
-                source: Source::Wikimedia,
+                source: ID.to_owned(),
             },
             image_url,
             pubdate: None,
             suggested_filename: None,
+            dimensions,
         }
     }
 }
@@ -172,6 +187,36 @@ pub async fn fetch_featured_image(
     fetch_featured_image_at_date(session, date, language_code).await
 }
 
+/// Wikimedia Picture of the Day.
+pub struct Wikimedia;
+
+impl SourceProvider for Wikimedia {
+    fn id(&self) -> &str {
+        ID
+    }
+
+    fn i18n_name(&self) -> GString {
+        dpgettext2(None, "source name", "Wikimedia Picture of the Day")
+    }
+
+    fn url(&self) -> Option<&str> {
+        Some("https://commons.wikimedia.org/wiki/Main_Page")
+    }
+
+    fn capabilities(&self) -> SourceCapabilities {
+        SourceCapabilities::DATE | SourceCapabilities::FREE_LICENSE
+    }
+
+    fn fetch_images<'a>(
+        &'a self,
+        session: &'a soup::Session,
+        date: Option<Date>,
+    ) -> LocalBoxFuture<'a, Result<Vec<DownloadableImage>, SourceError>> {
+        let date = date.unwrap_or_else(crate::date::today_local);
+        async move { Ok(vec![fetch_featured_image(session, date).await?]) }.boxed_local()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use glib::async_test;
@@ -243,7 +288,7 @@ Public domain)"
             image.metadata.url.unwrap(),
             "https://commons.wikimedia.org/wiki/File:George_Sand_by_Nadar,_1864.jpg"
         );
-        assert_eq!(image.metadata.source, Source::Wikimedia);
+        assert_eq!(image.metadata.source, ID);
 
         assert_eq!(
             image.image_url,
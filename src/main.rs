@@ -47,14 +47,25 @@ use gnome_app_utils::i18n::gettext;
 use gtk::prelude::*;
 
 mod app;
+mod caption;
 mod config;
+mod dark;
 mod date;
+mod dimensions;
+mod fit;
+mod history;
 mod images;
+mod monitors;
 mod net;
+mod retention;
+mod rotation;
+mod schedule;
+mod slideshow;
+mod target;
+mod wallpaper;
 mod xml;
 
 use config::G_LOG_DOMAIN;
-use images::Source;
 
 fn main() -> glib::ExitCode {
     gnome_app_utils::log::log_to_glib();
@@ -0,0 +1,346 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Wallpapers across multiple monitors.
+//!
+//! Desktops show the same wallpaper on every monitor by default.  Instead we
+//! can compose a single wallpaper which spans all monitors, either from
+//! different images per monitor, or from one wide image.  The desktop then
+//! needs to span this wallpaper across all monitors, e.g. with the "spanned"
+//! picture option of GNOME.
+
+use std::path::{Path, PathBuf};
+
+use gtk::gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::fit::{Size, image_info, write_processed_image};
+
+/// How to show wallpapers on multiple monitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiMonitorMode {
+    /// Show the same image on every monitor.
+    #[default]
+    Same,
+    /// Show a different image on every monitor.
+    PerMonitor,
+    /// Span one image across all monitors.
+    Span,
+}
+
+impl MultiMonitorMode {
+    fn from_id(id: &str) -> Self {
+        match id {
+            "per-monitor" => Self::PerMonitor,
+            "span" => Self::Span,
+            _ => Self::Same,
+        }
+    }
+
+    /// Read the multi-monitor mode from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self::from_id(&settings.string("multi-monitor"))
+    }
+}
+
+/// The position and size of a monitor in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitorGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub size: Size,
+}
+
+/// The monitors to lay out wallpapers for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorLayout {
+    /// How to show wallpapers on multiple monitors.
+    pub mode: MultiMonitorMode,
+    /// All monitors, with the primary monitor first.
+    ///
+    /// The top left corner of the area spanning all monitors is at the origin.
+    monitors: Vec<MonitorGeometry>,
+}
+
+impl MonitorLayout {
+    /// Lay out wallpapers across `monitors` with `mode`.
+    ///
+    /// Move `monitors` so that the area spanning all monitors starts at the
+    /// origin, for monitor layouts with negative coordinates.
+    pub fn new(mode: MultiMonitorMode, monitors: Vec<MonitorGeometry>) -> Self {
+        let min_x = monitors.iter().map(|m| m.x).min().unwrap_or_default();
+        let min_y = monitors.iter().map(|m| m.y).min().unwrap_or_default();
+        let monitors = monitors
+            .into_iter()
+            .map(|monitor| MonitorGeometry {
+                x: monitor.x - min_x,
+                y: monitor.y - min_y,
+                ..monitor
+            })
+            .collect();
+        Self { mode, monitors }
+    }
+
+    /// All monitors, with the primary monitor first.
+    pub fn monitors(&self) -> &[MonitorGeometry] {
+        &self.monitors
+    }
+
+    /// The size of the primary monitor, if any.
+    pub fn primary_size(&self) -> Option<Size> {
+        self.monitors.first().map(|monitor| monitor.size)
+    }
+
+    /// Whether the wallpaper spans multiple monitors.
+    ///
+    /// With a single monitor we always use a plain wallpaper.
+    pub fn is_spanned(&self) -> bool {
+        self.mode != MultiMonitorMode::Same && 1 < self.monitors.len()
+    }
+
+    /// Whether we show a different image on every monitor.
+    pub fn has_image_per_monitor(&self) -> bool {
+        self.is_spanned() && self.mode == MultiMonitorMode::PerMonitor
+    }
+
+    /// The size of the area which spans all monitors.
+    pub fn spanning_size(&self) -> Option<Size> {
+        let width = self.monitors.iter().map(|m| m.x + m.size.width).max()?;
+        let height = self.monitors.iter().map(|m| m.y + m.size.height).max()?;
+        Some(Size { width, height })
+    }
+
+    /// A suffix for files composed for this layout.
+    fn file_suffix(&self) -> String {
+        let monitors = self
+            .monitors
+            .iter()
+            .map(|m| format!("{}x{}+{}+{}", m.size.width, m.size.height, m.x, m.y))
+            .collect::<Vec<_>>();
+        monitors.join("_")
+    }
+}
+
+/// Compose `tiles` into a single image spanning all monitors of `layout`.
+///
+/// Put each tile at the position of the corresponding monitor, scaled to the
+/// size of the monitor; leave areas outside of all monitors black.
+fn compose_tiles(tiles: &[Pixbuf], layout: &MonitorLayout) -> Result<Pixbuf, glib::Error> {
+    let size = layout.spanning_size().ok_or_else(|| {
+        glib::Error::new(IOErrorEnum::InvalidArgument, "No monitors to compose for")
+    })?;
+    let canvas =
+        Pixbuf::new(Colorspace::Rgb, false, 8, size.width, size.height).ok_or_else(|| {
+            glib::Error::new(IOErrorEnum::Failed, "Failed to allocate spanning wallpaper")
+        })?;
+    canvas.fill(0x0000_00ff);
+    for (tile, monitor) in tiles.iter().zip(layout.monitors()) {
+        let Size { width, height } = monitor.size;
+        let scaled;
+        let tile = if (tile.width(), tile.height()) == (width, height) {
+            tile
+        } else {
+            scaled = tile
+                .scale_simple(width, height, InterpType::Bilinear)
+                .ok_or_else(|| {
+                    glib::Error::new(IOErrorEnum::Failed, "Failed to scale image for monitor")
+                })?;
+            &scaled
+        };
+        tile.copy_area(0, 0, width, height, &canvas, monitor.x, monitor.y);
+    }
+    Ok(canvas)
+}
+
+fn compose_wallpaper_blocking(
+    tiles: &[PathBuf],
+    layout: &MonitorLayout,
+    directory: &Path,
+) -> Result<PathBuf, glib::Error> {
+    let first = tiles
+        .first()
+        .ok_or_else(|| glib::Error::new(IOErrorEnum::InvalidArgument, "No images to compose"))?;
+    let (format, _) = image_info(first)?;
+    // Name the composed wallpaper after all tiles, to avoid reusing a
+    // composed wallpaper with different images on other monitors.
+    let names = tiles
+        .iter()
+        .map(|tile| tile.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let checksum = glib::compute_checksum_for_string(glib::ChecksumType::Sha256, &names)
+        .map_or_else(String::new, |checksum| checksum.chars().take(12).collect());
+    let suffix = format!("span.{}.{checksum}", layout.file_suffix());
+    write_processed_image(first, &format, directory, &suffix, || {
+        let tiles = tiles
+            .iter()
+            .map(Pixbuf::from_file)
+            .collect::<Result<Vec<_>, _>>()?;
+        compose_tiles(&tiles, layout)
+    })
+}
+
+/// Compose a wallpaper spanning all monitors of `layout` from `tiles`.
+///
+/// `tiles` holds one image per monitor, in the order of monitors of
+/// `layout`.  Write the composed wallpaper to `directory` and return its path.
+/// Reuse composed wallpapers from earlier calls.
+///
+/// ## Errors
+///
+/// Return an error if `tiles` is empty, or if we failed to load, compose or
+/// save images.
+pub async fn compose_wallpaper(
+    tiles: &[PathBuf],
+    layout: &MonitorLayout,
+    directory: &Path,
+) -> Result<PathBuf, glib::Error> {
+    let tiles = tiles.to_vec();
+    let layout = layout.clone();
+    let directory = directory.to_owned();
+    gio::spawn_blocking(move || compose_wallpaper_blocking(&tiles, &layout, &directory))
+        .await
+        .unwrap_or_else(|_| {
+            Err(glib::Error::new(
+                IOErrorEnum::Failed,
+                "Composing wallpaper panicked",
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identifiers of all multi-monitor modes, as stored in settings.
+    const MULTI_MONITOR_MODES: [&str; 3] = ["same", "per-monitor", "span"];
+
+    fn monitor(x: i32, y: i32, width: i32, height: i32) -> MonitorGeometry {
+        MonitorGeometry {
+            x,
+            y,
+            size: Size { width, height },
+        }
+    }
+
+    fn pixel(pixbuf: &Pixbuf, x: i32, y: i32) -> [u8; 3] {
+        let pixels = pixbuf.read_pixel_bytes();
+        let offset = usize::try_from(y * pixbuf.rowstride() + x * pixbuf.n_channels()).unwrap();
+        pixels.get(offset..offset + 3).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn mode_from_id() {
+        assert_eq!(
+            MULTI_MONITOR_MODES.map(MultiMonitorMode::from_id),
+            [
+                MultiMonitorMode::Same,
+                MultiMonitorMode::PerMonitor,
+                MultiMonitorMode::Span
+            ]
+        );
+        assert_eq!(MultiMonitorMode::from_id("foo"), MultiMonitorMode::Same);
+    }
+
+    #[test]
+    fn single_monitor_is_never_spanned() {
+        let monitors = vec![monitor(0, 0, 3840, 2160)];
+        for mode in MULTI_MONITOR_MODES.map(MultiMonitorMode::from_id) {
+            assert!(!MonitorLayout::new(mode, monitors.clone()).is_spanned());
+        }
+        assert!(!MonitorLayout::new(MultiMonitorMode::Span, Vec::new()).is_spanned());
+    }
+
+    #[test]
+    fn image_per_monitor() {
+        let monitors = vec![monitor(0, 0, 1920, 1080), monitor(1920, 0, 1920, 1080)];
+        let layouts = MULTI_MONITOR_MODES
+            .map(MultiMonitorMode::from_id)
+            .map(|mode| MonitorLayout::new(mode, monitors.clone()));
+        assert_eq!(
+            layouts.each_ref().map(MonitorLayout::is_spanned),
+            [false, true, true]
+        );
+        assert_eq!(
+            layouts.each_ref().map(MonitorLayout::has_image_per_monitor),
+            [false, true, false]
+        );
+    }
+
+    #[test]
+    fn spanning_size_of_monitors_side_by_side() {
+        let layout = MonitorLayout::new(
+            MultiMonitorMode::Span,
+            vec![
+                monitor(1920, 0, 2560, 1440),
+                monitor(0, 180, 1920, 1080),
+                monitor(4480, 0, 1920, 1080),
+            ],
+        );
+        assert!(layout.is_spanned());
+        assert_eq!(
+            layout.spanning_size(),
+            Some(Size {
+                width: 6400,
+                height: 1440
+            })
+        );
+        assert_eq!(
+            layout.primary_size(),
+            Some(Size {
+                width: 2560,
+                height: 1440
+            })
+        );
+    }
+
+    #[test]
+    fn monitors_move_to_origin() {
+        let layout = MonitorLayout::new(
+            MultiMonitorMode::PerMonitor,
+            vec![monitor(0, 0, 1920, 1080), monitor(-1080, -420, 1080, 1920)],
+        );
+        assert_eq!(
+            layout.monitors(),
+            [monitor(1080, 420, 1920, 1080), monitor(0, 0, 1080, 1920)]
+        );
+        assert_eq!(
+            layout.spanning_size(),
+            Some(Size {
+                width: 3000,
+                height: 1920
+            })
+        );
+        assert_eq!(layout.file_suffix(), "1920x1080+1080+420_1080x1920+0+0");
+    }
+
+    #[test]
+    fn compose_tiles_at_monitor_positions() {
+        let layout = MonitorLayout::new(
+            MultiMonitorMode::PerMonitor,
+            vec![monitor(0, 2, 4, 2), monitor(4, 0, 2, 4)],
+        );
+        let red = Pixbuf::new(Colorspace::Rgb, false, 8, 8, 4).unwrap();
+        red.fill(0xff00_00ff);
+        let green = Pixbuf::new(Colorspace::Rgb, true, 8, 2, 4).unwrap();
+        green.fill(0x00ff_00ff);
+        let composed = compose_tiles(&[red, green], &layout).unwrap();
+        assert_eq!((composed.width(), composed.height()), (6, 4));
+        assert_eq!(pixel(&composed, 0, 0), [0, 0, 0]);
+        assert_eq!(pixel(&composed, 3, 1), [0, 0, 0]);
+        assert_eq!(pixel(&composed, 0, 2), [0xff, 0, 0]);
+        assert_eq!(pixel(&composed, 3, 3), [0xff, 0, 0]);
+        assert_eq!(pixel(&composed, 4, 0), [0, 0xff, 0]);
+        assert_eq!(pixel(&composed, 5, 3), [0, 0xff, 0]);
+    }
+
+    #[test]
+    fn compose_without_tiles() {
+        let layout = MonitorLayout::new(MultiMonitorMode::PerMonitor, Vec::new());
+        let error = compose_tiles(&[], &layout).unwrap_err();
+        assert!(error.matches(IOErrorEnum::InvalidArgument));
+    }
+}
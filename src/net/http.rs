@@ -4,23 +4,82 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
+use std::time::Duration;
+
 use glib::{Bytes, GString};
+use gtk::gio::{self, IOErrorEnum};
 use serde::de::DeserializeOwned;
 use soup::prelude::SessionExt;
 
+use crate::config::G_LOG_DOMAIN;
+
+pub mod cache;
 pub mod download;
 
+use cache::{CachedResponse, HttpCache, Validators};
+
 /// An error during a HTTP request.
 #[derive(Debug)]
 pub enum HttpError {
     /// An IO error.
     IO(glib::Error),
-    /// An unexpected HTTP status.
-    HttpStatus(soup::Status, Option<GString>, Bytes),
+    /// An unexpected HTTP status, with the reason, the delay from the
+    /// `Retry-After` header, and the body.
+    HttpStatus(soup::Status, Option<GString>, Option<Duration>, Bytes),
     /// An invalid JSON body.
     InvalidJson(serde_json::Error),
 }
 
+impl HttpError {
+    /// An error for the unexpected status of `message`, with the response `body`.
+    pub fn unexpected_status(message: &soup::Message, body: Bytes) -> Self {
+        let retry_after = message
+            .response_headers()
+            .and_then(|headers| headers.one("Retry-After"))
+            .and_then(|value| parse_retry_after(&value, &glib::DateTime::now_utc().ok()?));
+        Self::HttpStatus(message.status(), message.reason_phrase(), retry_after, body)
+    }
+}
+
+/// Parse the value of a `Retry-After` header relative to `now`.
+///
+/// The header contains either a number of seconds, or a HTTP date.  Return
+/// `None` if the value is invalid.
+fn parse_retry_after(value: &str, now: &glib::DateTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = soup::date_time_new_from_http_string(value)?;
+    let microseconds = u64::try_from(date.difference(now).as_microseconds()).unwrap_or(0);
+    Some(Duration::from_micros(microseconds))
+}
+
+/// Whether `error` is a transient network error, e.g. while offline.
+///
+/// After a transient error a stale cached response is better than nothing;
+/// after any other error, e.g. an invalid TLS certificate or a cancelled
+/// request, we must not pretend that the request succeeded.
+fn is_transient_network_error(error: &glib::Error) -> bool {
+    error.kind::<gio::ResolverError>().is_some_and(|kind| {
+        matches!(
+            kind,
+            gio::ResolverError::NotFound | gio::ResolverError::TemporaryFailure
+        )
+    }) || error.kind::<IOErrorEnum>().is_some_and(|kind| {
+        matches!(
+            kind,
+            IOErrorEnum::TimedOut
+                | IOErrorEnum::HostNotFound
+                | IOErrorEnum::HostUnreachable
+                | IOErrorEnum::NetworkUnreachable
+                | IOErrorEnum::ConnectionRefused
+                | IOErrorEnum::ConnectionClosed
+                | IOErrorEnum::NotConnected
+        )
+    })
+}
+
 impl From<glib::Error> for HttpError {
     fn from(error: glib::Error) -> Self {
         Self::IO(error)
@@ -34,6 +93,25 @@ impl From<serde_json::Error> for HttpError {
 }
 
 pub trait SoupSessionExt {
+    /// Send a `message` with `priority` and read the response body through the cache.
+    ///
+    /// Revalidate a cached response for the URL of `message`, and return the
+    /// cached body if the server says it did not change.  Return the cached
+    /// body as well if the request fails with a transient network error, e.g.
+    /// while offline.
+    ///
+    /// ## Errors
+    ///
+    /// Return [`HttpError::HttpStatus`] if the request returns a status other
+    /// than [`soup::Status::Ok`] or [`soup::Status::NotModified`], or
+    /// [`HttpError::IO`] if the request failed and we have no cached response
+    /// or the error was not transient.
+    async fn send_and_read_cached(
+        &self,
+        message: &soup::Message,
+        priority: glib::Priority,
+    ) -> Result<Bytes, HttpError>;
+
     /// Send a `message` with `priority` and read a JSON response.
     ///
     /// ## Errors
@@ -50,20 +128,126 @@ pub trait SoupSessionExt {
 }
 
 impl SoupSessionExt for soup::Session {
+    async fn send_and_read_cached(
+        &self,
+        message: &soup::Message,
+        priority: glib::Priority,
+    ) -> Result<Bytes, HttpError> {
+        let Some(url) = message.uri().map(|uri| uri.to_str()) else {
+            let body = self.send_and_read_future(message, priority).await?;
+            return if message.status() == soup::Status::Ok {
+                Ok(body)
+            } else {
+                Err(HttpError::unexpected_status(message, body))
+            };
+        };
+        let cache = HttpCache::default();
+        let cached = cache.load(&url).await;
+        if let Some(cached) = &cached
+            && let Some(headers) = message.request_headers()
+        {
+            cached.validators.add_conditions(&headers);
+        }
+        let body = match self.send_and_read_future(message, priority).await {
+            Ok(body) => body,
+            Err(error) => {
+                return if let Some(cached) = cached
+                    && is_transient_network_error(&error)
+                {
+                    glib::warn!("Request to {url} failed, using cached response: {error}");
+                    Ok(cached.body)
+                } else {
+                    glib::warn!("Request to {url} failed: {error}");
+                    Err(error.into())
+                };
+            }
+        };
+        match (message.status(), cached) {
+            (soup::Status::NotModified, Some(cached)) => {
+                glib::debug!("{url} not modified, using cached response");
+                Ok(cached.body)
+            }
+            (soup::Status::Ok, _) => {
+                let response = CachedResponse {
+                    validators: message
+                        .response_headers()
+                        .map(|headers| Validators::from_headers(&headers))
+                        .unwrap_or_default(),
+                    body,
+                };
+                if let Err(error) = cache.store(&url, &response).await {
+                    glib::warn!("Failed to cache response of {url}: {error}");
+                }
+                Ok(response.body)
+            }
+            _ => Err(HttpError::unexpected_status(message, body)),
+        }
+    }
+
     async fn send_and_read_json<T: DeserializeOwned>(
         &self,
         message: &soup::Message,
         priority: glib::Priority,
     ) -> Result<T, HttpError> {
-        let body = self.send_and_read_future(message, priority).await?;
-        if message.status() == soup::Status::Ok {
-            Ok(serde_json::from_slice(&body)?)
-        } else {
-            Err(HttpError::HttpStatus(
-                message.status(),
-                message.reason_phrase(),
-                body,
-            ))
-        }
+        let body = self.send_and_read_cached(message, priority).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use gtk::gio::{self, IOErrorEnum};
+
+    use super::{is_transient_network_error, parse_retry_after};
+
+    #[test]
+    fn transient_network_errors() {
+        assert!(is_transient_network_error(&glib::Error::new(
+            IOErrorEnum::NetworkUnreachable,
+            "offline"
+        )));
+        assert!(is_transient_network_error(&glib::Error::new(
+            gio::ResolverError::TemporaryFailure,
+            "no DNS"
+        )));
+        assert!(!is_transient_network_error(&glib::Error::new(
+            IOErrorEnum::Cancelled,
+            "cancelled"
+        )));
+        assert!(!is_transient_network_error(&glib::Error::new(
+            gio::TlsError::BadCertificate,
+            "bad certificate"
+        )));
+    }
+
+    #[test]
+    fn parse_retry_after_seconds() {
+        let now = glib::DateTime::now_utc().unwrap();
+        assert_eq!(
+            parse_retry_after(" 120 ", &now),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn parse_retry_after_http_date() {
+        let now = glib::DateTime::from_utc(2015, 10, 21, 7, 27, 0.0).unwrap();
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", &now),
+            Some(Duration::from_secs(60))
+        );
+        // Dates in the past mean that we may retry right away
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:20:00 GMT", &now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn parse_retry_after_invalid() {
+        let now = glib::DateTime::now_utc().unwrap();
+        assert_eq!(parse_retry_after("soon", &now), None);
     }
 }
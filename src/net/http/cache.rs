@@ -0,0 +1,339 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! A persistent cache for HTTP responses.
+//!
+//! We cache responses of metadata requests to sources, and revalidate cached
+//! responses with `ETag` and `Last-Modified`, to make repeated requests to
+//! unchanged sources cheap, and to work from cache while offline.
+//!
+//! We never write secrets in URLs to disk, and evict entries which we did not
+//! update for a while, e.g. responses for past APOD dates.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use glib::Bytes;
+use gnome_app_utils::io::ensure_directory_with_parents;
+use gtk::gio::{self, FileCreateFlags, prelude::*};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::config::G_LOG_DOMAIN;
+
+/// Query parameters which hold secrets, e.g. the API key of APOD.
+const SECRET_QUERY_PARAMETERS: [&str; 1] = ["api_key"];
+
+/// The maximum age of a cache entry.
+///
+/// We refresh an entry whenever the server returns a new response, so this
+/// mostly evicts responses we no longer request at all.  An entry which the
+/// server keeps revalidating gets evicted too, which just costs one full
+/// response.
+const MAX_CACHE_ENTRY_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Validators of a cached response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The `ETag` of the response.
+    pub etag: Option<String>,
+    /// The `Last-Modified` date of the response.
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Get validators from response `headers`.
+    pub fn from_headers(headers: &soup::MessageHeaders) -> Self {
+        Self {
+            etag: headers.one("ETag").map(Into::into),
+            last_modified: headers.one("Last-Modified").map(Into::into),
+        }
+    }
+
+    /// Make a request conditional on these validators.
+    ///
+    /// Add `If-None-Match` and `If-Modified-Since` to request `headers`, so
+    /// that the server returns [`soup::Status::NotModified`] if the response
+    /// did not change.
+    pub fn add_conditions(&self, headers: &soup::MessageHeaders) {
+        if let Some(etag) = &self.etag {
+            headers.replace("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.replace("If-Modified-Since", last_modified);
+        }
+    }
+}
+
+/// The index of a cached response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheIndex {
+    /// The URL of the response, without secrets.
+    ///
+    /// We use a hash of the URL as file name, so we store the URL itself to
+    /// protect against collisions.
+    url: String,
+    /// Validators of the response.
+    #[serde(flatten)]
+    validators: Validators,
+}
+
+/// A cached response.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// Validators of the response.
+    pub validators: Validators,
+    /// The body of the response.
+    pub body: Bytes,
+}
+
+/// The URL to cache the response of `url` under.
+///
+/// Remove [`SECRET_QUERY_PARAMETERS`] from the query of `url`.
+fn cache_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_owned();
+    };
+    let is_secret = |name: &str| SECRET_QUERY_PARAMETERS.contains(&name);
+    if !parsed.query_pairs().any(|(name, _)| is_secret(&name)) {
+        return url.to_owned();
+    }
+    let pairs = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_secret(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    if pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.into()
+}
+
+/// The file name of cache entries for `url`, without extension.
+fn cache_key(url: &str) -> String {
+    // SHA256 is always supported, so this won't panic.
+    glib::compute_checksum_for_string(glib::ChecksumType::Sha256, url)
+        .unwrap()
+        .into()
+}
+
+/// A persistent cache for HTTP responses in a directory.
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    directory: PathBuf,
+}
+
+impl Default for HttpCache {
+    /// The cache in [`crate::config::http_cache_directory`].
+    fn default() -> Self {
+        Self::new(crate::config::http_cache_directory())
+    }
+}
+
+impl HttpCache {
+    /// Create a cache storing responses in `directory`.
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    fn files(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = cache_key(url);
+        (
+            self.directory.join(format!("{key}.json")),
+            self.directory.join(format!("{key}.body")),
+        )
+    }
+
+    /// Load the cached response for `url`.
+    ///
+    /// Return `None` if there is no cached response for `url`, or if the
+    /// cache entry is invalid.
+    pub async fn load(&self, url: &str) -> Option<CachedResponse> {
+        let url = cache_url(url);
+        let (index_file, body_file) = self.files(&url);
+        let (index, _) = gio::File::for_path(&index_file)
+            .load_contents_future()
+            .await
+            .ok()?;
+        let index = match serde_json::from_slice::<CacheIndex>(&index) {
+            Ok(index) if index.url == url => index,
+            Ok(_) => return None,
+            Err(error) => {
+                glib::warn!("Invalid cache entry {}: {error}", index_file.display());
+                return None;
+            }
+        };
+        let (body, _) = gio::File::for_path(&body_file)
+            .load_contents_future()
+            .await
+            .ok()?;
+        Some(CachedResponse {
+            validators: index.validators,
+            body: Bytes::from_owned(body),
+        })
+    }
+
+    /// Store `response` for `url` in the cache.
+    ///
+    /// Evict old entries afterwards, see [`evict_old_entries`].
+    ///
+    /// # Errors
+    ///
+    /// Return an error if writing cache files failed.
+    pub async fn store(&self, url: &str, response: &CachedResponse) -> Result<(), glib::Error> {
+        ensure_directory_with_parents(&self.directory).await?;
+        let url = cache_url(url);
+        let (index_file, body_file) = self.files(&url);
+        let index = CacheIndex {
+            url,
+            validators: response.validators.clone(),
+        };
+        // Write the body first, so that we never have an index without body
+        replace_contents(&body_file, response.body.to_vec()).await?;
+        // Serializing the index never fails, so this won't panic.
+        replace_contents(&index_file, serde_json::to_vec(&index).unwrap()).await?;
+        let directory = self.directory.clone();
+        if gio::spawn_blocking(move || evict_old_entries(&directory))
+            .await
+            .is_err()
+        {
+            glib::warn!("Evicting old cache entries panicked");
+        }
+        Ok(())
+    }
+}
+
+/// Delete cache files in `directory` older than [`MAX_CACHE_ENTRY_AGE`].
+fn evict_old_entries(directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.filter_map(Result::ok) {
+        let is_old = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| MAX_CACHE_ENTRY_AGE < age);
+        if is_old && let Err(error) = std::fs::remove_file(entry.path()) {
+            glib::warn!("Failed to evict {}: {error}", entry.path().display());
+        }
+    }
+}
+
+async fn replace_contents(path: &Path, data: Vec<u8>) -> Result<(), glib::Error> {
+    gio::File::for_path(path)
+        .replace_contents_future(
+            data,
+            None,
+            false,
+            FileCreateFlags::PRIVATE | FileCreateFlags::REPLACE_DESTINATION,
+        )
+        .await
+        .map_err(|(_, error)| error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_depends_on_url() {
+        let key = cache_key("https://example.com/feed.xml");
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key("https://example.com/feed.xml"));
+        assert_ne!(key, cache_key("https://example.com/feed.xml?page=2"));
+    }
+
+    #[test]
+    fn cache_url_strips_secrets() {
+        assert_eq!(
+            cache_url("https://api.nasa.gov/planetary/apod?api_key=secret&date=2025-01-01"),
+            "https://api.nasa.gov/planetary/apod?date=2025-01-01"
+        );
+        assert_eq!(
+            cache_url("https://api.nasa.gov/planetary/apod?api_key=secret"),
+            "https://api.nasa.gov/planetary/apod"
+        );
+        assert_eq!(
+            cache_url("https://example.com/feed.xml?page=2"),
+            "https://example.com/feed.xml?page=2"
+        );
+    }
+
+    #[glib::async_test]
+    async fn store_keeps_secrets_off_disk() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let cache = HttpCache::new(directory.clone());
+        let response = CachedResponse {
+            validators: Validators::default(),
+            body: Bytes::from_static(b"{}"),
+        };
+        cache
+            .store(
+                "https://api.nasa.gov/planetary/apod?api_key=secret",
+                &response,
+            )
+            .await
+            .unwrap();
+        let cached = cache
+            .load("https://api.nasa.gov/planetary/apod?api_key=other")
+            .await;
+        let leaked = std::fs::read_dir(&directory).unwrap().any(|entry| {
+            let entry = entry.unwrap();
+            entry.file_name().to_string_lossy().contains("secret")
+                || std::fs::read_to_string(entry.path())
+                    .unwrap()
+                    .contains("secret")
+        });
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(cached.unwrap().body, response.body);
+        assert!(!leaked);
+    }
+
+    #[test]
+    fn evict_old_entries_deletes_old_files() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let old = SystemTime::now() - MAX_CACHE_ENTRY_AGE - Duration::from_secs(60);
+        for name in ["old.json", "old.body", "new.json", "new.body"] {
+            std::fs::write(directory.join(name), b"{}").unwrap();
+        }
+        for name in ["old.json", "old.body"] {
+            std::fs::File::options()
+                .write(true)
+                .open(directory.join(name))
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+        evict_old_entries(&directory);
+        let exists = ["old.json", "old.body", "new.json", "new.body"]
+            .map(|name| directory.join(name).exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(exists, [false, false, true, true]);
+    }
+
+    #[test]
+    fn cache_index_json() {
+        let index = CacheIndex {
+            url: "https://example.com/feed.xml".into(),
+            validators: Validators {
+                etag: Some("\"abc\"".into()),
+                last_modified: None,
+            },
+        };
+        let json = serde_json::to_string(&index).unwrap();
+        assert_eq!(
+            json,
+            r#"{"url":"https://example.com/feed.xml","etag":"\"abc\"","last_modified":null}"#
+        );
+        assert_eq!(serde_json::from_str::<CacheIndex>(&json).unwrap(), index);
+    }
+}
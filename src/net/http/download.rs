@@ -4,32 +4,61 @@
 //
 // See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
 
-use std::{fmt::Display, path::Path};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 use glib::Priority;
 use glib::translate::IntoGlib;
 use gnome_app_utils::io::delete_file_ignore_error;
-use gtk::gio::{self, FileCopyFlags, IOErrorEnum, prelude::*};
+use gtk::gdk_pixbuf::{Pixbuf, PixbufError};
+use gtk::gio::{self, FileCopyFlags, FileCreateFlags, IOErrorEnum, prelude::*};
 use soup::prelude::SessionExt;
 
 use crate::config::G_LOG_DOMAIN;
 
-/// An error occurred while downloading.
+/// Why a download is not a valid image.
 #[derive(Debug, Clone)]
-pub enum DownloadError {
-    Glib(glib::Error),
-    SoupStatus(soup::Status),
+pub enum InvalidImage {
+    /// The server returned a content type other than an image.
+    ///
+    /// This usually means that some CDN returned an HTML error page.
+    ContentType(String),
+    /// The server sent less data than it announced.
+    Truncated { expected: u64, actual: u64 },
+    /// The data does not start with the signature of a known image format.
+    UnknownFormat,
+    /// The data looks like an image, but does not decode.
+    Undecodable(glib::Error),
 }
 
-impl DownloadError {
-    pub fn matches<T: ErrorDomain>(&self, domain: T) -> bool {
+impl Display for InvalidImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            DownloadError::Glib(error) => error.matches(domain),
-            DownloadError::SoupStatus(_) => false,
+            InvalidImage::ContentType(content_type) => {
+                write!(f, "Unexpected content type: {content_type}")
+            }
+            InvalidImage::Truncated { expected, actual } => {
+                write!(f, "Expected {expected} bytes but got only {actual} bytes")
+            }
+            InvalidImage::UnknownFormat => write!(f, "Unknown image format"),
+            InvalidImage::Undecodable(error) => write!(f, "Invalid image: {error}"),
         }
     }
 }
 
+/// An error occurred while downloading.
+#[derive(Debug, Clone)]
+pub enum DownloadError {
+    Glib(glib::Error),
+    SoupStatus(soup::Status),
+    /// The download succeeded, but did not contain a valid image.
+    InvalidImage(InvalidImage),
+}
+
 impl Display for DownloadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -37,6 +66,7 @@ impl Display for DownloadError {
             DownloadError::SoupStatus(status) => {
                 write!(f, "Unexpected status: {}", status.into_glib())
             }
+            DownloadError::InvalidImage(invalid) => write!(f, "{invalid}"),
         }
     }
 }
@@ -44,8 +74,9 @@ impl Display for DownloadError {
 impl std::error::Error for DownloadError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            DownloadError::Glib(error) => Some(error),
-            DownloadError::SoupStatus(_) => None,
+            DownloadError::Glib(error)
+            | DownloadError::InvalidImage(InvalidImage::Undecodable(error)) => Some(error),
+            DownloadError::SoupStatus(_) | DownloadError::InvalidImage(_) => None,
         }
     }
 }
@@ -56,86 +87,354 @@ impl From<glib::Error> for DownloadError {
     }
 }
 
-/// A temporary target file for a download.
+impl From<InvalidImage> for DownloadError {
+    fn from(invalid: InvalidImage) -> Self {
+        Self::InvalidImage(invalid)
+    }
+}
+
+/// Check whether a response with `content_type` can contain an image.
+///
+/// Accept a missing content type and generic binary data, because many
+/// servers do not bother to tell the proper image type.
+fn check_content_type(content_type: Option<&str>) -> Result<(), InvalidImage> {
+    let Some(content_type) = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(());
+    };
+    if content_type.starts_with("image/")
+        || content_type == "application/octet-stream"
+        || content_type == "binary/octet-stream"
+    {
+        Ok(())
+    } else {
+        Err(InvalidImage::ContentType(content_type))
+    }
+}
+
+/// How many bytes we need to recognize the format of an image.
+const SIGNATURE_LENGTH: usize = 16;
+
+/// Recognize the format of an image from the first bytes of its `data`.
+///
+/// Return `None` if `data` does not start with the signature of any image
+/// format we know of.
+fn image_format(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP".as_slice()) {
+        Some("webp")
+    } else if data.get(4..8) == Some(b"ftyp".as_slice()) {
+        match data.get(8..12)? {
+            b"avif" | b"avis" => Some("avif"),
+            b"heic" | b"heix" | b"mif1" => Some("heif"),
+            _ => None,
+        }
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        Some("tiff")
+    } else if data.starts_with(&[0xFF, 0x0A]) || data.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n") {
+        Some("jxl")
+    } else {
+        None
+    }
+}
+
+/// The size to decode images at to verify them.
+///
+/// Small, to keep verification cheap, but loaders still decode all image data.
+const VERIFY_SIZE: i32 = 64;
+
+/// Verify that `file` contains an image.
+///
+/// Check the signature of `file`, and if gdk-pixbuf has a loader for the
+/// format of `file`, decode the image at a small size, to check that the
+/// image data is intact.
+///
+/// Note that some loaders decode truncated images without error, and just
+/// leave the missing part blank, so this may not catch truncated files.  We
+/// only detect truncated downloads when the server tells us the size of the
+/// image, see [`transfer_file`].
+///
+/// ## Errors
+///
+/// Return [`DownloadError::InvalidImage`] if `file` does not contain a valid
+/// image, or [`DownloadError::Glib`] if reading `file` failed.
+pub async fn verify_image_file(file: &gio::File) -> Result<(), DownloadError> {
+    let signature = file
+        .read_future(Priority::DEFAULT)
+        .await?
+        .read_bytes_future(SIGNATURE_LENGTH, Priority::DEFAULT)
+        .await?;
+    let format = image_format(&signature).ok_or(InvalidImage::UnknownFormat)?;
+    let stream = file.read_future(Priority::DEFAULT).await?;
+    match Pixbuf::from_stream_at_scale_future(&stream, VERIFY_SIZE, VERIFY_SIZE, true).await {
+        Ok(_) => Ok(()),
+        Err(error) if error.matches(PixbufError::UnknownType) => {
+            glib::debug!("No loader for {format}, not decoding {}", file.uri());
+            Ok(())
+        }
+        Err(error) => Err(InvalidImage::Undecodable(error).into()),
+    }
+}
+
+/// Progress of a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// How many bytes we have downloaded so far.
+    pub transferred: u64,
+    /// The total size of the download in bytes, if known.
+    pub total: Option<u64>,
+}
+
+impl DownloadProgress {
+    /// The fraction of the download we have transferred so far.
+    ///
+    /// Return `None` if the total size of the download is unknown.
+    pub fn fraction(self) -> Option<f64> {
+        let total = self.total.filter(|total| 0 < *total)?;
+        // Compute permille in integers to avoid lossy float conversions
+        let permille =
+            u32::try_from((self.transferred.saturating_mul(1000) / total).min(1000)).ok()?;
+        Some(f64::from(permille) / 1000.0)
+    }
+}
+
+/// How many bytes to read from the network at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The suffix of partial downloads.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Whether `name` denotes a partial download, or its validator.
+pub fn is_partial_download(name: &str) -> bool {
+    name.starts_with('.')
+        && (name.ends_with(PARTIAL_SUFFIX) || name.ends_with(".partial.validator"))
+}
+
+thread_local! {
+    /// Downloads in progress, by their target file.
+    ///
+    /// Every download holds the sender of the channel, and closes it when
+    /// finished.
+    static DOWNLOADS_IN_PROGRESS: RefCell<HashMap<PathBuf, async_channel::Receiver<()>>> =
+        RefCell::default();
+}
+
+/// Exclusive access to the download of a target file.
+///
+/// Only one download may write to the partial download of a target file at a
+/// time.  Release the target when dropped, and wake up all other downloads
+/// waiting for the target.
+struct DownloadLock {
+    target: PathBuf,
+    _finished: async_channel::Sender<()>,
+}
+
+impl DownloadLock {
+    /// Wait until no other download of `target` is in progress, and lock it.
+    async fn acquire(target: PathBuf) -> Self {
+        loop {
+            let in_progress =
+                DOWNLOADS_IN_PROGRESS.with_borrow(|downloads| downloads.get(&target).cloned());
+            match in_progress {
+                Some(finished) => {
+                    glib::debug!("Waiting for download of {}", target.display());
+                    // We never send anything, so this returns once the other
+                    // download closes the channel.
+                    while finished.recv().await.is_ok() {}
+                }
+                None => {
+                    let (sender, receiver) = async_channel::bounded(1);
+                    DOWNLOADS_IN_PROGRESS.with_borrow_mut(|downloads| {
+                        downloads.insert(target.clone(), receiver);
+                    });
+                    return Self {
+                        target,
+                        _finished: sender,
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Drop for DownloadLock {
+    fn drop(&mut self) {
+        DOWNLOADS_IN_PROGRESS.with_borrow_mut(|downloads| downloads.remove(&self.target));
+    }
+}
+
+/// A partial download of a file.
 ///
-/// When dropped the temporary file is scheduled to be deleted asynchronously
-/// on the glib main loop.
-struct TemporaryDownloadFile {
-    temp_file: gio::File,
+/// Keep partial downloads under a stable name next to the target file, so that
+/// we can resume them after a failed download, together with the validator to
+/// resume them with.
+struct PartialDownload {
+    file: gio::File,
+    validator_file: gio::File,
 }
 
-impl TemporaryDownloadFile {
+impl PartialDownload {
     pub fn new(directory: &Path, name: &str) -> Self {
-        let temp_file =
-            gio::File::for_path(directory.join(format!(".{name}.download.{}", glib::random_int())));
-        Self { temp_file }
+        Self {
+            file: gio::File::for_path(directory.join(format!(".{name}{PARTIAL_SUFFIX}"))),
+            validator_file: gio::File::for_path(
+                directory.join(format!(".{name}{PARTIAL_SUFFIX}.validator")),
+            ),
+        }
     }
 
-    /// Move this temporary file to a final destination.
+    /// The size of this partial download, and the validator to resume it with.
     ///
-    /// Move this temporary file to `target` which must be on the same file system
-    /// or the move will fail.
+    /// Return `None` if there's nothing to resume, or if we can't tell whether
+    /// the file changed since we started the download.
+    async fn resume_from(&self) -> Option<(u64, String)> {
+        let info = self
+            .file
+            .query_info_future(
+                gio::FILE_ATTRIBUTE_STANDARD_SIZE,
+                gio::FileQueryInfoFlags::NONE,
+                Priority::DEFAULT,
+            )
+            .await
+            .ok()?;
+        let size = u64::try_from(info.size()).ok().filter(|size| 0 < *size)?;
+        let (validator, _) = self.validator_file.load_contents_future().await.ok()?;
+        let validator = std::str::from_utf8(&validator).ok()?.trim();
+        (!validator.is_empty()).then(|| (size, validator.to_owned()))
+    }
+
+    /// Start this download over, with a new `validator`.
+    async fn start_over(
+        &self,
+        validator: Option<&str>,
+    ) -> Result<gio::FileOutputStream, glib::Error> {
+        delete_file_ignore_error(&self.file).await;
+        match validator {
+            Some(validator) => {
+                self.validator_file
+                    .replace_contents_future(
+                        validator.as_bytes().to_vec(),
+                        None,
+                        false,
+                        FileCreateFlags::PRIVATE | FileCreateFlags::REPLACE_DESTINATION,
+                    )
+                    .await
+                    .map_err(|(_, error)| error)?;
+            }
+            None => delete_file_ignore_error(&self.validator_file).await,
+        }
+        self.file
+            .create_future(FileCreateFlags::PRIVATE, Priority::DEFAULT)
+            .await
+    }
+
+    /// Discard this partial download.
+    async fn discard(&self) {
+        delete_file_ignore_error(&self.file).await;
+        delete_file_ignore_error(&self.validator_file).await;
+    }
+
+    /// Move this finished download to a final destination.
     ///
-    /// Consumes the temporary file since it no longer needs to be deleted
-    /// automatically on drop.
+    /// Move this download to `target` which must be on the same file system
+    /// or the move will fail.
     pub async fn move_to(self, target: &gio::File) -> Result<(), glib::Error> {
-        // Attempt to atomically (NO_FALLBACK_FOR_MOVE) move the temp file to the target file
+        // Attempt to atomically (NO_FALLBACK_FOR_MOVE) move the file to the target file
         let flags = FileCopyFlags::NOFOLLOW_SYMLINKS | FileCopyFlags::NO_FALLBACK_FOR_MOVE;
-        self.temp_file
+        self.file
             .move_future(target, flags, glib::Priority::DEFAULT)
             .0
             .await?;
-        // Forget about
-        std::mem::forget(self);
+        delete_file_ignore_error(&self.validator_file).await;
         Ok(())
     }
 }
 
-impl Drop for TemporaryDownloadFile {
-    fn drop(&mut self) {
-        let file = self.temp_file.clone();
-
-        glib::spawn_future_local(async move {
-            glib::debug!("Deleting temporary download file {}", file.uri());
-            delete_file_ignore_error(&file).await;
-        });
-    }
+/// Get the validator to resume a download with from `etag` and `last_modified`.
+///
+/// `If-Range` requires a strong validator, so ignore weak `ETag`s.
+fn if_range_validator(etag: Option<&str>, last_modified: Option<&str>) -> Option<String> {
+    etag.filter(|etag| !etag.starts_with("W/"))
+        .or(last_modified)
+        .map(ToOwned::to_owned)
 }
 
-impl AsRef<gio::File> for TemporaryDownloadFile {
-    fn as_ref(&self) -> &gio::File {
-        &self.temp_file
-    }
+/// Get the first byte of a `Content-Range` header `value`.
+fn content_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.trim().strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
 }
 
 /// Download a file from an URL to a directory.
 ///
 /// Download the contents of `url` to a new file named `filename` in the given
-/// `directory`.  Contents are written to a temporary file in `directory`, and
+/// `directory`.  Contents are written to a partial file in `directory`, and
 /// atomically moved to `filename` only after the download is finished.
+///
+/// If a previous download of `filename` failed, resume it where it stopped,
+/// unless the file changed on the server in the meantime, or the server does
+/// not support ranges.
+///
+/// Wait for other downloads of `filename` in `directory` to finish first, and
+/// do nothing if another download created `filename` in the meantime.
+///
+/// Report `progress` after every chunk of data.
 pub async fn download_file_to_directory(
     session: &soup::Session,
     url: &str,
     directory: &Path,
     filename: &str,
+    progress: &dyn Fn(DownloadProgress),
 ) -> Result<(), DownloadError> {
-    let temp_file = TemporaryDownloadFile::new(directory, filename);
-    transfer_file(session, url, temp_file.as_ref()).await?;
     let target = gio::File::for_path(directory.join(filename));
-    temp_file.move_to(&target).await?;
+    let _lock = DownloadLock::acquire(directory.join(filename)).await;
+    if target
+        .query_info_future(
+            gio::FILE_ATTRIBUTE_STANDARD_TYPE,
+            gio::FileQueryInfoFlags::NONE,
+            Priority::DEFAULT,
+        )
+        .await
+        .is_ok()
+    {
+        glib::debug!("Another download created {} already", target.uri());
+        return Ok(());
+    }
+    let partial = PartialDownload::new(directory, filename);
+    transfer_file(session, url, &partial, progress).await?;
+    if let Err(error) = verify_image_file(&partial.file).await {
+        partial.discard().await;
+        return Err(error);
+    }
+    partial.move_to(&target).await?;
     Ok(())
 }
 
-/// Return a file from `url` to `target`.
+/// Transfer a file from `url` to a `partial` download.
+///
+/// Resume the `partial` download if possible, or start over, and report
+/// `progress` after every chunk of data.
 ///
-/// Fails if `target` already exists.
+/// Fail with [`InvalidImage::Truncated`] if we received less data than the
+/// `Content-Length` of the response announced.  We can't tell whether we
+/// received all data if the response has no `Content-Length`, or if the
+/// server compressed the response.
 ///
 /// Return the amount of bytes transferred.
 async fn transfer_file(
     session: &soup::Session,
     url: &str,
-    target: &gio::File,
-) -> Result<isize, DownloadError> {
+    partial: &PartialDownload,
+    progress: &dyn Fn(DownloadProgress),
+) -> Result<u64, DownloadError> {
     let message = soup::Message::new("GET", url).map_err(|error| {
         glib::Error::new(
             IOErrorEnum::InvalidArgument,
@@ -143,20 +442,233 @@ async fn transfer_file(
         )
     })?;
 
+    let resume_from = partial.resume_from().await;
+    if let Some((offset, validator)) = &resume_from
+        && let Some(headers) = message.request_headers()
+    {
+        glib::info!("Resuming download of {url} at {offset} bytes");
+        headers.replace("Range", &format!("bytes={offset}-"));
+        headers.replace("If-Range", validator);
+    }
+
     let source = session.send_future(&message, Priority::DEFAULT).await?;
-    if message.status() != soup::Status::Ok {
-        return Err(DownloadError::SoupStatus(message.status()));
+    let headers = message.response_headers();
+    if matches!(
+        message.status(),
+        soup::Status::Ok | soup::Status::PartialContent
+    ) {
+        check_content_type(
+            headers
+                .as_ref()
+                .and_then(|headers| headers.one("Content-Type"))
+                .as_deref(),
+        )?;
     }
+    let (sink, resumed_at) = match message.status() {
+        soup::Status::PartialContent => {
+            let start = headers
+                .as_ref()
+                .and_then(|headers| headers.one("Content-Range"))
+                .and_then(|value| content_range_start(&value));
+            match resume_from {
+                Some((offset, _)) if start == Some(offset) => {
+                    let sink = partial
+                        .file
+                        .append_to_future(FileCreateFlags::PRIVATE, Priority::DEFAULT)
+                        .await?;
+                    (sink, offset)
+                }
+                _ => {
+                    glib::warn!("Server returned unexpected range for {url}, discarding download");
+                    partial.discard().await;
+                    return Err(DownloadError::SoupStatus(message.status()));
+                }
+            }
+        }
+        soup::Status::Ok => {
+            // The server sent the whole file, either because we didn't ask for
+            // a range, or because the file changed, or because the server
+            // doesn't support ranges.
+            let validator = headers.as_ref().and_then(|headers| {
+                if_range_validator(
+                    headers.one("ETag").as_deref(),
+                    headers.one("Last-Modified").as_deref(),
+                )
+            });
+            (partial.start_over(validator.as_deref()).await?, 0)
+        }
+        soup::Status::RequestedRangeNotSatisfiable => {
+            // Our partial download doesn't match the file on the server, so
+            // start over with the next attempt.
+            partial.discard().await;
+            return Err(DownloadError::SoupStatus(message.status()));
+        }
+        status => return Err(DownloadError::SoupStatus(status)),
+    };
 
-    let sink = target
-        .create_future(gio::FileCreateFlags::NONE, glib::Priority::DEFAULT)
-        .await?;
-    let transferred = sink
-        .splice_future(
-            &source,
-            gio::OutputStreamSpliceFlags::CLOSE_SOURCE | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
-            glib::Priority::DEFAULT,
-        )
-        .await?;
+    // For partial content the content length is the length of the remaining
+    // range, so add the part we downloaded before.  Soup decodes compressed
+    // content, so the content length does not tell the size of encoded content.
+    let total = headers
+        .filter(|headers| {
+            headers
+                .one("Content-Encoding")
+                .is_none_or(|encoding| encoding == "identity")
+        })
+        .and_then(|headers| u64::try_from(headers.content_length()).ok())
+        .filter(|length| 0 < *length)
+        .map(|length| length.saturating_add(resumed_at));
+    let mut transferred = resumed_at;
+    progress(DownloadProgress { transferred, total });
+    loop {
+        let chunk = source
+            .read_bytes_future(CHUNK_SIZE, Priority::DEFAULT)
+            .await?;
+        if chunk.is_empty() {
+            break;
+        }
+        let (_, written, error) = sink
+            .write_all_future(chunk, Priority::DEFAULT)
+            .await
+            .map_err(|(_, error)| error)?;
+        if let Some(error) = error {
+            return Err(error.into());
+        }
+        transferred = transferred.saturating_add(u64::try_from(written).unwrap_or(0));
+        progress(DownloadProgress { transferred, total });
+    }
+    sink.close_future(Priority::DEFAULT).await?;
+    if let Err(error) = source.close_future(Priority::DEFAULT).await {
+        glib::debug!("Failed to close response stream of {url}: {error}");
+    }
+    if let Some(expected) = total
+        && transferred != expected
+    {
+        if expected < transferred {
+            // We can't resume a download which is larger than expected
+            partial.discard().await;
+        }
+        return Err(InvalidImage::Truncated {
+            expected,
+            actual: transferred,
+        }
+        .into());
+    }
     Ok(transferred)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_content_type_accepts_images() {
+        assert!(check_content_type(None).is_ok());
+        assert!(check_content_type(Some("image/jpeg")).is_ok());
+        assert!(check_content_type(Some("Image/PNG; charset=binary")).is_ok());
+        assert!(check_content_type(Some("application/octet-stream")).is_ok());
+        assert!(matches!(
+            check_content_type(Some("text/html; charset=utf-8")),
+            Err(InvalidImage::ContentType(content_type)) if content_type == "text/html"
+        ));
+    }
+
+    #[test]
+    fn image_format_from_signature() {
+        assert_eq!(image_format(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), Some("jpeg"));
+        assert_eq!(
+            image_format(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"),
+            Some("png")
+        );
+        assert_eq!(image_format(b"GIF89a\x01\0"), Some("gif"));
+        assert_eq!(image_format(b"RIFF\x24\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(image_format(b"\0\0\0\x1cftypavif\0\0\0\0"), Some("avif"));
+        assert_eq!(image_format(b"\0\0\0\x1cftypmp42\0\0\0\0"), None);
+        assert_eq!(image_format(b"<!DOCTYPE html>"), None);
+        assert_eq!(image_format(b""), None);
+    }
+
+    #[test]
+    fn download_progress_fraction() {
+        let progress = |transferred, total| DownloadProgress { transferred, total };
+        assert_eq!(progress(0, Some(2048)).fraction(), Some(0.0));
+        assert_eq!(progress(1024, Some(2048)).fraction(), Some(0.5));
+        assert_eq!(progress(4096, Some(2048)).fraction(), Some(1.0));
+        assert_eq!(progress(1024, Some(0)).fraction(), None);
+        assert_eq!(progress(1024, None).fraction(), None);
+    }
+
+    #[test]
+    fn if_range_validator_prefers_strong_etag() {
+        assert_eq!(
+            if_range_validator(Some("\"abc\""), Some("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some("\"abc\"".to_owned())
+        );
+        assert_eq!(
+            if_range_validator(Some("W/\"abc\""), Some("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT".to_owned())
+        );
+        assert_eq!(if_range_validator(Some("W/\"abc\""), None), None);
+        assert_eq!(if_range_validator(None, None), None);
+    }
+
+    #[test]
+    fn is_partial_download_file() {
+        assert!(is_partial_download(".a.jpg.partial"));
+        assert!(is_partial_download(".a.jpg.partial.validator"));
+        assert!(!is_partial_download("a.jpg"));
+        assert!(!is_partial_download("a.partial"));
+        assert!(!is_partial_download(".a.jpg"));
+    }
+
+    #[glib::async_test]
+    async fn download_lock_serializes_downloads() {
+        let target = PathBuf::from("/images/bing/a.jpg");
+        let first = DownloadLock::acquire(target.clone()).await;
+        let (tx, rx) = async_channel::bounded(1);
+        glib::spawn_future_local(glib::clone!(
+            #[strong]
+            target,
+            async move {
+                let _second = DownloadLock::acquire(target).await;
+                tx.send(()).await.unwrap();
+            }
+        ));
+        // Let the second download start waiting
+        glib::timeout_future(std::time::Duration::from_millis(50)).await;
+        assert!(rx.is_empty());
+        drop(first);
+        rx.recv().await.unwrap();
+        assert!(DOWNLOADS_IN_PROGRESS.with_borrow(|downloads| !downloads.contains_key(&target)));
+    }
+
+    #[test]
+    fn content_range_start_of_header() {
+        assert_eq!(content_range_start("bytes 1024-2047/2048"), Some(1024));
+        assert_eq!(content_range_start("bytes 0-99/*"), Some(0));
+        assert_eq!(content_range_start("bytes */2048"), None);
+        assert_eq!(content_range_start("items 1-2/3"), None);
+    }
+
+    #[glib::async_test]
+    async fn verify_image_file_decodes_image() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let path = directory.join("image.png");
+        let pixbuf = Pixbuf::new(gtk::gdk_pixbuf::Colorspace::Rgb, false, 8, 32, 32).unwrap();
+        pixbuf.fill(0x1234_56ff);
+        pixbuf.savev(&path, "png", &[]).unwrap();
+        let valid = verify_image_file(&gio::File::for_path(&path)).await;
+        // Keep the signature and the header, but corrupt the image data
+        let mut data = std::fs::read(&path).unwrap();
+        data.truncate(40);
+        data.resize(400, 0);
+        std::fs::write(&path, data).unwrap();
+        let corrupt = verify_image_file(&gio::File::for_path(&path)).await;
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(valid.is_ok());
+        assert!(matches!(
+            corrupt,
+            Err(DownloadError::InvalidImage(InvalidImage::Undecodable(_)))
+        ));
+    }
+}
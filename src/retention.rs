@@ -0,0 +1,336 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Clean up downloaded images.
+//!
+//! We only ever look at images in the images directory of this application,
+//! and thus never touch files of the user, e.g. in the folder of the local
+//! folder source.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use gtk::gio::{self, prelude::SettingsExt};
+
+use crate::net::http::download::is_partial_download;
+
+/// Seconds per day.
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Delete partial downloads older than this.
+const MAX_PARTIAL_DOWNLOAD_AGE: Duration = Duration::from_secs(7 * SECONDS_PER_DAY);
+
+/// How long to keep downloaded images, and how much space they may take.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Delete images older than this many days, if any.
+    pub keep_days: Option<u32>,
+    /// Delete oldest images until all images take less than this many bytes, if any.
+    pub max_size: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Read the retention policy from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        let keep_days = settings.uint("images-keep-days");
+        let max_size_mib = settings.uint("images-max-size");
+        Self {
+            keep_days: Some(keep_days).filter(|days| 0 < *days),
+            max_size: Some(u64::from(max_size_mib) * 1024 * 1024).filter(|size| 0 < *size),
+        }
+    }
+
+    /// Whether this policy keeps all images forever.
+    pub fn keeps_everything(&self) -> bool {
+        self.keep_days.is_none() && self.max_size.is_none()
+    }
+}
+
+/// A downloaded image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageFile {
+    /// The path of the image.
+    pub path: PathBuf,
+    /// The identifier of the source the image belongs to.
+    pub source: String,
+    /// The size of the image, in bytes.
+    pub size: u64,
+    /// When the image was last modified, i.e. downloaded.
+    pub modified: SystemTime,
+}
+
+/// List all downloaded images in `directory`.
+///
+/// `directory` contains one directory per source, named after the
+/// identifier of the source.  Skip partial downloads.
+///
+/// # Errors
+///
+/// If listing `directory` fails.  Ignore failures to list or inspect
+/// individual files.
+pub fn list_image_files(directory: &Path) -> std::io::Result<Vec<ImageFile>> {
+    list_files(directory, |name| !is_partial_download(name))
+}
+
+/// List partial downloads in `directory` which did not change for a while.
+///
+/// We resume failed downloads, so keep recent partial downloads.
+///
+/// # Errors
+///
+/// If listing `directory` fails.
+pub fn list_stale_partial_downloads(
+    directory: &Path,
+    now: SystemTime,
+) -> std::io::Result<Vec<PathBuf>> {
+    let files = list_files(directory, is_partial_download)?;
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            now.duration_since(file.modified)
+                .is_ok_and(|age| MAX_PARTIAL_DOWNLOAD_AGE < age)
+        })
+        .map(|file| file.path)
+        .collect())
+}
+
+/// List all files in the source directories in `directory` with a name that
+/// matches `predicate`.
+fn list_files(
+    directory: &Path,
+    predicate: impl Fn(&str) -> bool,
+) -> std::io::Result<Vec<ImageFile>> {
+    let source_directories = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut files = Vec::new();
+    for source_directory in source_directories.filter_map(Result::ok) {
+        let source = source_directory.file_name().to_string_lossy().into_owned();
+        let Ok(entries) = std::fs::read_dir(source_directory.path()) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            if !predicate(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_file() {
+                files.push(ImageFile {
+                    path: entry.path(),
+                    source: source.clone(),
+                    size: metadata.len(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Compute the total size of `files` per source.
+///
+/// Return sizes sorted by source identifier.
+pub fn size_per_source(files: &[ImageFile]) -> Vec<(String, u64)> {
+    let mut sizes: Vec<(String, u64)> = Vec::new();
+    for file in files {
+        match sizes.iter_mut().find(|(source, _)| *source == file.source) {
+            Some((_, size)) => *size += file.size,
+            None => sizes.push((file.source.clone(), file.size)),
+        }
+    }
+    sizes.sort();
+    sizes
+}
+
+/// Plan which of `files` to delete according to `policy` at `now`.
+///
+/// First delete all images older than the days to keep, then delete the
+/// oldest images until all images fit into the maximum size.  Never delete
+/// `protected` files, but count their size.
+pub fn plan_cleanup(
+    files: &[ImageFile],
+    policy: &RetentionPolicy,
+    protected: &[PathBuf],
+    now: SystemTime,
+) -> Vec<PathBuf> {
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort_by_key(|file| file.modified);
+    let max_age = policy
+        .keep_days
+        .map(|days| Duration::from_secs(u64::from(days) * SECONDS_PER_DAY));
+    let (mut delete, mut keep): (Vec<&ImageFile>, Vec<&ImageFile>) =
+        files.into_iter().partition(|file| {
+            !protected.contains(&file.path)
+                && max_age.is_some_and(|max_age| {
+                    now.duration_since(file.modified)
+                        .is_ok_and(|age| max_age < age)
+                })
+        });
+    if let Some(max_size) = policy.max_size {
+        let mut total_size = keep.iter().map(|file| file.size).sum::<u64>();
+        keep.retain(|file| {
+            if max_size < total_size && !protected.contains(&file.path) {
+                total_size -= file.size;
+                delete.push(*file);
+                false
+            } else {
+                true
+            }
+        });
+    }
+    delete.into_iter().map(|file| file.path.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    fn days_ago(now: SystemTime, days: u64) -> SystemTime {
+        now - Duration::from_secs(days * SECONDS_PER_DAY)
+    }
+
+    fn files(now: SystemTime) -> Vec<ImageFile> {
+        [
+            ("a.jpg", 10, 30),
+            ("b.jpg", 20, 20),
+            ("c.jpg", 30, 10),
+            ("d.jpg", 40, 0),
+        ]
+        .into_iter()
+        .map(|(name, size, age)| ImageFile {
+            path: PathBuf::from("/images/bing").join(name),
+            source: "bing".into(),
+            size,
+            modified: days_ago(now, age),
+        })
+        .collect()
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn plan_cleanup_keep_everything() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy::default();
+        assert!(policy.keeps_everything());
+        assert!(plan_cleanup(&files(now), &policy, &[], now).is_empty());
+    }
+
+    #[test]
+    fn plan_cleanup_keep_days() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_days: Some(15),
+            max_size: None,
+        };
+        let delete = plan_cleanup(&files(now), &policy, &[], now);
+        assert_eq!(names(&delete), vec!["a.jpg", "b.jpg"]);
+    }
+
+    #[test]
+    fn plan_cleanup_max_size_deletes_oldest_first() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_days: None,
+            max_size: Some(70),
+        };
+        let delete = plan_cleanup(&files(now), &policy, &[], now);
+        assert_eq!(names(&delete), vec!["a.jpg", "b.jpg"]);
+    }
+
+    #[test]
+    fn plan_cleanup_never_deletes_protected_files() {
+        let now = SystemTime::now();
+        let policy = RetentionPolicy {
+            keep_days: Some(5),
+            max_size: Some(45),
+        };
+        let protected = vec![PathBuf::from("/images/bing/a.jpg")];
+        let delete = plan_cleanup(&files(now), &policy, &protected, now);
+        // a.jpg is protected, and still counts towards the total size, so we
+        // have to delete d.jpg as well.
+        assert_eq!(names(&delete), vec!["b.jpg", "c.jpg", "d.jpg"]);
+    }
+
+    #[test]
+    fn size_per_source_sums_sizes() {
+        let mut files = files(SystemTime::now());
+        files.push(ImageFile {
+            path: PathBuf::from("/images/apod/e.jpg"),
+            source: "apod".into(),
+            size: 5,
+            modified: SystemTime::now(),
+        });
+        assert_eq!(
+            size_per_source(&files),
+            vec![("apod".to_owned(), 5), ("bing".to_owned(), 100)]
+        );
+    }
+
+    #[test]
+    fn list_image_files_in_source_directories() {
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        std::fs::create_dir(directory.join("bing")).unwrap();
+        std::fs::write(directory.join("bing").join("a.jpg"), b"12345").unwrap();
+        std::fs::write(directory.join("bing").join(".b.jpg.partial"), b"12").unwrap();
+        std::fs::write(directory.join("stray.jpg"), b"123").unwrap();
+        let files = list_image_files(&directory).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let files = files
+            .into_iter()
+            .map(|file| (file.source, file.size))
+            .collect::<Vec<_>>();
+        assert_eq!(files, vec![("bing".to_owned(), 5)]);
+    }
+
+    #[test]
+    fn list_image_files_missing_directory() {
+        let files = list_image_files(Path::new("/this/does/not/exist")).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn list_stale_partial_downloads_in_source_directories() {
+        let now = SystemTime::now();
+        let directory = glib::mkdtemp(glib::tmp_dir().join("potd-test-XXXXXX")).unwrap();
+        let source_directory = directory.join("bing");
+        std::fs::create_dir(&source_directory).unwrap();
+        for (name, age) in [
+            ("a.jpg", 30),
+            (".b.jpg.partial", 30),
+            (".b.jpg.partial.validator", 30),
+            (".c.jpg.partial", 1),
+        ] {
+            let path = source_directory.join(name);
+            std::fs::write(&path, b"12345").unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(days_ago(now, age))
+                .unwrap();
+        }
+        let mut stale = list_stale_partial_downloads(&directory, now).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        stale.sort();
+        assert_eq!(
+            names(&stale),
+            vec![".b.jpg.partial", ".b.jpg.partial.validator"]
+        );
+    }
+}
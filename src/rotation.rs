@@ -0,0 +1,210 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Rotate automatic wallpapers through multiple sources.
+
+use gtk::gio::{self, prelude::*};
+use jiff::civil::{Date, date};
+
+use crate::date::cycle_index_for_date;
+
+/// The first day of round-robin rotation.
+///
+/// A Monday, to make rotation a bit more predictable for users.
+const ROUND_ROBIN_BASE: Date = date(2024, 1, 1);
+
+/// How to rotate through sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationMode {
+    /// Do not rotate; always use the selected source.
+    #[default]
+    Off,
+    /// Use the next enabled source every day.
+    RoundRobin,
+    /// Pick an enabled source at random, according to its weight.
+    WeightedRandom,
+    /// Use a fixed source for every day of the week.
+    Weekday,
+}
+
+impl RotationMode {
+    fn from_id(id: &str) -> Self {
+        match id {
+            "round-robin" => Self::RoundRobin,
+            "weighted-random" => Self::WeightedRandom,
+            "weekday" => Self::Weekday,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Rotation through multiple sources.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rotation {
+    /// How to rotate.
+    pub mode: RotationMode,
+    /// Identifiers and weights of sources to rotate through.
+    ///
+    /// Sources with weight 0 are disabled.
+    pub sources: Vec<(String, u32)>,
+    /// Identifiers of sources for each day of the week, starting on Monday.
+    ///
+    /// An empty identifier uses the selected source.
+    pub weekdays: Vec<String>,
+}
+
+impl Rotation {
+    /// Read the rotation from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            mode: RotationMode::from_id(&settings.string("rotation-mode")),
+            sources: settings.get("rotation-sources"),
+            weekdays: settings.get("rotation-weekdays"),
+        }
+    }
+
+    /// Pick the identifier of the source to use at `date`.
+    ///
+    /// `random` returns a random number less than its argument, for weighted
+    /// random rotation.
+    ///
+    /// Return `None` to use the selected source, i.e. if rotation is off, or
+    /// if there are no enabled sources.
+    pub fn source_for_date(&self, date: Date, random: impl FnOnce(u32) -> u32) -> Option<&str> {
+        let mut enabled = self.sources.iter().filter(|(_, weight)| 0 < *weight);
+        match self.mode {
+            RotationMode::Off => None,
+            RotationMode::RoundRobin => {
+                let enabled = enabled.collect::<Vec<_>>();
+                let index = cycle_index_for_date(ROUND_ROBIN_BASE, date, enabled.len())?;
+                enabled.get(index).map(|(id, _)| id.as_str())
+            }
+            RotationMode::WeightedRandom => {
+                let total = enabled
+                    .clone()
+                    .fold(0_u32, |total, (_, weight)| total.saturating_add(*weight));
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = random(total);
+                enabled
+                    .find(|(_, weight)| {
+                        if pick < *weight {
+                            true
+                        } else {
+                            pick -= weight;
+                            false
+                        }
+                    })
+                    .map(|(id, _)| id.as_str())
+            }
+            RotationMode::Weekday => {
+                let weekday = usize::try_from(date.weekday().to_monday_zero_offset()).ok()?;
+                self.weekdays
+                    .get(weekday)
+                    .map(String::as_str)
+                    .filter(|id| !id.is_empty())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use super::*;
+
+    fn rotation(mode: RotationMode) -> Rotation {
+        Rotation {
+            mode,
+            sources: vec![
+                ("apod".into(), 1),
+                ("bing".into(), 0),
+                ("wikimedia".into(), 3),
+                ("stalenhag".into(), 1),
+            ],
+            weekdays: ["apod", "", "", "", "", "stalenhag", "stalenhag"]
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn off_uses_selected_source() {
+        let rotation = rotation(RotationMode::Off);
+        assert_eq!(rotation.source_for_date(date(2025, 3, 3), |_| 0), None);
+    }
+
+    #[test]
+    fn round_robin_cycles_enabled_sources_by_day() {
+        let rotation = rotation(RotationMode::RoundRobin);
+        let ids = (0..4)
+            .map(|day| {
+                let date = date(2025, 3, 3) + jiff::Span::new().days(day);
+                rotation.source_for_date(date, |_| 0)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                Some("wikimedia"),
+                Some("stalenhag"),
+                Some("apod"),
+                Some("wikimedia")
+            ]
+        );
+    }
+
+    #[test]
+    fn weighted_random_respects_weights() {
+        let rotation = rotation(RotationMode::WeightedRandom);
+        let ids = (0..5)
+            .map(|pick| {
+                rotation.source_for_date(date(2025, 3, 3), |total| {
+                    assert_eq!(total, 5);
+                    pick
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                Some("apod"),
+                Some("wikimedia"),
+                Some("wikimedia"),
+                Some("wikimedia"),
+                Some("stalenhag")
+            ]
+        );
+    }
+
+    #[test]
+    fn weighted_random_without_enabled_sources() {
+        let rotation = Rotation {
+            mode: RotationMode::WeightedRandom,
+            sources: vec![("apod".into(), 0)],
+            weekdays: Vec::new(),
+        };
+        assert_eq!(rotation.source_for_date(date(2025, 3, 3), |_| 0), None);
+    }
+
+    #[test]
+    fn weekday_uses_source_of_day() {
+        let rotation = rotation(RotationMode::Weekday);
+        // 2025-03-03 is a Monday
+        assert_eq!(
+            rotation.source_for_date(date(2025, 3, 3), |_| 0),
+            Some("apod")
+        );
+        assert_eq!(rotation.source_for_date(date(2025, 3, 4), |_| 0), None);
+        assert_eq!(
+            rotation.source_for_date(date(2025, 3, 9), |_| 0),
+            Some("stalenhag")
+        );
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! When to update the wallpaper automatically.
+//!
+//! All times are civil times in the local timezone.
+
+use gtk::gio::{self, prelude::*};
+use jiff::SignedDuration;
+use jiff::civil::{DateTime, Time};
+
+/// Never attempt to update more often than this.
+///
+/// This applies to failed updates, and to updates which found no new image
+/// while waiting for the source to publish.
+pub const RETRY_INTERVAL: SignedDuration = SignedDuration::from_mins(30);
+
+/// Never back off longer than this after failed updates.
+///
+/// Unless the source explicitly asks us to wait longer.
+pub const MAX_RETRY_INTERVAL: SignedDuration = SignedDuration::from_hours(12);
+
+/// How long to wait before retrying after `failures` consecutive failed updates.
+///
+/// Start at [`RETRY_INTERVAL`] and double the delay with every failure, up to
+/// [`MAX_RETRY_INTERVAL`].  Wait at least `retry_after` if the source asked us
+/// to.
+pub fn retry_delay(failures: u32, retry_after: Option<SignedDuration>) -> SignedDuration {
+    let backoff = 2_i32
+        .checked_pow(failures.saturating_sub(1))
+        .and_then(|factor| RETRY_INTERVAL.checked_mul(factor))
+        .map_or(MAX_RETRY_INTERVAL, |delay| delay.min(MAX_RETRY_INTERVAL));
+    retry_after.map_or(backoff, |retry_after| backoff.max(retry_after))
+}
+
+/// A schedule for automatic wallpaper updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSchedule {
+    /// Update once a day at the given time.
+    Daily(Time),
+    /// Update every given number of hours.
+    Interval(u32),
+    /// Update as soon as the source publishes a new image.
+    ///
+    /// Check for a new image periodically, until we found a new image today.
+    WhenPublished,
+}
+
+impl Default for UpdateSchedule {
+    fn default() -> Self {
+        Self::Interval(12)
+    }
+}
+
+impl UpdateSchedule {
+    /// Read the update schedule from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        match settings.string("update-schedule").as_str() {
+            "daily" => {
+                let hour = i8::try_from(settings.uint("update-schedule-hour")).unwrap_or(0);
+                let minute = i8::try_from(settings.uint("update-schedule-minute")).unwrap_or(0);
+                Self::Daily(Time::new(hour, minute, 0, 0).unwrap_or(Time::midnight()))
+            }
+            "published" => Self::WhenPublished,
+            _ => Self::Interval(settings.uint("update-schedule-interval").max(1)),
+        }
+    }
+
+    /// Whether a new image with the same URL as the current one counts as update.
+    ///
+    /// When we wait for the source to publish, an unchanged image means that
+    /// the source did not publish yet, so we should try again later.
+    pub fn accepts_unchanged_image(self) -> bool {
+        self != Self::WhenPublished
+    }
+
+    /// Whether an update is due at `now`.
+    ///
+    /// `last_update` is the time of the last successful update.  Never update
+    /// before `retry_at`, to avoid hammering the source after failed attempts.
+    pub fn is_due(
+        self,
+        last_update: Option<DateTime>,
+        retry_at: Option<DateTime>,
+        now: DateTime,
+    ) -> bool {
+        if retry_at.is_some_and(|retry_at| now < retry_at) {
+            return false;
+        }
+        let Some(last_update) = last_update else {
+            return true;
+        };
+        match self {
+            Self::Daily(time) => {
+                let today = now.date().to_datetime(time);
+                let latest = if today <= now {
+                    Some(today)
+                } else {
+                    today.yesterday().ok()
+                };
+                latest.is_some_and(|latest| last_update < latest)
+            }
+            Self::Interval(hours) => {
+                SignedDuration::from_hours(i64::from(hours)) <= now.duration_since(last_update)
+            }
+            Self::WhenPublished => last_update.date() < now.date(),
+        }
+    }
+}
+
+/// The last successful automatic wallpaper update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastUpdate {
+    /// When we updated the wallpaper.
+    pub at: DateTime,
+    /// The identifier of the source we updated the wallpaper from.
+    pub source: String,
+    /// The URL of the image we set as wallpaper.
+    pub image_url: String,
+}
+
+impl LastUpdate {
+    /// Read the last update from `settings`.
+    ///
+    /// Return `None` if we never updated the wallpaper automatically, or if
+    /// settings contain an invalid time.
+    pub fn from_settings(settings: &gio::Settings) -> Option<Self> {
+        let (at, source, image_url) =
+            settings.get::<(String, String, String)>("last-automatic-update");
+        Self::from_parts(&at, source, image_url)
+    }
+
+    fn from_parts(at: &str, source: String, image_url: String) -> Option<Self> {
+        let at = at.parse().ok()?;
+        Some(Self {
+            at,
+            source,
+            image_url,
+        })
+    }
+
+    /// Save this update to `settings`.
+    ///
+    /// # Errors
+    ///
+    /// If settings are not writable.
+    pub fn save_to_settings(&self, settings: &gio::Settings) -> Result<(), glib::BoolError> {
+        settings.set(
+            "last-automatic-update",
+            (
+                self.at.to_string(),
+                self.source.as_str(),
+                self.image_url.as_str(),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::{date, time};
+
+    use super::*;
+
+    #[test]
+    fn last_update_from_parts() {
+        let update = LastUpdate {
+            at: date(2025, 3, 4).at(7, 12, 30, 0),
+            source: "apod".into(),
+            image_url: "https://example.com/image.jpg".into(),
+        };
+        assert_eq!(
+            LastUpdate::from_parts(
+                &update.at.to_string(),
+                update.source.clone(),
+                update.image_url.clone()
+            ),
+            Some(update)
+        );
+        assert_eq!(
+            LastUpdate::from_parts("", String::new(), String::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn never_updated_is_due() {
+        let now = date(2025, 3, 4).at(6, 0, 0, 0);
+        for schedule in [
+            UpdateSchedule::Daily(time(7, 0, 0, 0)),
+            UpdateSchedule::Interval(12),
+            UpdateSchedule::WhenPublished,
+        ] {
+            assert!(schedule.is_due(None, None, now), "{schedule:?}");
+        }
+    }
+
+    #[test]
+    fn never_due_before_retry() {
+        let now = date(2025, 3, 4).at(6, 0, 0, 0);
+        let retry_at = Some(date(2025, 3, 4).at(6, 15, 0, 0));
+        assert!(!UpdateSchedule::Interval(1).is_due(None, retry_at, now));
+        let retry_at = Some(date(2025, 3, 4).at(6, 0, 0, 0));
+        assert!(UpdateSchedule::Interval(1).is_due(None, retry_at, now));
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        let delays = (1..=7)
+            .map(|failures| retry_delay(failures, None).as_mins())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 720, 720]);
+        assert_eq!(retry_delay(u32::MAX, None), MAX_RETRY_INTERVAL);
+    }
+
+    #[test]
+    fn retry_delay_honours_retry_after() {
+        let retry_after = SignedDuration::from_hours(24);
+        assert_eq!(retry_delay(1, Some(retry_after)), retry_after);
+        let retry_after = SignedDuration::from_mins(5);
+        assert_eq!(
+            retry_delay(2, Some(retry_after)),
+            SignedDuration::from_hours(1)
+        );
+    }
+
+    #[test]
+    fn daily_is_due_after_time_of_day() {
+        let schedule = UpdateSchedule::Daily(time(7, 0, 0, 0));
+        let last_update = Some(date(2025, 3, 3).at(7, 5, 0, 0));
+        assert!(!schedule.is_due(last_update, None, date(2025, 3, 3).at(23, 0, 0, 0)));
+        assert!(!schedule.is_due(last_update, None, date(2025, 3, 4).at(6, 59, 0, 0)));
+        assert!(schedule.is_due(last_update, None, date(2025, 3, 4).at(7, 0, 0, 0)));
+    }
+
+    #[test]
+    fn daily_catches_up_on_missed_update() {
+        let schedule = UpdateSchedule::Daily(time(7, 0, 0, 0));
+        let last_update = Some(date(2025, 3, 1).at(7, 0, 0, 0));
+        assert!(schedule.is_due(last_update, None, date(2025, 3, 4).at(6, 0, 0, 0)));
+    }
+
+    #[test]
+    fn interval_is_due_after_hours() {
+        let schedule = UpdateSchedule::Interval(6);
+        let last_update = Some(date(2025, 3, 4).at(8, 0, 0, 0));
+        assert!(!schedule.is_due(last_update, None, date(2025, 3, 4).at(13, 59, 0, 0)));
+        assert!(schedule.is_due(last_update, None, date(2025, 3, 4).at(14, 0, 0, 0)));
+    }
+
+    #[test]
+    fn when_published_is_due_on_next_day() {
+        let schedule = UpdateSchedule::WhenPublished;
+        let last_update = Some(date(2025, 3, 4).at(0, 30, 0, 0));
+        assert!(!schedule.is_due(last_update, None, date(2025, 3, 4).at(23, 59, 0, 0)));
+        assert!(schedule.is_due(last_update, None, date(2025, 3, 5).at(0, 0, 0, 0)));
+        assert!(!schedule.accepts_unchanged_image());
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Cycle the wallpaper through all recent images.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use gtk::gio::{self, prelude::*};
+use jiff::civil::Date;
+
+use crate::history::HistoryEntry;
+
+/// Slideshow settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlideshowSettings {
+    /// Whether to cycle through images.
+    pub enabled: bool,
+    /// How long to show each image.
+    pub interval: Duration,
+    /// How many days of images to include, counting today.
+    pub days: u32,
+}
+
+impl SlideshowSettings {
+    /// Read slideshow settings from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            enabled: settings.boolean("slideshow"),
+            interval: Duration::from_secs(u64::from(settings.uint("slideshow-interval")) * 60),
+            days: settings.uint("slideshow-days").max(1),
+        }
+    }
+
+    /// The first day of images to include, if `today` is the last day.
+    pub fn since(&self, today: Date) -> Date {
+        today
+            .checked_sub(jiff::Span::new().days(i64::from(self.days.saturating_sub(1))))
+            .unwrap_or(today)
+    }
+}
+
+/// Get the files of all images fetched from `source` on or after `since`.
+///
+/// Return files from the newest to the oldest day, and images of the same
+/// day in the order we fetched them.  Skip entries without files and
+/// `blocked` images, and return every file only once.
+pub fn slideshow_files(
+    entries: &[HistoryEntry],
+    source: &str,
+    blocked: &[String],
+    since: Date,
+) -> Vec<PathBuf> {
+    let mut entries = entries
+        .iter()
+        .filter(|entry| {
+            since <= entry.date
+                && entry.metadata.source == source
+                && !blocked.contains(&entry.image_url)
+        })
+        .collect::<Vec<_>>();
+    // A stable sort retains the order of entries of the same day
+    entries.sort_by(|a, b| b.date.cmp(&a.date));
+    let mut files: Vec<PathBuf> = Vec::new();
+    for file in entries.into_iter().filter_map(|entry| entry.file.as_ref()) {
+        if !files.contains(file) {
+            files.push(file.clone());
+        }
+    }
+    files
+}
+
+/// A slideshow through a list of files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Slideshow {
+    files: Vec<PathBuf>,
+    position: usize,
+}
+
+impl Slideshow {
+    /// Create a slideshow through `files`, starting at the first file.
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self { files, position: 0 }
+    }
+
+    /// Create a slideshow through `files`, starting at `file`.
+    ///
+    /// Start at the first file if `files` does not contain `file`.
+    pub fn starting_at(files: Vec<PathBuf>, file: &Path) -> Self {
+        let position = files
+            .iter()
+            .position(|candidate| candidate == file)
+            .unwrap_or(0);
+        Self { files, position }
+    }
+
+    /// The current file, if any.
+    pub fn current(&self) -> Option<&Path> {
+        self.files.get(self.position).map(PathBuf::as_path)
+    }
+
+    /// Advance to the next file, and return it.
+    ///
+    /// Start over after the last file.
+    pub fn advance(&mut self) -> Option<&Path> {
+        if self.files.is_empty() {
+            return None;
+        }
+        self.position = (self.position + 1) % self.files.len();
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::date;
+
+    use crate::images::{DownloadableImage, ImageMetadata};
+
+    use super::*;
+
+    fn entry(day: i8, url: &str, file: Option<&str>) -> HistoryEntry {
+        entry_from_source(day, url, file, "bing")
+    }
+
+    fn entry_from_source(day: i8, url: &str, file: Option<&str>, source: &str) -> HistoryEntry {
+        let image = DownloadableImage {
+            metadata: ImageMetadata {
+                title: "A lake".into(),
+                description: None,
+                copyright: None,
+                url: None,
+                source: source.into(),
+            },
+            image_url: url.into(),
+            pubdate: None,
+            suggested_filename: None,
+            dimensions: None,
+        };
+        HistoryEntry::new(date(2025, 10, day), &image, file.map(PathBuf::from))
+    }
+
+    #[test]
+    fn slideshow_files_newest_day_first() {
+        let entries = vec![
+            entry(14, "https://old.jpg", Some("/images/old.jpg")),
+            entry(15, "https://a.jpg", Some("/images/a.jpg")),
+            entry(15, "https://b.jpg", None),
+            entry(16, "https://c.jpg", Some("/images/c.jpg")),
+            entry(16, "https://d.jpg", Some("/images/d.jpg")),
+            entry(16, "https://a.jpg", Some("/images/a.jpg")),
+        ];
+        assert_eq!(
+            slideshow_files(&entries, "bing", &[], date(2025, 10, 15)),
+            vec![
+                PathBuf::from("/images/c.jpg"),
+                PathBuf::from("/images/d.jpg"),
+                PathBuf::from("/images/a.jpg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn slideshow_files_of_source_without_blocked_images() {
+        let entries = vec![
+            entry(15, "https://a.jpg", Some("/images/bing/a.jpg")),
+            entry_from_source(15, "https://b.jpg", Some("/images/apod/b.jpg"), "apod"),
+            entry(16, "https://c.jpg", Some("/images/bing/c.jpg")),
+            entry(16, "https://d.jpg", Some("/images/bing/d.jpg")),
+            entry_from_source(16, "https://e.jpg", Some("/images/apod/e.jpg"), "apod"),
+        ];
+        assert_eq!(
+            slideshow_files(
+                &entries,
+                "bing",
+                &["https://c.jpg".to_owned()],
+                date(2025, 10, 15)
+            ),
+            vec![
+                PathBuf::from("/images/bing/d.jpg"),
+                PathBuf::from("/images/bing/a.jpg"),
+            ]
+        );
+        assert_eq!(
+            slideshow_files(&entries, "apod", &[], date(2025, 10, 15)),
+            vec![
+                PathBuf::from("/images/apod/e.jpg"),
+                PathBuf::from("/images/apod/b.jpg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn since_counts_today() {
+        let settings = SlideshowSettings {
+            enabled: true,
+            interval: Duration::from_secs(60),
+            days: 3,
+        };
+        assert_eq!(settings.since(date(2025, 10, 17)), date(2025, 10, 15));
+    }
+
+    #[test]
+    fn slideshow_cycles_through_files() {
+        let mut slideshow = Slideshow::new(vec!["a.jpg".into(), "b.jpg".into()]);
+        assert_eq!(slideshow.current(), Some(Path::new("a.jpg")));
+        assert_eq!(slideshow.advance(), Some(Path::new("b.jpg")));
+        assert_eq!(slideshow.advance(), Some(Path::new("a.jpg")));
+    }
+
+    #[test]
+    fn slideshow_starting_at_file() {
+        let files = vec!["a.jpg".into(), "b.jpg".into(), "c.jpg".into()];
+        let mut slideshow = Slideshow::starting_at(files.clone(), Path::new("b.jpg"));
+        assert_eq!(slideshow.current(), Some(Path::new("b.jpg")));
+        assert_eq!(slideshow.advance(), Some(Path::new("c.jpg")));
+        let slideshow = Slideshow::starting_at(files, Path::new("d.jpg"));
+        assert_eq!(slideshow.current(), Some(Path::new("a.jpg")));
+    }
+
+    #[test]
+    fn empty_slideshow() {
+        let mut slideshow = Slideshow::default();
+        assert_eq!(slideshow.current(), None);
+        assert_eq!(slideshow.advance(), None);
+    }
+}
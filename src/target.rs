@@ -0,0 +1,156 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Set wallpapers on the desktop background, the lock screen, or both.
+
+use gnome_app_utils::portal::wallpaper::SetOn;
+use gtk::gio::{self, prelude::*};
+
+/// Where to set wallpapers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WallpaperTarget {
+    /// Set wallpapers on the desktop background and the lock screen.
+    #[default]
+    Both,
+    /// Set wallpapers on the desktop background only.
+    Background,
+    /// Set wallpapers on the lock screen only.
+    LockScreen,
+}
+
+impl WallpaperTarget {
+    fn from_id(id: &str) -> Self {
+        match id {
+            "background" => Self::Background,
+            "lock-screen" => Self::LockScreen,
+            _ => Self::Both,
+        }
+    }
+
+    /// The identifier of this target, as stored in settings.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Both => "both",
+            Self::Background => "background",
+            Self::LockScreen => "lock-screen",
+        }
+    }
+
+    /// Whether this target includes the desktop background.
+    pub fn includes_background(self) -> bool {
+        self != Self::LockScreen
+    }
+
+    /// Whether this target includes the lock screen.
+    pub fn includes_lock_screen(self) -> bool {
+        self != Self::Background
+    }
+
+    /// Where to ask the wallpaper portal to set wallpapers.
+    pub fn set_on(self) -> SetOn {
+        match self {
+            Self::Both => SetOn::Both,
+            Self::Background => SetOn::Background,
+            Self::LockScreen => SetOn::Lockscreen,
+        }
+    }
+}
+
+/// Where automatic updates set wallpapers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WallpaperTargets {
+    /// Where to set wallpapers.
+    pub target: WallpaperTarget,
+    /// The identifier of the source for the lock screen.
+    ///
+    /// An empty identifier uses the image of the desktop background on the
+    /// lock screen as well.
+    pub lock_screen_source: String,
+}
+
+impl WallpaperTargets {
+    /// Read wallpaper targets from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        Self {
+            target: WallpaperTarget::from_id(&settings.string("wallpaper-target")),
+            lock_screen_source: settings.string("lock-screen-source").into(),
+        }
+    }
+
+    /// The source for a separate image on the lock screen.
+    ///
+    /// Return `None` if the lock screen uses the same image as the desktop
+    /// background, or if we do not set wallpapers on both.
+    pub fn separate_lock_screen_source(&self) -> Option<&str> {
+        (self.target == WallpaperTarget::Both && !self.lock_screen_source.is_empty())
+            .then_some(self.lock_screen_source.as_str())
+    }
+
+    /// Where to set the image of automatic updates.
+    ///
+    /// If the lock screen gets a separate image, set the image on the desktop
+    /// background only.
+    pub fn automatic_target(&self) -> WallpaperTarget {
+        if self.separate_lock_screen_source().is_some() {
+            WallpaperTarget::Background
+        } else {
+            self.target
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identifiers of all wallpaper targets, as stored in settings.
+    const WALLPAPER_TARGETS: [&str; 3] = ["both", "background", "lock-screen"];
+
+    #[test]
+    fn target_from_id() {
+        assert_eq!(
+            WALLPAPER_TARGETS.map(WallpaperTarget::from_id),
+            [
+                WallpaperTarget::Both,
+                WallpaperTarget::Background,
+                WallpaperTarget::LockScreen
+            ]
+        );
+        assert_eq!(WallpaperTarget::from_id("foo"), WallpaperTarget::Both);
+        for id in WALLPAPER_TARGETS {
+            assert_eq!(WallpaperTarget::from_id(id).id(), id);
+        }
+    }
+
+    #[test]
+    fn same_image_on_lock_screen() {
+        let targets = WallpaperTargets::default();
+        assert_eq!(targets.separate_lock_screen_source(), None);
+        assert_eq!(targets.automatic_target(), WallpaperTarget::Both);
+    }
+
+    #[test]
+    fn separate_lock_screen_source() {
+        let targets = WallpaperTargets {
+            target: WallpaperTarget::Both,
+            lock_screen_source: "apod".into(),
+        };
+        assert_eq!(targets.separate_lock_screen_source(), Some("apod"));
+        assert_eq!(targets.automatic_target(), WallpaperTarget::Background);
+    }
+
+    #[test]
+    fn lock_screen_source_requires_both_targets() {
+        for target in [WallpaperTarget::Background, WallpaperTarget::LockScreen] {
+            let targets = WallpaperTargets {
+                target,
+                lock_screen_source: "apod".into(),
+            };
+            assert_eq!(targets.separate_lock_screen_source(), None);
+            assert_eq!(targets.automatic_target(), target);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Set wallpapers through different backends.
+//!
+//! The wallpaper portal works on GNOME and KDE, but does nothing on desktops
+//! without a portal implementation for wallpapers, e.g. sway or Hyprland.  For
+//! these desktops users can run a custom command to set wallpapers instead.
+
+use std::fmt::Debug;
+use std::future::Future;
+use std::path::Path;
+
+use gnome_app_utils::futures::future::LocalBoxFuture;
+use gnome_app_utils::portal::window::PortalWindowHandle;
+use gtk::gio::{self, prelude::*};
+
+use crate::config::G_LOG_DOMAIN;
+use crate::target::WallpaperTarget;
+
+mod command;
+mod gsettings;
+mod portal;
+#[cfg(test)]
+pub mod testutil;
+
+pub use command::Command;
+pub use gsettings::GSettings;
+pub use portal::Portal;
+
+/// A way to set wallpapers.
+pub trait WallpaperSetter {
+    /// The identifier of this backend, for logging.
+    fn id(&self) -> &str;
+
+    /// Set the image at `file` as wallpaper on `target`.
+    fn set_wallpaper<'a>(
+        &'a self,
+        file: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>>;
+
+    /// Whether this backend can set separate wallpapers for light and dark style.
+    fn supports_dark_variant(&self) -> bool {
+        false
+    }
+
+    /// Whether this backend can span a wallpaper across all monitors.
+    fn supports_spanning(&self) -> bool {
+        false
+    }
+
+    /// Set `light` and `dark` as wallpapers for light and dark style on `target`.
+    ///
+    /// Backends which do not [support dark variants](Self::supports_dark_variant)
+    /// set `light` only.
+    fn set_wallpaper_variants<'a>(
+        &'a self,
+        light: &'a Path,
+        _dark: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        self.set_wallpaper(light, target)
+    }
+}
+
+impl Debug for dyn WallpaperSetter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WallpaperSetter({})", self.id())
+    }
+}
+
+/// The backend to set wallpapers with, as configured in settings.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum WallpaperBackend {
+    /// Use the wallpaper portal.
+    #[default]
+    Portal,
+    /// Write the GNOME background settings directly.
+    GSettings,
+    /// Run a command built from the given template.
+    Command(String),
+}
+
+impl WallpaperBackend {
+    /// Read the wallpaper backend from `settings`.
+    pub fn from_settings(settings: &gio::Settings) -> Self {
+        match settings.string("wallpaper-backend").as_str() {
+            "gsettings" => Self::GSettings,
+            "command" => Self::Command(settings.string("wallpaper-command").into()),
+            _ => Self::Portal,
+        }
+    }
+}
+
+/// Create the wallpaper setter configured in `settings`.
+///
+/// Use `connection` and the window handle from `parent_window` for the
+/// portal; we only create a window handle if we actually use the portal.
+///
+/// If `spanned` is true, tell the desktop to span wallpapers across all
+/// monitors, if the backend supports this.
+pub async fn setter_from_settings<F>(
+    settings: &gio::Settings,
+    connection: &gio::DBusConnection,
+    parent_window: F,
+    spanned: bool,
+) -> Box<dyn WallpaperSetter>
+where
+    F: Future<Output = PortalWindowHandle>,
+{
+    match WallpaperBackend::from_settings(settings) {
+        WallpaperBackend::Portal => Box::new(Portal::new(connection.clone(), parent_window.await)),
+        WallpaperBackend::GSettings => Box::new(GSettings::new(spanned)),
+        WallpaperBackend::Command(template) => Box::new(Command::new(template)),
+    }
+}
+
+/// Set the image at `file` as wallpaper on `target` with `setter`.
+///
+/// # Errors
+///
+/// Return an error if `setter` failed to set the wallpaper.
+pub async fn set_wallpaper(
+    setter: &dyn WallpaperSetter,
+    file: &Path,
+    target: WallpaperTarget,
+) -> Result<(), glib::Error> {
+    glib::info!(
+        "Setting wallpaper on {} to {} with {}",
+        target.id(),
+        file.display(),
+        setter.id()
+    );
+    let result = setter.set_wallpaper(file, target).await;
+    if let Err(error) = &result {
+        glib::warn!("Backend {} failed to set wallpaper: {error}", setter.id());
+    }
+    result
+}
+
+/// Set `light` and `dark` variants of a wallpaper on `target` with `setter`.
+///
+/// If `setter` can't set separate wallpapers for light and dark style, set
+/// the variant for the current style, i.e. `dark` if `dark_style` is true.
+///
+/// # Errors
+///
+/// Return an error if `setter` failed to set the wallpaper.
+pub async fn set_wallpaper_variants(
+    setter: &dyn WallpaperSetter,
+    light: &Path,
+    dark: &Path,
+    target: WallpaperTarget,
+    dark_style: bool,
+) -> Result<(), glib::Error> {
+    if !setter.supports_dark_variant() {
+        let file = if dark_style { dark } else { light };
+        return set_wallpaper(setter, file, target).await;
+    }
+    glib::info!(
+        "Setting wallpaper on {} to {} and {} for dark style with {}",
+        target.id(),
+        light.display(),
+        dark.display(),
+        setter.id()
+    );
+    let result = setter.set_wallpaper_variants(light, dark, target).await;
+    if let Err(error) = &result {
+        glib::warn!("Backend {} failed to set wallpaper: {error}", setter.id());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::async_test;
+
+    use super::testutil::FakeSetter;
+    use super::*;
+
+    #[async_test]
+    async fn set_wallpaper_with_backend() {
+        let setter = FakeSetter::default();
+        set_wallpaper(&setter, Path::new("/a.jpg"), WallpaperTarget::Both)
+            .await
+            .unwrap();
+        set_wallpaper(&setter, Path::new("/b.jpg"), WallpaperTarget::LockScreen)
+            .await
+            .unwrap();
+        assert_eq!(
+            *setter.wallpapers.borrow(),
+            vec![
+                (Path::new("/a.jpg").to_owned(), WallpaperTarget::Both),
+                (Path::new("/b.jpg").to_owned(), WallpaperTarget::LockScreen),
+            ]
+        );
+    }
+
+    #[async_test]
+    async fn set_wallpaper_fails() {
+        let setter = FakeSetter {
+            fail: true,
+            ..FakeSetter::default()
+        };
+        let result = set_wallpaper(&setter, Path::new("/a.jpg"), WallpaperTarget::Both).await;
+        assert!(result.is_err());
+        assert!(setter.wallpapers.borrow().is_empty());
+    }
+
+    #[async_test]
+    async fn set_variants_for_current_style() {
+        let setter = FakeSetter::default();
+        let (light, dark) = (Path::new("/light.jpg"), Path::new("/dark.jpg"));
+        set_wallpaper_variants(&setter, light, dark, WallpaperTarget::Both, false)
+            .await
+            .unwrap();
+        set_wallpaper_variants(&setter, light, dark, WallpaperTarget::Both, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            *setter.wallpapers.borrow(),
+            vec![
+                (light.to_owned(), WallpaperTarget::Both),
+                (dark.to_owned(), WallpaperTarget::Both),
+            ]
+        );
+        assert!(setter.dark_wallpapers.borrow().is_empty());
+    }
+
+    #[async_test]
+    async fn set_both_variants() {
+        let setter = FakeSetter {
+            dark_variants: true,
+            ..FakeSetter::default()
+        };
+        let (light, dark) = (Path::new("/light.jpg"), Path::new("/dark.jpg"));
+        set_wallpaper_variants(&setter, light, dark, WallpaperTarget::Background, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            *setter.wallpapers.borrow(),
+            vec![(light.to_owned(), WallpaperTarget::Background)]
+        );
+        assert_eq!(*setter.dark_wallpapers.borrow(), vec![dark.to_owned()]);
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Set wallpapers by running a command.
+//!
+//! The command is a template in shell syntax, e.g. `swww img {file}`, with
+//! the following placeholders:
+//!
+//! - `{file}`: The path of the image.
+//! - `{uri}`: The URI of the image.
+//! - `{target}`: Where to set the image, i.e. `both`, `background` or
+//!   `lock-screen`.
+//!
+//! We do not run the command through a shell; use `sh -c '…'` explicitly for
+//! pipes or multiple commands.  Inside a Flatpak sandbox, prefix the command
+//! with `flatpak-spawn --host` to run it on the host.
+
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::config::G_LOG_DOMAIN;
+use crate::target::WallpaperTarget;
+
+use super::WallpaperSetter;
+
+/// Build the arguments of the command `template` for `file` and `target`.
+///
+/// # Errors
+///
+/// Return an error if `template` is empty or not valid shell syntax, or if
+/// `target` is the lock screen and `template` does not tell the command about
+/// the target.
+fn command_arguments(
+    template: &str,
+    file: &Path,
+    target: WallpaperTarget,
+) -> Result<Vec<OsString>, glib::Error> {
+    if template.trim().is_empty() {
+        return Err(glib::Error::new(
+            IOErrorEnum::InvalidArgument,
+            "No wallpaper command configured",
+        ));
+    }
+    if target == WallpaperTarget::LockScreen && !template.contains("{target}") {
+        return Err(glib::Error::new(
+            IOErrorEnum::NotSupported,
+            "Wallpaper command does not support the lock screen",
+        ));
+    }
+    let path = file.to_string_lossy();
+    let uri = gio::File::for_path(file).uri();
+    Ok(glib::shell_parse_argv(template)?
+        .into_iter()
+        .map(|argument| {
+            if argument == "{file}" {
+                // Pass the path as is, in case it's not valid unicode
+                file.as_os_str().to_owned()
+            } else {
+                argument
+                    .to_string_lossy()
+                    .replace("{file}", &path)
+                    .replace("{uri}", &uri)
+                    .replace("{target}", target.id())
+                    .into()
+            }
+        })
+        .collect())
+}
+
+/// Set wallpapers by running a command.
+pub struct Command {
+    template: String,
+}
+
+impl Command {
+    /// Run commands built from `template`.
+    pub fn new(template: String) -> Self {
+        Self { template }
+    }
+
+    async fn run(&self, file: &Path, target: WallpaperTarget) -> Result<(), glib::Error> {
+        let arguments = command_arguments(&self.template, file, target)?;
+        glib::info!(
+            "Running wallpaper command {} for {}",
+            self.template,
+            file.display()
+        );
+        let argv = arguments
+            .iter()
+            .map(OsString::as_os_str)
+            .collect::<Vec<&OsStr>>();
+        gio::Subprocess::newv(&argv, gio::SubprocessFlags::NONE)?
+            .wait_check_future()
+            .await
+    }
+}
+
+impl WallpaperSetter for Command {
+    fn id(&self) -> &str {
+        "command"
+    }
+
+    fn set_wallpaper<'a>(
+        &'a self,
+        file: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        self.run(file, target).boxed_local()
+    }
+
+    /// Commands get the composed wallpaper for all monitors; whether it
+    /// spans all monitors is up to the command.
+    fn supports_spanning(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glib::async_test;
+
+    use super::*;
+
+    #[test]
+    fn arguments_with_placeholders() {
+        let file = Path::new("/images/bing/a b.jpg");
+        assert_eq!(
+            command_arguments("swww img {file}", file, WallpaperTarget::Both).unwrap(),
+            vec!["swww", "img", "/images/bing/a b.jpg"]
+        );
+        assert_eq!(
+            command_arguments(
+                "set-bg --uri={uri} --on '{target}'",
+                file,
+                WallpaperTarget::Background
+            )
+            .unwrap(),
+            vec![
+                "set-bg",
+                "--uri=file:///images/bing/a%20b.jpg",
+                "--on",
+                "background"
+            ]
+        );
+    }
+
+    #[test]
+    fn arguments_without_template() {
+        let error =
+            command_arguments("  ", Path::new("/a.jpg"), WallpaperTarget::Both).unwrap_err();
+        assert!(error.matches(IOErrorEnum::InvalidArgument));
+    }
+
+    #[test]
+    fn arguments_for_lock_screen_need_target() {
+        let file = Path::new("/a.jpg");
+        let error =
+            command_arguments("swww img {file}", file, WallpaperTarget::LockScreen).unwrap_err();
+        assert!(error.matches(IOErrorEnum::NotSupported));
+        assert_eq!(
+            command_arguments("set-bg {target} {file}", file, WallpaperTarget::LockScreen).unwrap(),
+            vec!["set-bg", "lock-screen", "/a.jpg"]
+        );
+    }
+
+    #[test]
+    fn arguments_with_invalid_syntax() {
+        assert!(
+            command_arguments(
+                "swww img '{file}",
+                Path::new("/a.jpg"),
+                WallpaperTarget::Both
+            )
+            .is_err()
+        );
+    }
+
+    #[async_test]
+    async fn run_command() {
+        let file = Path::new("/a.jpg");
+        Command::new("test {file} = /a.jpg".into())
+            .set_wallpaper(file, WallpaperTarget::Both)
+            .await
+            .unwrap();
+        assert!(
+            Command::new("test {file} = /b.jpg".into())
+                .set_wallpaper(file, WallpaperTarget::Both)
+                .await
+                .is_err()
+        );
+    }
+}
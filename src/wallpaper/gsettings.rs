@@ -0,0 +1,162 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Set wallpapers by writing GNOME background settings directly.
+//!
+//! This works for desktops which read GNOME background settings but have no
+//! wallpaper portal, e.g. older GNOME releases or Budgie.  It does not work
+//! inside a Flatpak sandbox, because the sandbox has its own settings.
+
+use std::path::Path;
+
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::{self, IOErrorEnum, prelude::*};
+
+use crate::config::G_LOG_DOMAIN;
+use crate::target::WallpaperTarget;
+
+use super::WallpaperSetter;
+
+/// The schema for the desktop background.
+const BACKGROUND_SCHEMA: &str = "org.gnome.desktop.background";
+
+/// The schema for the lock screen.
+const SCREENSAVER_SCHEMA: &str = "org.gnome.desktop.screensaver";
+
+/// The picture option to span a wallpaper across all monitors.
+const SPANNED: &str = "spanned";
+
+/// Get settings for `schema_id`.
+///
+/// Unlike [`gio::Settings::new`] return an error instead of aborting if the
+/// schema does not exist.
+fn settings_for_schema(schema_id: &str) -> Result<gio::Settings, glib::Error> {
+    let schema = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(schema_id, true))
+        .ok_or_else(|| {
+            glib::Error::new(
+                IOErrorEnum::NotSupported,
+                &format!("Settings schema {schema_id} not installed"),
+            )
+        })?;
+    Ok(gio::Settings::new_full(
+        &schema,
+        None::<&gio::SettingsBackend>,
+        None,
+    ))
+}
+
+/// Set `uri` and `dark_uri` as pictures in the settings for `schema_id`.
+///
+/// Only set `dark_uri` if the schema has a picture for dark style.  If
+/// `spanned` is true span the pictures across all monitors; otherwise undo
+/// spanning from earlier calls.
+fn set_picture_uri(
+    schema_id: &str,
+    uri: &str,
+    dark_uri: &str,
+    spanned: bool,
+) -> Result<(), glib::Error> {
+    let settings = settings_for_schema(schema_id)?;
+    if settings
+        .settings_schema()
+        .is_some_and(|schema| schema.has_key("picture-options"))
+    {
+        if spanned {
+            settings
+                .set_string("picture-options", SPANNED)
+                .map_err(|error| {
+                    glib::Error::new(
+                        IOErrorEnum::Failed,
+                        &format!("Failed to set {schema_id} picture-options: {error}"),
+                    )
+                })?;
+        } else if settings.string("picture-options") == SPANNED {
+            settings.reset("picture-options");
+        }
+    }
+    let mut keys = vec![("picture-uri", uri)];
+    if settings
+        .settings_schema()
+        .is_some_and(|schema| schema.has_key("picture-uri-dark"))
+    {
+        keys.push(("picture-uri-dark", dark_uri));
+    }
+    for (key, uri) in keys {
+        settings.set_string(key, uri).map_err(|error| {
+            glib::Error::new(
+                IOErrorEnum::Failed,
+                &format!("Failed to set {schema_id} {key}: {error}"),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Set wallpapers by writing GNOME background settings.
+pub struct GSettings {
+    spanned: bool,
+}
+
+impl GSettings {
+    /// Set wallpapers in GNOME background settings.
+    ///
+    /// If `spanned` is true, span wallpapers across all monitors.
+    pub fn new(spanned: bool) -> Self {
+        Self { spanned }
+    }
+
+    fn set_wallpaper_files(
+        &self,
+        light: &Path,
+        dark: &Path,
+        target: WallpaperTarget,
+    ) -> Result<(), glib::Error> {
+        let uri = gio::File::for_path(light).uri();
+        let dark_uri = gio::File::for_path(dark).uri();
+        if target.includes_background() {
+            glib::info!("Setting {BACKGROUND_SCHEMA} to {uri} and {dark_uri}");
+            set_picture_uri(BACKGROUND_SCHEMA, &uri, &dark_uri, self.spanned)?;
+        }
+        if target != WallpaperTarget::Background {
+            glib::info!("Setting {SCREENSAVER_SCHEMA} to {uri} and {dark_uri}");
+            set_picture_uri(SCREENSAVER_SCHEMA, &uri, &dark_uri, self.spanned)?;
+        }
+        gio::Settings::sync();
+        Ok(())
+    }
+}
+
+impl WallpaperSetter for GSettings {
+    fn id(&self) -> &str {
+        "gsettings"
+    }
+
+    fn set_wallpaper<'a>(
+        &'a self,
+        file: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        std::future::ready(self.set_wallpaper_files(file, file, target)).boxed_local()
+    }
+
+    fn supports_dark_variant(&self) -> bool {
+        true
+    }
+
+    fn supports_spanning(&self) -> bool {
+        true
+    }
+
+    fn set_wallpaper_variants<'a>(
+        &'a self,
+        light: &'a Path,
+        dark: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        std::future::ready(self.set_wallpaper_files(light, dark, target)).boxed_local()
+    }
+}
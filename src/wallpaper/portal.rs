@@ -0,0 +1,63 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Set wallpapers through the wallpaper portal.
+
+use std::path::Path;
+
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gnome_app_utils::portal::{wallpaper, window::PortalWindowHandle};
+use gtk::gio;
+
+use crate::target::WallpaperTarget;
+
+use super::WallpaperSetter;
+
+/// Set wallpapers through the wallpaper portal.
+pub struct Portal {
+    connection: gio::DBusConnection,
+    parent_window: PortalWindowHandle,
+}
+
+impl Portal {
+    /// Use the portal on `connection`, with the given `parent_window`.
+    pub fn new(connection: gio::DBusConnection, parent_window: PortalWindowHandle) -> Self {
+        Self {
+            connection,
+            parent_window,
+        }
+    }
+
+    async fn set_wallpaper_file(
+        &self,
+        file: &Path,
+        target: WallpaperTarget,
+    ) -> Result<(), glib::Error> {
+        wallpaper::set_wallpaper_file(
+            &self.connection,
+            &self.parent_window,
+            &gio::File::for_path(file),
+            wallpaper::Preview::NoPreview,
+            target.set_on(),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl WallpaperSetter for Portal {
+    fn id(&self) -> &str {
+        "portal"
+    }
+
+    fn set_wallpaper<'a>(
+        &'a self,
+        file: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        self.set_wallpaper_file(file, target).boxed_local()
+    }
+}
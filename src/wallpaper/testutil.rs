@@ -0,0 +1,64 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use gnome_app_utils::futures::{FutureExt, future::LocalBoxFuture};
+use gtk::gio::IOErrorEnum;
+
+use crate::target::WallpaperTarget;
+
+use super::WallpaperSetter;
+
+/// A fake wallpaper setter which records all wallpapers it sets.
+#[derive(Debug, Default)]
+pub struct FakeSetter {
+    /// Fail to set wallpapers.
+    pub fail: bool,
+    /// Support separate wallpapers for dark style.
+    pub dark_variants: bool,
+    /// All wallpapers set so far.
+    pub wallpapers: RefCell<Vec<(PathBuf, WallpaperTarget)>>,
+    /// All wallpapers for dark style set so far.
+    pub dark_wallpapers: RefCell<Vec<PathBuf>>,
+}
+
+impl WallpaperSetter for FakeSetter {
+    fn id(&self) -> &str {
+        "fake"
+    }
+
+    fn set_wallpaper<'a>(
+        &'a self,
+        file: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        let result = if self.fail {
+            Err(glib::Error::new(IOErrorEnum::Failed, "Fake failure"))
+        } else {
+            self.wallpapers.borrow_mut().push((file.to_owned(), target));
+            Ok(())
+        };
+        std::future::ready(result).boxed_local()
+    }
+
+    fn supports_dark_variant(&self) -> bool {
+        self.dark_variants
+    }
+
+    fn set_wallpaper_variants<'a>(
+        &'a self,
+        light: &'a Path,
+        dark: &'a Path,
+        target: WallpaperTarget,
+    ) -> LocalBoxFuture<'a, Result<(), glib::Error>> {
+        if !self.fail {
+            self.dark_wallpapers.borrow_mut().push(dark.to_owned());
+        }
+        self.set_wallpaper(light, target)
+    }
+}
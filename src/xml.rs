@@ -12,6 +12,7 @@ use quick_xml::{
 };
 
 pub mod rss;
+pub mod xmp;
 
 /// Read to start of next element.
 ///
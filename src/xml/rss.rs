@@ -10,6 +10,7 @@ use std::fmt::Display;
 
 use quick_xml::{
     NsReader,
+    events::BytesStart,
     name::{Namespace, ResolveResult},
 };
 
@@ -64,6 +65,38 @@ impl From<quick_xml::events::attributes::AttrError> for RssError {
 
 type Result<T> = std::result::Result<T, RssError>;
 
+/// The namespace of Media RSS elements.
+const MEDIA_RSS_NS: &[u8] = b"http://search.yahoo.com/mrss/";
+
+/// A media object referenced by an RSS item.
+///
+/// Denotes either a `media:content` element or an `enclosure` element.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RssMedia {
+    /// The URL of the media object.
+    pub url: String,
+    /// The MIME type of the media object, if known.
+    pub mime_type: Option<String>,
+    /// The type of the media object, e.g. `image` or `video`, if known.
+    pub medium: Option<String>,
+    /// The width of the media object in pixels, if known.
+    pub width: Option<u32>,
+}
+
+impl RssMedia {
+    /// Whether this media object possibly denotes an image.
+    ///
+    /// Return `false` only if type information exists, and denotes something
+    /// else than an image.
+    pub fn is_image(&self) -> bool {
+        match (&self.medium, &self.mime_type) {
+            (Some(medium), _) => medium == "image",
+            (None, Some(mime_type)) => mime_type.starts_with("image/"),
+            (None, None) => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RssItem {
     pub title: Option<String>,
@@ -71,6 +104,72 @@ pub struct RssItem {
     pub link: Option<String>,
     pub thumbnail: Option<String>,
     pub pubdate: Option<jiff::Zoned>,
+    /// All `media:content` elements of this item, including those in `media:group`.
+    pub media_content: Vec<RssMedia>,
+    /// The enclosure of this item.
+    pub enclosure: Option<RssMedia>,
+}
+
+fn read_attribute(
+    reader: &NsReader<&[u8]>,
+    start: &BytesStart,
+    name: &[u8],
+) -> Result<Option<String>> {
+    match start.try_get_attribute(name)? {
+        Some(value) => Ok(Some(
+            value
+                .decode_and_unescape_value(reader.decoder())?
+                .into_owned(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Read a media object from `start`, and skip over its content.
+///
+/// Return `None` if `start` has no URL.
+fn read_media(reader: &mut NsReader<&[u8]>, start: &BytesStart) -> Result<Option<RssMedia>> {
+    let media = read_attribute(reader, start, b"url")?
+        .map(|url| {
+            Ok::<_, RssError>(RssMedia {
+                url,
+                mime_type: read_attribute(reader, start, b"type")?,
+                medium: read_attribute(reader, start, b"medium")?,
+                width: read_attribute(reader, start, b"width")?.and_then(|w| w.parse().ok()),
+            })
+        })
+        .transpose()?;
+    reader.read_to_end(start.name())?;
+    Ok(media)
+}
+
+/// Read the thumbnail URL from `start` and skip over its content.
+fn read_thumbnail(reader: &mut NsReader<&[u8]>, start: &BytesStart) -> Result<Option<String>> {
+    let url = read_attribute(reader, start, b"url")?;
+    // Skip over the (empty) content of the thumbnail
+    reader.read_to_end(start.name())?;
+    Ok(url)
+}
+
+fn read_media_group(reader: &mut NsReader<&[u8]>, item: &mut RssItem) -> Result<()> {
+    while let Some(start) = read_to_start(reader)? {
+        let (ns, local_name) = reader.resolve_element(start.name());
+        match (ns, local_name.as_ref()) {
+            (ResolveResult::Bound(Namespace(MEDIA_RSS_NS)), b"content") => {
+                item.media_content.extend(read_media(reader, &start)?);
+            }
+            (ResolveResult::Bound(Namespace(MEDIA_RSS_NS)), b"thumbnail") => {
+                let thumbnail = read_thumbnail(reader, &start)?;
+                if item.thumbnail.is_none() {
+                    item.thumbnail = thumbnail;
+                }
+            }
+            _ => {
+                reader.read_to_end(start.name())?;
+            }
+        }
+    }
+    Ok(())
 }
 
 fn read_item(reader: &mut NsReader<&[u8]>) -> Result<RssItem> {
@@ -93,15 +192,19 @@ fn read_item(reader: &mut NsReader<&[u8]>) -> Result<RssItem> {
                 let date = jiff::fmt::rfc2822::parse(text.trim())?;
                 item.pubdate = Some(date);
             }
-            (ResolveResult::Bound(Namespace(b"http://search.yahoo.com/mrss/")), b"thumbnail") => {
-                if let Some(url) = start.try_get_attribute(b"url")? {
-                    item.thumbnail = Some(
-                        url.decode_and_unescape_value(reader.decoder())?
-                            .into_owned(),
-                    );
+            (ResolveResult::Unbound, b"enclosure") => {
+                item.enclosure = read_media(reader, &start)?;
+            }
+            (ResolveResult::Bound(Namespace(MEDIA_RSS_NS)), b"thumbnail") => {
+                if let Some(url) = read_thumbnail(reader, &start)? {
+                    item.thumbnail = Some(url);
                 }
-                // Skip over the (empty) content of the thumbnail
-                reader.read_to_end(start.name())?;
+            }
+            (ResolveResult::Bound(Namespace(MEDIA_RSS_NS)), b"content") => {
+                item.media_content.extend(read_media(reader, &start)?);
+            }
+            (ResolveResult::Bound(Namespace(MEDIA_RSS_NS)), b"group") => {
+                read_media_group(reader, &mut item)?;
             }
             // Skip over all elements we're not interested in
             _ => {
@@ -266,4 +369,95 @@ mod tests {
             "2025-04-20T04:00:00Z"
         );
     }
+
+    const MEDIA_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Photos</title>
+    <item>
+      <title>A lake</title>
+      <link>https://photos.example.com/lake</link>
+      <media:content url="https://photos.example.com/lake.mp4" type="video/mp4" medium="video" />
+      <media:content url="https://photos.example.com/lake_large.jpg" type="image/jpeg" width="4000" />
+      <media:thumbnail url="https://photos.example.com/lake_th.jpg" />
+      <enclosure url="https://photos.example.com/lake.jpg" type="image/jpeg" length="12345" />
+    </item>
+    <item>
+      <title>A mountain</title>
+      <media:group>
+        <media:content url="https://photos.example.com/mountain_small.jpg" medium="image" width="640" />
+        <media:content url="https://photos.example.com/mountain_large.jpg" medium="image" width="2048" />
+        <media:thumbnail url="https://photos.example.com/mountain_th.jpg" />
+      </media:group>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn read_media() {
+        let mut items = super::read_rss_channel(NsReader::from_str(MEDIA_XML))
+            .unwrap()
+            .collect::<super::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(items.len(), 2);
+
+        let mountain = items.pop().unwrap();
+        let lake = items.pop().unwrap();
+        assert_eq!(lake.title.as_deref(), Some("A lake"));
+        assert_eq!(
+            lake.thumbnail.as_deref(),
+            Some("https://photos.example.com/lake_th.jpg")
+        );
+        assert_eq!(
+            lake.media_content,
+            vec![
+                super::RssMedia {
+                    url: "https://photos.example.com/lake.mp4".into(),
+                    mime_type: Some("video/mp4".into()),
+                    medium: Some("video".into()),
+                    width: None,
+                },
+                super::RssMedia {
+                    url: "https://photos.example.com/lake_large.jpg".into(),
+                    mime_type: Some("image/jpeg".into()),
+                    medium: None,
+                    width: Some(4000),
+                }
+            ]
+        );
+        assert_eq!(
+            lake.media_content
+                .iter()
+                .map(super::RssMedia::is_image)
+                .collect::<Vec<_>>(),
+            vec![false, true]
+        );
+        assert_eq!(
+            lake.enclosure,
+            Some(super::RssMedia {
+                url: "https://photos.example.com/lake.jpg".into(),
+                mime_type: Some("image/jpeg".into()),
+                medium: None,
+                width: None,
+            })
+        );
+
+        assert_eq!(mountain.title.as_deref(), Some("A mountain"));
+        assert_eq!(
+            mountain.thumbnail.as_deref(),
+            Some("https://photos.example.com/mountain_th.jpg")
+        );
+        assert_eq!(
+            mountain
+                .media_content
+                .iter()
+                .map(|media| (media.url.as_str(), media.width))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://photos.example.com/mountain_small.jpg", Some(640)),
+                ("https://photos.example.com/mountain_large.jpg", Some(2048)),
+            ]
+        );
+        assert!(mountain.enclosure.is_none());
+    }
 }
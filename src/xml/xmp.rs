@@ -0,0 +1,185 @@
+// Copyright Sebastian Wiesner <sebastian@swsnr.de>
+//
+// Licensed under the EUPL
+//
+// See https://interoperable-europe.ec.europa.eu/collection/eupl/eupl-text-eupl-12
+
+//! Read Dublin Core metadata from XMP packets.
+
+use quick_xml::{
+    NsReader, Result,
+    events::Event,
+    name::{Namespace, ResolveResult},
+};
+
+use crate::xml::read_text;
+
+/// The namespace of Dublin Core elements.
+const DC_NS: &[u8] = b"http://purl.org/dc/elements/1.1/";
+
+/// The namespace of RDF elements.
+const RDF_NS: &[u8] = b"http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+
+/// Dublin Core metadata from an XMP packet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmpMetadata {
+    /// The `dc:title` of the resource.
+    pub title: Option<String>,
+    /// The `dc:description` of the resource.
+    pub description: Option<String>,
+    /// The `dc:rights` of the resource.
+    pub rights: Option<String>,
+    /// The first `dc:creator` of the resource.
+    pub creator: Option<String>,
+}
+
+/// Find an XMP packet embedded in `data`.
+///
+/// JPEG, PNG, WebP and most other image formats embed XMP as plain XML text,
+/// so we just look for the `x:xmpmeta` element.
+pub fn find_xmp_packet(data: &[u8]) -> Option<&[u8]> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+    let start = data
+        .windows(START.len())
+        .position(|window| window == START)?;
+    let rest = data.get(start..)?;
+    let end = rest.windows(END.len()).position(|window| window == END)?;
+    rest.get(..end + END.len())
+}
+
+/// Read the text of the first `rdf:li` of a property, or the text of the
+/// property itself.
+///
+/// Call after [`Event::Start`] of the property element, and consume the
+/// element up to and including its end.
+fn read_property_text(reader: &mut NsReader<&[u8]>) -> Result<Option<String>> {
+    let mut depth = 0_usize;
+    let mut text = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => {
+                let (ns, local_name) = reader.resolve_element(start.name());
+                if text.is_none()
+                    && ns == ResolveResult::Bound(Namespace(RDF_NS))
+                    && local_name.as_ref() == b"li"
+                {
+                    text = Some(read_text(reader)?);
+                } else {
+                    depth += 1;
+                }
+            }
+            Event::End(_) if depth == 0 => break,
+            Event::End(_) => depth -= 1,
+            Event::Text(value) if depth == 0 && text.is_none() => {
+                let value = value.unescape()?;
+                if !value.trim().is_empty() {
+                    text = Some(value.into_owned());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text
+        .map(|text| text.trim().to_owned())
+        .filter(|text| !text.is_empty()))
+}
+
+/// Read Dublin Core metadata from the given XMP `packet`.
+///
+/// # Errors
+///
+/// If the XMP packet is not valid XML.
+pub fn read_xmp(packet: &[u8]) -> Result<XmpMetadata> {
+    let mut reader = NsReader::from_reader(packet);
+    reader.config_mut().expand_empty_elements = true;
+    let mut metadata = XmpMetadata::default();
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => {
+                let (ns, local_name) = reader.resolve_element(start.name());
+                if ns == ResolveResult::Bound(Namespace(DC_NS)) {
+                    let property = match local_name.as_ref() {
+                        b"title" => &mut metadata.title,
+                        b"description" => &mut metadata.description,
+                        b"rights" => &mut metadata.rights,
+                        b"creator" => &mut metadata.creator,
+                        _ => {
+                            reader.read_to_end(start.name())?;
+                            continue;
+                        }
+                    };
+                    *property = read_property_text(&mut reader)?;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{XmpMetadata, find_xmp_packet, read_xmp};
+
+    const XMP: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:title>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">Harbour at dawn</rdf:li>
+          <rdf:li xml:lang="de">Hafen im Morgengrauen</rdf:li>
+        </rdf:Alt>
+      </dc:title>
+      <dc:description>
+        <rdf:Alt>
+          <rdf:li xml:lang="x-default">Fishing boats &amp; cranes in the morning mist.</rdf:li>
+        </rdf:Alt>
+      </dc:description>
+      <dc:creator>
+        <rdf:Seq>
+          <rdf:li>Jane Doe</rdf:li>
+          <rdf:li>John Doe</rdf:li>
+        </rdf:Seq>
+      </dc:creator>
+      <dc:format>image/jpeg</dc:format>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>"#;
+
+    #[test]
+    fn read_dublin_core() {
+        let metadata = read_xmp(XMP.as_bytes()).unwrap();
+        assert_eq!(
+            metadata,
+            XmpMetadata {
+                title: Some("Harbour at dawn".into()),
+                description: Some("Fishing boats & cranes in the morning mist.".into()),
+                rights: None,
+                creator: Some("Jane Doe".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn read_empty_packet() {
+        let metadata = read_xmp(br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"/>"#).unwrap();
+        assert_eq!(metadata, XmpMetadata::default());
+    }
+
+    #[test]
+    fn find_embedded_packet() {
+        let mut data = b"\xff\xd8\xff\xe1garbage http://ns.adobe.com/xap/1.0/\0".to_vec();
+        data.extend_from_slice(XMP.as_bytes());
+        data.extend_from_slice(b"\xff\xdbmore binary data");
+        assert_eq!(find_xmp_packet(&data), Some(XMP.as_bytes()));
+    }
+
+    #[test]
+    fn find_no_packet() {
+        assert_eq!(find_xmp_packet(b"\xff\xd8\xff\xdbbinary data"), None);
+        assert_eq!(find_xmp_packet(b"<x:xmpmeta unterminated"), None);
+    }
+}